@@ -1,7 +1,13 @@
 use corewa_rs::{
-    language::{read_champion, write_champion},
+    language::{compile_with_includes, compiler::CompileOptions, disassemble, include::FileSystem},
+    rules::RuleSet,
     spec::HEADER_SIZE,
 };
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+use structopt::StructOpt;
 
 fn main() {
     let exit_code = match run() {
@@ -16,16 +22,94 @@ fn main() {
 }
 
 fn run() -> Result<(), String> {
-    let champion =
-        read_champion(std::io::stdin()).map_err(|e| format!("Failed to read champion:\n{}", e))?;
+    let opts = Options::from_args();
+
+    let rules = match &opts.rules {
+        Some(path) => {
+            RuleSet::load(path).map_err(|e| format!("Failed to load the rule set:\n{}", e))?
+        }
+        None => RuleSet::default(),
+    };
+
+    match opts.command {
+        None => assemble(None, &rules, opts.wrap),
+        Some(Command::Assemble { source }) => assemble(source.as_deref(), &rules, opts.wrap),
+        Some(Command::Disassemble) => disassemble_champion(&rules),
+    }
+}
+
+/// The files included by a source read from stdin are relative to the current
+/// directory
+fn assemble(path: Option<&Path>, rules: &RuleSet, wrap_numbers: bool) -> Result<(), String> {
+    let source = match path {
+        Some(path) => std::fs::read_to_string(path),
+        None => {
+            let mut source = String::new();
+            std::io::stdin().read_to_string(&mut source).map(|_| source)
+        }
+    }
+    .map_err(|e| format!("Failed to read champion:\n{}", e))?;
+
+    let options = CompileOptions { wrap_numbers };
+    let compilation = compile_with_includes(&source, path, &FileSystem, rules, &options).map_err(
+        |diagnostics| {
+            let lines: Vec<_> = diagnostics.iter().map(ToString::to_string).collect();
+            format!("Failed to compile champion:\n{}", lines.join("\n"))
+        },
+    )?;
 
-    let champion_name = champion.name.clone();
+    for warning in &compilation.warnings {
+        eprintln!("{}", warning);
+    }
 
-    let size_written = write_champion(std::io::stdout(), champion)
+    std::io::stdout()
+        .write_all(&compilation.byte_code)
         .map_err(|e| format!("Failed to write champion:\n{}", e))?;
 
-    eprintln!("Successfully compiled '{}'", champion_name);
-    eprintln!("code section: {} bytes", size_written - HEADER_SIZE);
+    eprintln!("Successfully compiled '{}'", compilation.name);
+    eprintln!(
+        "code section: {} bytes",
+        compilation.byte_code.len() - HEADER_SIZE
+    );
 
     Ok(())
 }
+
+fn disassemble_champion(rules: &RuleSet) -> Result<(), String> {
+    let mut champion = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut champion)
+        .map_err(|e| format!("Failed to read champion:\n{}", e))?;
+
+    let source = disassemble(&champion, rules)
+        .map_err(|e| format!("Failed to disassemble champion:\n{}", e))?;
+
+    std::io::stdout()
+        .write_all(source.as_bytes())
+        .map_err(|e| format!("Failed to write source:\n{}", e))
+}
+
+/// Assembles a champion source and writes it to stdout
+#[derive(Debug, StructOpt)]
+struct Options {
+    /// Rule set preset file
+    #[structopt(long = "rules")]
+    rules: Option<String>,
+    /// Truncates the numbers that don't fit their encoding instead of failing
+    #[structopt(long = "wrap")]
+    wrap: bool,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Compiles a source file, or the source read from stdin, the default
+    Assemble {
+        /// Read instead of stdin, the files it includes being relative to it
+        #[structopt(parse(from_os_str))]
+        source: Option<PathBuf>,
+    },
+    /// Turns the compiled champion read from stdin back into source
+    Disassemble,
+}
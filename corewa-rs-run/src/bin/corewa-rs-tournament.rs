@@ -0,0 +1,313 @@
+use corewa_rs::{
+    rules::RuleSet,
+    tournament::{run_tournament, Entrant, TournamentConfig, TournamentResults},
+};
+use corewa_rs_run::load_champion;
+use serde::Serialize;
+use std::{error::Error, fs, path::PathBuf, str::FromStr};
+use structopt::StructOpt;
+
+fn main() {
+    let exit_code = match run() {
+        Ok(_) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    };
+
+    std::process::exit(exit_code)
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let opts = Options::from_args();
+
+    let rules = match &opts.rules {
+        Some(path) => RuleSet::load(path)?,
+        None => RuleSet::default(),
+    };
+
+    let mut paths: Vec<_> = fs::read_dir(&opts.directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension == "cor" || extension == "s")
+    });
+    paths.sort();
+
+    let entrants = paths
+        .iter()
+        .map(|path| {
+            let name = path
+                .file_stem()
+                .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+            Ok(Entrant {
+                name,
+                champion: load_champion(path, &rules)?,
+            })
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    if entrants.len() < 2 {
+        return Err("Require at least 2 champions".into());
+    }
+
+    let mut config = TournamentConfig {
+        rules,
+        cycle_limit: opts.cycle_limit,
+        max_players: opts.max_players,
+        seeds: opts.seeds,
+        ..TournamentConfig::default()
+    };
+    if let Some(threads) = opts.threads {
+        config.threads = threads;
+    }
+
+    let results = run_tournament(&entrants, &config)?;
+    let report = Report::new(&entrants, &results);
+
+    match opts.format {
+        Format::Text => print_text(&report),
+        Format::Csv => print_csv(&report),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    standings: Vec<StandingReport<'a>>,
+    matchups: Vec<MatchupReport<'a>>,
+    matches: Vec<MatchReport<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct StandingReport<'a> {
+    rank: usize,
+    name: &'a str,
+    elo: f64,
+    wins: u32,
+    losses: u32,
+    draws: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct MatchupReport<'a> {
+    name: &'a str,
+    opponent: &'a str,
+    wins: u32,
+    losses: u32,
+    draws: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct MatchReport<'a> {
+    seats: Vec<&'a str>,
+    /// `None` for a draw
+    winner: Option<&'a str>,
+    cycles: u32,
+    seed: Option<u64>,
+}
+
+impl<'a> Report<'a> {
+    fn new(entrants: &'a [Entrant], results: &TournamentResults) -> Self {
+        let name = |entrant: usize| entrants[entrant].name.as_str();
+
+        Self {
+            standings: results
+                .standings
+                .iter()
+                .zip(1..)
+                .map(|(standing, rank)| StandingReport {
+                    rank,
+                    name: name(standing.entrant),
+                    elo: (standing.elo * 10.0).round() / 10.0,
+                    wins: standing.wins,
+                    losses: standing.losses,
+                    draws: standing.draws,
+                })
+                .collect(),
+            matchups: results
+                .matchups
+                .iter()
+                .map(|matchup| MatchupReport {
+                    name: name(matchup.entrant),
+                    opponent: name(matchup.opponent),
+                    wins: matchup.wins,
+                    losses: matchup.losses,
+                    draws: matchup.draws,
+                })
+                .collect(),
+            matches: results
+                .matches
+                .iter()
+                .map(|result| MatchReport {
+                    seats: result.seats.iter().map(|&seat| name(seat)).collect(),
+                    winner: result.winner.map(name),
+                    cycles: result.cycles,
+                    seed: result.seed,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn print_text(report: &Report<'_>) {
+    let width = report
+        .standings
+        .iter()
+        .map(|standing| standing.name.len())
+        .max()
+        .unwrap_or(0)
+        .max(8);
+
+    println!(
+        "{:>4}  {:<width$}  {:>7}  {:>5}  {:>5}  {:>5}",
+        "Rank",
+        "Champion",
+        "Elo",
+        "W",
+        "L",
+        "D",
+        width = width
+    );
+    for standing in &report.standings {
+        println!(
+            "{:>4}  {:<width$}  {:>7.1}  {:>5}  {:>5}  {:>5}",
+            standing.rank,
+            standing.name,
+            standing.elo,
+            standing.wins,
+            standing.losses,
+            standing.draws,
+            width = width
+        );
+    }
+
+    if report.matchups.is_empty() {
+        return;
+    }
+
+    // Rows and columns follow the ranking
+    println!();
+    println!("Matchups (W-L-D of the row against the column)");
+    let names: Vec<_> = report
+        .standings
+        .iter()
+        .map(|standing| standing.name)
+        .collect();
+    let cell_width = names
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(8);
+
+    print!("{:<width$}", "", width = width);
+    for name in &names {
+        print!("  {:>width$}", name, width = cell_width);
+    }
+    println!();
+    for row in &names {
+        print!("{:<width$}", row, width = width);
+        for column in &names {
+            let cell = report
+                .matchups
+                .iter()
+                .find(|matchup| matchup.name == *row && matchup.opponent == *column)
+                .map_or_else(
+                    || String::from("-"),
+                    |matchup| format!("{}-{}-{}", matchup.wins, matchup.losses, matchup.draws),
+                );
+            print!("  {:>width$}", cell, width = cell_width);
+        }
+        println!();
+    }
+}
+
+/// Prints the standings, then the matchups after an empty line
+fn print_csv(report: &Report<'_>) {
+    println!("rank,name,elo,wins,losses,draws");
+    for standing in &report.standings {
+        println!(
+            "{},{},{:.1},{},{},{}",
+            standing.rank,
+            csv_field(standing.name),
+            standing.elo,
+            standing.wins,
+            standing.losses,
+            standing.draws
+        );
+    }
+
+    println!();
+    println!("name,opponent,wins,losses,draws");
+    for matchup in &report.matchups {
+        println!(
+            "{},{},{},{},{}",
+            csv_field(matchup.name),
+            csv_field(matchup.opponent),
+            matchup.wins,
+            matchup.losses,
+            matchup.draws
+        );
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(&[',', '"', '\n'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[derive(Debug)]
+enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "Invalid format '{}', expected text, csv or json",
+                s
+            )),
+        }
+    }
+}
+
+/// Plays every champion of a directory against each other and ranks them
+#[derive(Debug, StructOpt)]
+struct Options {
+    /// Directory of champion files, either compiled (.cor) or assembly sources (.s)
+    directory: PathBuf,
+    /// Also plays every combination of up to this many champions, from 2 to 4
+    #[structopt(short = "p", long = "players", default_value = "2")]
+    max_players: usize,
+    /// Number of matches running in parallel, defaults to the number of CPUs
+    #[structopt(short = "j", long = "threads")]
+    threads: Option<usize>,
+    /// Stops every match after this many cycles, which makes it a draw
+    #[structopt(short = "l", long = "cycle-limit")]
+    cycle_limit: Option<u32>,
+    /// Plays every match once per seed, with randomized starting conditions.
+    /// Can be repeated
+    #[structopt(long = "seed", number_of_values = 1)]
+    seeds: Vec<u64>,
+    /// Rule set preset file
+    #[structopt(long = "rules")]
+    rules: Option<String>,
+    /// Output format: text, csv or json
+    #[structopt(long = "format", default_value = "text")]
+    format: Format,
+}
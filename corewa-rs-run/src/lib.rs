@@ -0,0 +1,25 @@
+//! Helpers shared by the command line runners
+
+use corewa_rs::{
+    language::{self, include::FileSystem},
+    rules::RuleSet,
+};
+use std::{error::Error, fs, path::Path};
+
+/// Reads a `.cor` file, or assembles a `.s` file
+pub fn load_champion(path: impl AsRef<Path>, rules: &RuleSet) -> Result<Vec<u8>, Box<dyn Error>> {
+    let path = path.as_ref();
+    let is_source = path.extension().is_some_and(|extension| extension == "s");
+
+    if is_source {
+        let champion =
+            language::read_champion_with_includes(fs::File::open(path)?, Some(path), &FileSystem)
+                .map_err(|e| format!("Failed to read champion '{}':\n{}", path.display(), e))?;
+        let mut binary = Vec::new();
+        language::write_champion(&mut binary, champion, rules)
+            .map_err(|e| format!("Failed to write champion '{}':\n{}", path.display(), e))?;
+        Ok(binary)
+    } else {
+        Ok(fs::read(path)?)
+    }
+}
@@ -0,0 +1,219 @@
+use corewa_rs::{
+    rules::RuleSet,
+    spec::MAX_PLAYERS,
+    vm::{
+        memory::{Owner, NO_OWNER},
+        observer::{VmEvent, VmObserver},
+        outcome::EndReason,
+        types::{Pid, PlayerId},
+        VirtualMachine,
+    },
+};
+use corewa_rs_run::load_champion;
+use serde::Serialize;
+use std::{collections::HashMap, error::Error};
+use structopt::StructOpt;
+
+fn main() {
+    let exit_code = match run() {
+        Ok(_) => 0,
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    };
+
+    std::process::exit(exit_code)
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let opts = Options::from_args();
+
+    if opts.champion_files.is_empty() {
+        return Err("Require at least 1 champion".into());
+    }
+
+    let rules = match &opts.rules {
+        Some(path) => RuleSet::load(path)?,
+        None => RuleSet::default(),
+    };
+
+    let players = opts
+        .champion_files
+        .iter()
+        .enumerate()
+        .map(|(i, file_name)| Ok((i as PlayerId + 1, load_champion(file_name, &rules)?)))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+    let mut vm = VirtualMachine::with_rules(rules);
+    vm.load_players(&players)?;
+
+    let mut peaks = ProcessPeaks::new(&vm);
+    let outcome = vm.run_to_completion_with(opts.cycle_limit, &mut peaks);
+
+    let mut coverages = [0; MAX_PLAYERS];
+    let mut unowned_cells = 0;
+    for &owner in vm.memory.owners.inner() {
+        match owner {
+            NO_OWNER => unowned_cells += 1,
+            owner => coverages[usize::from(owner)] += 1,
+        }
+    }
+
+    let report = Report {
+        winner: outcome.winner,
+        cycles: outcome.cycles,
+        end_reason: outcome.reason,
+        players: vm
+            .players
+            .iter()
+            .enumerate()
+            .map(|(idx, player)| PlayerReport {
+                id: player.id,
+                name: player.name.clone(),
+                last_live: outcome.last_lives[idx],
+                peak_processes: peaks.peaks[idx],
+                coverage: coverages[idx],
+            })
+            .collect(),
+        peak_processes: peaks.total_peak,
+        unowned_cells,
+        memory_size: vm.memory.size(),
+    };
+
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    winner: Option<PlayerId>,
+    cycles: u32,
+    #[serde(with = "EndReasonDef")]
+    end_reason: EndReason,
+    players: Vec<PlayerReport>,
+    /// The highest number of processes alive at the same time
+    peak_processes: u32,
+    unowned_cells: usize,
+    memory_size: usize,
+}
+
+/// Serializes [`EndReason`], which lives in a crate without serde
+#[derive(Serialize)]
+#[serde(remote = "EndReason", rename_all = "snake_case")]
+enum EndReasonDef {
+    AllProcessesKilled,
+    CycleLimitReached,
+}
+
+#[derive(Debug, Serialize)]
+struct PlayerReport {
+    id: PlayerId,
+    name: String,
+    last_live: u32,
+    peak_processes: u32,
+    /// The number of memory cells owned at the end of the match
+    coverage: usize,
+}
+
+fn print_report(report: &Report) {
+    let winner = report
+        .winner
+        .and_then(|id| report.players.iter().find(|player| player.id == id));
+    match winner {
+        Some(player) => println!("Winner: {} ({})", player.name, player.id),
+        None => println!("No winner"),
+    }
+
+    let reason = match report.end_reason {
+        EndReason::AllProcessesKilled => "all processes killed",
+        EndReason::CycleLimitReached => "cycle limit reached",
+    };
+    println!("Cycles: {} ({})", report.cycles, reason);
+    println!("Peak processes: {}", report.peak_processes);
+
+    let percent = |cells| 100.0 * cells as f64 / report.memory_size as f64;
+    for player in &report.players {
+        println!();
+        println!("Player {}: {}", player.id, player.name);
+        println!("  Last live:      {}", player.last_live);
+        println!("  Peak processes: {}", player.peak_processes);
+        println!(
+            "  Coverage:       {} ({:.1}%)",
+            player.coverage,
+            percent(player.coverage)
+        );
+    }
+    println!();
+    println!(
+        "Unowned memory: {} ({:.1}%)",
+        report.unowned_cells,
+        percent(report.unowned_cells)
+    );
+}
+
+/// Tracks the highest number of processes of each player during the match
+struct ProcessPeaks {
+    owners: HashMap<Pid, Owner>,
+    counts: [u32; MAX_PLAYERS],
+    peaks: [u32; MAX_PLAYERS],
+    total_peak: u32,
+}
+
+impl ProcessPeaks {
+    fn new(vm: &VirtualMachine) -> Self {
+        let owners = vm
+            .processes
+            .iter()
+            .map(|process| (process.pid, process.owner))
+            .collect();
+        Self {
+            owners,
+            counts: vm.process_count_by_owner,
+            peaks: vm.process_count_by_owner,
+            total_peak: vm.processes.len() as u32,
+        }
+    }
+}
+
+impl VmObserver for ProcessPeaks {
+    fn notify(&mut self, event: VmEvent) {
+        match event {
+            VmEvent::ForkSpawned { parent, child, .. } => {
+                if let Some(&owner) = self.owners.get(&parent) {
+                    self.owners.insert(child, owner);
+                    let idx = usize::from(owner);
+                    self.counts[idx] += 1;
+                    self.peaks[idx] = self.peaks[idx].max(self.counts[idx]);
+                    self.total_peak = self.total_peak.max(self.counts.iter().sum());
+                }
+            }
+            VmEvent::ProcessKilled { pid, owner, .. } => {
+                self.owners.remove(&pid);
+                self.counts[usize::from(owner)] -= 1;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Runs a match without any interface and prints its outcome
+#[derive(Debug, StructOpt)]
+struct Options {
+    /// Champion files, either compiled (.cor) or assembly sources (.s)
+    champion_files: Vec<String>,
+    /// Stops the match after this many cycles
+    #[structopt(short = "l", long = "cycle-limit")]
+    cycle_limit: Option<u32>,
+    /// Rule set preset file
+    #[structopt(long = "rules")]
+    rules: Option<String>,
+    /// Prints the outcome as JSON
+    #[structopt(long = "json")]
+    json: bool,
+}
@@ -0,0 +1,37 @@
+use std::process::Command;
+
+const SAMPLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../corewa-rs/tests/vm/samples");
+
+fn run(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_corewa-rs-run"))
+        .arg(format!("{}/zork.cor", SAMPLES))
+        .arg(format!("{}/helltrain.cor", SAMPLES))
+        .args(args)
+        .output()
+        .expect("Failed to run corewa-rs-run");
+    assert!(output.status.success(), "{:?}", output);
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn text_report() {
+    let report = run(&[]);
+
+    assert!(report.starts_with("Winner: helltrain (2)\nCycles: 24367 (all processes killed)\n"));
+    assert!(report.contains("\nPlayer 1: zork\n"));
+    assert!(report.contains("\nPlayer 2: helltrain\n"));
+}
+
+#[test]
+fn json_report() {
+    let report: serde_json::Value = serde_json::from_str(&run(&["--json", "-l", "5000"])).unwrap();
+
+    assert_eq!(report["winner"], 1);
+    assert_eq!(report["cycles"], 5000);
+    assert_eq!(report["end_reason"], "cycle_limit_reached");
+    assert_eq!(report["players"][0]["name"], "zork");
+    assert_eq!(report["players"][1]["name"], "helltrain");
+    assert_eq!(report["players"][1]["last_live"], 4995);
+    assert_eq!(report["memory_size"], 4096);
+}
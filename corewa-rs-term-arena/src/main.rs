@@ -1,7 +1,23 @@
 mod util;
 
-use corewa_rs::vm::{memory::NO_OWNER, VirtualMachine};
-use std::{error::Error, fs, io};
+use corewa_rs::{
+    rules::RuleSet,
+    vm::{
+        debugger::{Breakpoint, BreakpointHit},
+        loader::PlacementMode,
+        memory::NO_OWNER,
+        outcome::TickStatus,
+        replay::{ReplayReader, ReplayWriter},
+        types::PlayerId,
+        VirtualMachine,
+    },
+};
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    str::FromStr,
+};
 use structopt::StructOpt;
 use termion::{event::Key, input::MouseTerminal, raw::IntoRawMode, screen::AlternateScreen};
 use tui::{
@@ -31,22 +47,39 @@ const PLAYER_COLORS: [Color; 4] = [Color::Yellow, Color::Magenta, Color::Green,
 fn run() -> Result<(), Box<dyn Error>> {
     let opts = Options::from_args();
 
-    if opts.champion_files.is_empty() {
+    if opts.champion_files.is_empty() && opts.replay.is_none() {
         panic!("Require at least 1 champion");
     }
 
-    let mut vm = VirtualMachine::new();
+    let rules = match &opts.rules {
+        Some(path) => RuleSet::load(path)?,
+        None => RuleSet::default(),
+    };
+
     let players: Vec<_> = opts
         .champion_files
         .iter()
         .enumerate()
         .map(|(i, file_name)| {
-            let champion = fs::read(&file_name)?;
+            let champion = fs::read(file_name)?;
             Ok((i as i32 + 1, champion))
         })
         .collect::<Result<_, io::Error>>()?;
 
-    vm.load_players(&players);
+    let (mut vm, mut playback) = match &opts.replay {
+        Some(path) => {
+            let replay = ReplayReader::read_from(BufReader::new(File::open(path)?))?;
+            (
+                VirtualMachine::from_snapshot(replay.state()),
+                Playback::Replay(Box::new(replay)),
+            )
+        }
+        None => {
+            let vm = new_vm(&opts, &rules, &players)?;
+            let recorder = new_recorder(&opts, &vm, &players)?;
+            (vm, Playback::Live(recorder))
+        }
+    };
 
     let stdout = io::stdout().into_raw_mode()?;
     let stdout = MouseTerminal::from(stdout);
@@ -58,41 +91,52 @@ fn run() -> Result<(), Box<dyn Error>> {
     let events = Events::new();
 
     let mut controls = Controls::default();
+    let mut last_hits = Vec::new();
 
     loop {
-        if controls.running {
-            terminal.draw(|f| {
-                let chunks = Layout::default()
-                    .direction(Direction::Horizontal)
-                    .margin(1)
-                    .constraints([Constraint::Percentage(20), Constraint::Percentage(80)].as_ref())
-                    .split(f.size());
-
-                let block = Block::default().borders(Borders::ALL).title("VM state");
-                f.render_widget(block, chunks[0]);
-
-                let info_chunks = Layout::default()
-                    .direction(Direction::Vertical)
-                    .margin(1)
-                    .constraints([Constraint::Percentage(20), Constraint::Percentage(80)].as_ref())
-                    .split(chunks[0]);
-
-                let block = Block::default().borders(Borders::BOTTOM);
-                f.render_widget(block, info_chunks[0]);
-
-                let block = Block::default().borders(Borders::ALL).title("Memory");
-                f.render_widget(block, chunks[1]);
-
-                let vm_chunks = Layout::default()
-                    .constraints([Constraint::Percentage(100)].as_ref())
-                    .margin(1)
-                    .split(chunks[1]);
-
-                f.render_widget(&controls, info_chunks[0]);
-                f.render_widget(VMStateWidget(&vm), info_chunks[1]);
-                f.render_widget(MemoryWidget(&vm, opts.chr), vm_chunks[0]);
-            })?;
-        }
+        terminal.draw(|f| {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .margin(1)
+                .constraints([Constraint::Percentage(20), Constraint::Percentage(80)].as_ref())
+                .split(f.size());
+
+            let block = Block::default().borders(Borders::ALL).title("VM state");
+            f.render_widget(block, chunks[0]);
+
+            let info_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .margin(1)
+                .constraints(
+                    [
+                        Constraint::Percentage(15),
+                        Constraint::Percentage(45),
+                        Constraint::Percentage(40),
+                    ]
+                    .as_ref(),
+                )
+                .split(chunks[0]);
+
+            let block = Block::default().borders(Borders::BOTTOM);
+            f.render_widget(block, info_chunks[0]);
+
+            let chat_block = Block::default().borders(Borders::TOP).title("Chat");
+            let chat_area = chat_block.inner(info_chunks[2]);
+            f.render_widget(chat_block, info_chunks[2]);
+
+            let block = Block::default().borders(Borders::ALL).title("Memory");
+            f.render_widget(block, chunks[1]);
+
+            let vm_chunks = Layout::default()
+                .constraints([Constraint::Percentage(100)].as_ref())
+                .margin(1)
+                .split(chunks[1]);
+
+            f.render_widget(&controls, info_chunks[0]);
+            f.render_widget(VMStateWidget(&vm, &last_hits), info_chunks[1]);
+            f.render_widget(ChatWidget(&vm), chat_area);
+            f.render_widget(MemoryWidget(&vm, opts.chr), vm_chunks[0]);
+        })?;
 
         match events.next()? {
             Event::Key(key) => match key {
@@ -102,12 +146,36 @@ fn run() -> Result<(), Box<dyn Error>> {
                     '-' => controls.slower(),
                     ' ' => controls.toggle_running(),
                     'r' => {
-                        vm = VirtualMachine::new();
-                        vm.load_players(&players)
+                        match &mut playback {
+                            Playback::Live(recorder) => {
+                                vm = new_vm(&opts, &rules, &players)?;
+                                *recorder = new_recorder(&opts, &vm, &players)?;
+                            }
+                            Playback::Replay(_) => playback.seek(&mut vm, 0)?,
+                        }
+                        last_hits.clear();
+                    }
+                    // Deletes the breakpoints that stopped the VM
+                    'd' => {
+                        for hit in last_hits.drain(..) {
+                            vm.breakpoints.remove(hit.id);
+                        }
                     }
                     _ => (),
                 },
-                Key::Right => vm.tick(),
+                Key::Right => {
+                    if let Some(hits) = playback.forward(&mut vm)? {
+                        last_hits = hits;
+                    }
+                }
+                Key::Left | Key::PageUp | Key::PageDown => {
+                    let target = match key {
+                        Key::Left => vm.cycles.saturating_sub(1),
+                        Key::PageUp => vm.cycles.saturating_sub(SEEK_STEP),
+                        _ => vm.cycles + SEEK_STEP,
+                    };
+                    playback.seek(&mut vm, target)?
+                }
                 _ => (),
             },
             Event::Mouse(_ev) => {
@@ -116,16 +184,128 @@ fn run() -> Result<(), Box<dyn Error>> {
             Event::Tick => {
                 if controls.running {
                     for _ in 0..controls.speed {
-                        vm.tick();
+                        if let Some(hits) = playback.forward(&mut vm)? {
+                            last_hits = hits;
+                            controls.running = false;
+                            break;
+                        }
                     }
                 }
             }
         }
     }
 
+    if let Playback::Live(Some(recorder)) = playback {
+        recorder.finish()?;
+    }
+
     Ok(())
 }
 
+/// Number of cycles skipped by the page up and page down keys
+const SEEK_STEP: u32 = 1000;
+
+/// Checkpoints kept to seek backwards in a live match, a checkpoint being
+/// taken every 256 cycles
+const HISTORY_CHECKPOINTS: usize = 1024;
+
+type Recorder = Box<ReplayWriter<BufWriter<File>>>;
+
+/// Where the displayed states come from
+enum Playback {
+    /// A match simulated by the VM, optionally being recorded
+    Live(Option<Recorder>),
+    Replay(Box<ReplayReader>),
+}
+
+impl Playback {
+    /// Moves one cycle forward.
+    /// Returns the breakpoint hits if the playback should be paused
+    fn forward(&mut self, vm: &mut VirtualMachine) -> io::Result<Option<Vec<BreakpointHit>>> {
+        match self {
+            Playback::Live(recorder) => {
+                let status = match recorder {
+                    Some(recorder) => recorder.tick(vm)?,
+                    None => vm.tick(),
+                };
+                match status {
+                    TickStatus::Break(hits) => Ok(Some(hits)),
+                    _ => Ok(None),
+                }
+            }
+            Playback::Replay(replay) => {
+                if replay.step() {
+                    vm.restore(replay.state());
+                    Ok(None)
+                } else {
+                    Ok(Some(Vec::new()))
+                }
+            }
+        }
+    }
+
+    fn seek(&mut self, vm: &mut VirtualMachine, cycle: u32) -> io::Result<()> {
+        match self {
+            Playback::Live(_) if cycle < vm.cycles => {
+                vm.step_back(vm.cycles - cycle);
+            }
+            Playback::Live(_) => {
+                while vm.cycles < cycle && !vm.is_over() {
+                    if self.forward(vm)?.is_some() {
+                        break;
+                    }
+                }
+            }
+            Playback::Replay(replay) => vm.restore(replay.seek(cycle)),
+        }
+        Ok(())
+    }
+}
+
+fn new_recorder(
+    opts: &Options,
+    vm: &VirtualMachine,
+    players: &[(PlayerId, Vec<u8>)],
+) -> Result<Option<Recorder>, Box<dyn Error>> {
+    match &opts.record {
+        Some(path) => {
+            let out = BufWriter::new(File::create(path)?);
+            Ok(Some(Box::new(ReplayWriter::new(out, vm, players)?)))
+        }
+        None => Ok(None),
+    }
+}
+
+fn new_vm(
+    opts: &Options,
+    rules: &RuleSet,
+    players: &[(PlayerId, Vec<u8>)],
+) -> Result<VirtualMachine, Box<dyn Error>> {
+    let mut builder = VirtualMachine::builder().rules(rules.clone());
+    for (idx, (player_id, champion)) in players.iter().enumerate() {
+        let address = opts
+            .addresses
+            .iter()
+            .rev()
+            .find(|address| address.player == idx + 1)
+            .map(|address| address.addr);
+        builder = builder.player_with_address(*player_id, champion.clone(), address);
+    }
+    if let Some(seed) = opts.random_placement {
+        builder = builder.placement_mode(PlacementMode::Random {
+            seed,
+            min_separation: opts.min_separation,
+        });
+    }
+
+    let mut vm = builder.build()?;
+    vm.enable_history(HISTORY_CHECKPOINTS);
+    for breakpoint in &opts.breakpoints {
+        vm.add_breakpoint(breakpoint.clone())?;
+    }
+    Ok(vm)
+}
+
 struct Controls {
     speed: u16,
     running: bool,
@@ -167,7 +347,7 @@ impl Widget for &Controls {
     }
 }
 
-struct VMStateWidget<'a>(&'a VirtualMachine);
+struct VMStateWidget<'a>(&'a VirtualMachine, &'a [BreakpointHit]);
 
 impl Widget for VMStateWidget<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
@@ -196,6 +376,69 @@ impl Widget for VMStateWidget<'_> {
             "Checks passed:  {}",
             vm.checks_without_cycle_decrement
         ));
+
+        if let Some(outcome) = vm.outcome() {
+            let winner = outcome
+                .winner
+                .and_then(|id| vm.players.iter().find(|player| player.id == id));
+
+            show_line(String::new());
+            match winner {
+                Some(player) => show_line(format!("Winner: {} ({})", player.name, player.id)),
+                None => show_line(String::from("No winner")),
+            }
+        }
+
+        if !self.1.is_empty() {
+            show_line(String::new());
+        }
+        for hit in self.1 {
+            if let Some(breakpoint) = vm.breakpoints.get(hit.id) {
+                show_line(format!("Break: {} (pid {})", breakpoint, hit.pid));
+            }
+        }
+    }
+}
+
+struct ChatWidget<'a>(&'a VirtualMachine);
+
+impl Widget for ChatWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let vm = &self.0;
+        let width = usize::from(area.width);
+
+        // Wrap every player's output into lines of the panel's width and only
+        // keep the most recent ones
+        let lines: Vec<_> = vm
+            .players
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, player)| {
+                let text = format!("{}: {}", player.name, vm.aff_text(idx));
+                let chars: Vec<_> = text.chars().map(printable).collect();
+                let color = PLAYER_COLORS[idx];
+
+                chars
+                    .chunks(width.max(1))
+                    .map(|line| (line.iter().collect::<String>(), color))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        let visible_count = lines.len().min(usize::from(area.height));
+        let visible_lines = &lines[lines.len() - visible_count..];
+
+        for ((line, color), y) in visible_lines.iter().zip(area.top()..) {
+            buf.set_string(area.left(), y, line, Style::default().fg(*color));
+        }
+    }
+}
+
+fn printable(chr: char) -> char {
+    if chr.is_control() {
+        ' '
+    } else {
+        chr
     }
 }
 
@@ -244,4 +487,47 @@ struct Options {
     champion_files: Vec<String>,
     #[structopt(short = "c", default_value = "▮")]
     chr: char,
+    /// Rule set preset file
+    #[structopt(long = "rules")]
+    rules: Option<String>,
+    /// Pauses the VM when a breakpoint fires: pc=ADDR, op=PID:OP,
+    /// write=START..END[:OWNER] or reg=rN:VALUE
+    #[structopt(short = "b", long = "break")]
+    breakpoints: Vec<Breakpoint>,
+    /// Loads a player at a given address: PLAYER:ADDR, players being
+    /// numbered from 1 in the order of the champion files
+    #[structopt(long = "at")]
+    addresses: Vec<LoadAddress>,
+    /// Places the players without an explicit address randomly, from this seed
+    #[structopt(long = "random-placement")]
+    random_placement: Option<u64>,
+    /// Minimum number of bytes between two randomly placed champions
+    #[structopt(long = "min-separation", default_value = "0")]
+    min_separation: usize,
+    /// Records the match in a replay file
+    #[structopt(long = "record")]
+    record: Option<String>,
+    /// Plays a replay file back instead of simulating a match
+    #[structopt(long = "replay", conflicts_with = "record")]
+    replay: Option<String>,
+}
+
+#[derive(Debug)]
+struct LoadAddress {
+    player: usize,
+    addr: usize,
+}
+
+impl FromStr for LoadAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid load address '{}', expected PLAYER:ADDR", s);
+
+        let (player, addr) = s.split_once(':').ok_or_else(invalid)?;
+        Ok(Self {
+            player: player.parse().map_err(|_| invalid())?,
+            addr: addr.parse().map_err(|_| invalid())?,
+        })
+    }
 }
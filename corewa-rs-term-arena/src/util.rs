@@ -55,11 +55,7 @@ impl Events {
                                 return;
                             }
                         }
-                        TermEvent::Mouse(ev) => {
-                            if tx.send(Event::Mouse(ev)).is_err() {
-                                return;
-                            }
-                        }
+                        TermEvent::Mouse(ev) if tx.send(Event::Mouse(ev)).is_err() => return,
                         _ => {}
                     }
                 }
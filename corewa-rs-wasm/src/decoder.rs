@@ -1,4 +1,5 @@
 use corewa_rs::{
+    rules::RuleSet,
     spec::*,
     vm::{
         decoder::{Decode, InstrDecodeError, InvalidOpCode},
@@ -42,15 +43,19 @@ pub enum DecodeResultFormat {
 }
 
 impl DecodeResult {
-    pub fn read(memory: &Memory, idx: usize) -> Self {
-        Self(Self::read_result(memory, idx))
+    pub fn read(memory: &Memory, idx: usize, rules: &RuleSet) -> Self {
+        Self(Self::read_result(memory, idx, rules))
     }
 
-    fn read_result(memory: &Memory, idx: usize) -> Result<Instruction, DecodeError> {
+    fn read_result(
+        memory: &Memory,
+        idx: usize,
+        rules: &RuleSet,
+    ) -> Result<Instruction, DecodeError> {
         let op = memory.decode_op(idx).map_err(DecodeError::InvalidOp)?;
 
         memory
-            .decode_instr(op, idx)
+            .decode_instr(op, idx, rules)
             .map_err(|err| DecodeError::OpOnly(op, err))
     }
 }
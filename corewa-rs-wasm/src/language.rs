@@ -1,26 +1,72 @@
-use corewa_rs::{language, spec};
-
+use corewa_rs::{
+    language::{self, compiler::CompileOptions, diagnostic, include},
+    rules::RuleSet,
+    spec,
+};
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 use wasm_bindgen::prelude::*;
 
+/// Throws a [`CompileError`] holding every diagnostic when the champion
+/// cannot be compiled
 #[wasm_bindgen]
 pub fn compile_champion(input: &str) -> Result<CompiledChampion, JsValue> {
-    compile_champion_impl(input).map_err(JsValue::from)
+    compile_champion_with_files(input, &VirtualFiles::default())
 }
 
-fn compile_champion_impl(input: &str) -> Result<CompiledChampion, CompileError> {
-    let parsed_champion = language::read_champion(input.as_bytes())?;
-    let name = parsed_champion.name.clone();
-    let comment = parsed_champion.comment.clone();
+/// [`compile_champion`], with the `.include` directives reading `files`
+#[wasm_bindgen]
+pub fn compile_champion_with_files(
+    input: &str,
+    files: &VirtualFiles,
+) -> Result<CompiledChampion, JsValue> {
+    match language::compile_with_includes(
+        input,
+        None,
+        &files.files,
+        &RuleSet::default(),
+        &CompileOptions::default(),
+    ) {
+        Ok(compilation) => Ok(CompiledChampion {
+            name: compilation.name,
+            comment: compilation.comment,
+            code_size: compilation.byte_code.len() - spec::HEADER_SIZE,
+            byte_code: compilation.byte_code,
+            warnings: compilation
+                .warnings
+                .into_iter()
+                .map(Diagnostic::from)
+                .collect(),
+        }),
+        Err(diagnostics) => Err(CompileError {
+            diagnostics: diagnostics.into_iter().map(Diagnostic::from).collect(),
+        }
+        .into()),
+    }
+}
 
-    let mut byte_code = Vec::new();
-    let size_written = language::write_champion(&mut byte_code, parsed_champion)?;
+/// The files that champions can include, by path
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct VirtualFiles {
+    files: HashMap<PathBuf, String>,
+}
+
+#[wasm_bindgen]
+impl VirtualFiles {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    Ok(CompiledChampion {
-        name,
-        comment,
-        byte_code,
-        code_size: size_written - spec::HEADER_SIZE,
-    })
+    /// Replaces the file at `path` if there is one
+    pub fn add(&mut self, path: &str, content: String) {
+        self.files
+            .insert(include::normalize(Path::new(path)), content);
+    }
 }
 
 #[wasm_bindgen]
@@ -28,6 +74,7 @@ pub struct CompiledChampion {
     name: String,
     comment: String,
     byte_code: Vec<u8>,
+    warnings: Vec<Diagnostic>,
     pub code_size: usize,
 }
 
@@ -44,6 +91,10 @@ impl CompiledChampion {
     pub fn byte_code(&self) -> Vec<u8> {
         self.byte_code.clone()
     }
+
+    pub fn warnings(&self) -> Vec<Diagnostic> {
+        self.warnings.clone()
+    }
 }
 
 #[wasm_bindgen]
@@ -66,61 +117,103 @@ impl Region {
     }
 }
 
+impl From<diagnostic::Span> for Region {
+    fn from(span: diagnostic::Span) -> Self {
+        let line = span.line as u32;
+        Region::new(
+            line,
+            span.columns.start as u32,
+            line,
+            span.columns.end as u32,
+        )
+    }
+}
+
 #[wasm_bindgen]
-pub struct CompileError {
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Diagnostic {
+    file: Option<String>,
     region: Option<Region>,
-    reason: String,
+    pub severity: Severity,
+    message: String,
+    call_sites: Vec<Region>,
+    constant_definitions: Vec<Region>,
 }
 
 #[wasm_bindgen]
-impl CompileError {
-    pub fn reason(&self) -> String {
-        self.reason.clone()
+impl Diagnostic {
+    pub fn message(&self) -> String {
+        self.message.clone()
     }
 
+    /// The included file the region is in, none for the main source
+    pub fn file(&self) -> Option<String> {
+        self.file.clone()
+    }
+
+    /// Champion wide diagnostics have no region
     pub fn region(&self) -> Option<Region> {
         self.region.clone()
     }
-}
 
-impl From<language::ReadError> for CompileError {
-    fn from(err: language::ReadError) -> Self {
-        let (region, reason) = match err {
-            language::ReadError::ParseError(e, line) => {
-                let line = line as u32;
-                let (col_start, opt_col_end) = language::error_range(&e);
-                let region = Region::new(
-                    line,
-                    col_start as u32,
-                    line,
-                    opt_col_end.unwrap_or(20000) as u32,
-                );
-                (Some(region), format!("{}", e))
-            }
-            language::ReadError::AssembleError(e) => {
-                (None, format!("Error while assembling champion: {}", e))
-            }
-            language::ReadError::IOError(e) => (None, format!("Unexpected IO error: {}", e)),
-        };
+    /// The macro invocations the region was expanded from, innermost first
+    pub fn call_sites(&self) -> Vec<Region> {
+        self.call_sites.clone()
+    }
 
-        Self { region, reason }
+    /// The definitions of the constants the error comes from, innermost first
+    pub fn constant_definitions(&self) -> Vec<Region> {
+        self.constant_definitions.clone()
     }
 }
 
-impl From<language::WriteError> for CompileError {
-    fn from(err: language::WriteError) -> Self {
-        let reason = match err {
-            language::WriteError::CompileError(e) => {
-                format!("Error while compiling champion: {}", e)
-            }
-            language::WriteError::IOError(e) => {
-                format!("Unexpected IO error: {}", e)
-            }
+impl From<diagnostic::Diagnostic> for Diagnostic {
+    fn from(diagnostic: diagnostic::Diagnostic) -> Self {
+        let file = diagnostic
+            .span
+            .as_ref()
+            .and_then(|span| span.file.as_deref().map(String::from));
+        let region = diagnostic.span.map(Region::from);
+        let severity = match diagnostic.severity {
+            diagnostic::Severity::Warning => Severity::Warning,
+            diagnostic::Severity::Error => Severity::Error,
         };
 
         Self {
-            region: None,
-            reason,
+            file,
+            region,
+            severity,
+            message: diagnostic.message,
+            call_sites: diagnostic
+                .call_sites
+                .into_iter()
+                .map(Region::from)
+                .collect(),
+            constant_definitions: diagnostic
+                .constant_definitions
+                .into_iter()
+                .map(Region::from)
+                .collect(),
         }
     }
 }
+
+#[wasm_bindgen]
+pub struct CompileError {
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[wasm_bindgen]
+impl CompileError {
+    /// Errors and warnings, sorted by position
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostics.clone()
+    }
+}
@@ -6,6 +6,7 @@ pub mod decoder;
 pub mod language;
 pub mod memory;
 pub mod process;
+pub mod replay;
 pub mod vm;
 
 mod utils;
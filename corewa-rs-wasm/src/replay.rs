@@ -0,0 +1,70 @@
+use corewa_rs::vm::{replay::ReplayReader, VirtualMachine as VMImpl};
+
+use super::vm::VirtualMachine;
+
+use wasm_bindgen::prelude::*;
+
+/// A recorded match that can be played back from any cycle
+#[wasm_bindgen]
+pub struct Replay(ReplayReader);
+
+#[wasm_bindgen]
+impl Replay {
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<Replay, JsValue> {
+        let reader =
+            ReplayReader::from_bytes(bytes).map_err(|e| JsValue::from(format!("{}", e)))?;
+        Ok(Self(reader))
+    }
+
+    pub fn first_cycle(&self) -> u32 {
+        self.0.first_cycle()
+    }
+
+    pub fn last_cycle(&self) -> u32 {
+        self.0.last_cycle()
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.0.header.players.len()
+    }
+
+    pub fn player_id(&self, player_idx: usize) -> Option<i32> {
+        self.0
+            .header
+            .players
+            .get(player_idx)
+            .map(|player| player.id)
+    }
+
+    /// The champion binary of a player, to reload the match in a live VM
+    pub fn champion(&self, player_idx: usize) -> Option<Vec<u8>> {
+        self.0
+            .header
+            .players
+            .get(player_idx)
+            .map(|player| player.champion.clone())
+    }
+
+    /// A VM in the current state of the replay, to be updated with `seek`
+    pub fn vm(&self) -> VirtualMachine {
+        VirtualMachine(VMImpl::from_snapshot(self.0.state()), Vec::new())
+    }
+
+    /// Puts `vm` in the last recorded state at or before `cycle`
+    pub fn seek(&mut self, vm: &mut VirtualMachine, cycle: u32) {
+        vm.0.restore(self.0.seek(cycle));
+        vm.1.clear();
+    }
+
+    /// Puts `vm` in the next recorded state, returns `false` at the end
+    pub fn step(&mut self, vm: &mut VirtualMachine) -> bool {
+        let stepped = self.0.step();
+        vm.0.restore(self.0.state());
+        vm.1.clear();
+        stepped
+    }
+
+    // Forces the replay to be dropped, see `VirtualMachine::release`
+    pub fn release(self) {}
+}
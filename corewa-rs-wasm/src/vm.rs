@@ -1,6 +1,14 @@
 use corewa_rs::{
     spec,
-    vm::{memory::NO_OWNER, types::*, VirtualMachine as VMImpl},
+    vm::{
+        builder::VmBuilder,
+        debugger::{Breakpoint, BreakpointId},
+        loader::PlacementMode,
+        memory::NO_OWNER,
+        outcome::{self, TickStatus},
+        types::*,
+        VirtualMachine as VMImpl,
+    },
 };
 
 use super::{
@@ -9,8 +17,13 @@ use super::{
 
 use wasm_bindgen::prelude::*;
 
+/// Checkpoints kept for the debugger to step back, a checkpoint being taken
+/// every 256 cycles
+const HISTORY_CHECKPOINTS: usize = 256;
+
+/// The VM along with the ids of the breakpoints that fired during the last tick
 #[wasm_bindgen]
-pub struct VirtualMachine(VMImpl);
+pub struct VirtualMachine(pub(crate) VMImpl, pub(crate) Vec<BreakpointId>);
 
 #[wasm_bindgen]
 impl VirtualMachine {
@@ -35,8 +48,44 @@ impl VirtualMachine {
     }
 
     pub fn tick(&mut self) -> bool {
-        self.0.tick();
-        self.0.processes.is_empty()
+        let status = self.0.tick();
+        self.record_hits(&status);
+        status.is_finished()
+    }
+
+    /// Runs at most `max_cycles` cycles, stopping early if a breakpoint fires
+    /// or the match ends. Returns the ids of the breakpoints that fired
+    pub fn run_until_break(&mut self, max_cycles: u32) -> Vec<BreakpointId> {
+        let status = self.0.run_until_break(Some(self.0.cycles + max_cycles));
+        self.record_hits(&status);
+        self.1.clone()
+    }
+
+    /// Adds a breakpoint described as `pc=ADDR`, `op=PID:OP`,
+    /// `write=START..END[:OWNER]` or `reg=rN:VALUE` and returns its id
+    pub fn add_breakpoint(&mut self, spec: &str) -> Result<BreakpointId, JsValue> {
+        let breakpoint: Breakpoint = spec.parse().map_err(|e| JsValue::from(format!("{}", e)))?;
+        self.0
+            .add_breakpoint(breakpoint)
+            .map_err(|e| JsValue::from(format!("{}", e)))
+    }
+
+    pub fn remove_breakpoint(&mut self, id: BreakpointId) -> bool {
+        self.0.breakpoints.remove(id).is_some()
+    }
+
+    /// The ids of the breakpoints that fired during the last tick
+    pub fn breakpoint_hits(&self) -> Vec<BreakpointId> {
+        self.1.clone()
+    }
+
+    /// Rewinds the VM `n` cycles back, returns the number of cycles actually rewound
+    pub fn step_back(&mut self, n: u32) -> u32 {
+        self.0.step_back(n)
+    }
+
+    pub fn outcome(&self) -> Option<MatchOutcome> {
+        self.0.outcome().map(MatchOutcome)
     }
 
     pub fn process_count(&self) -> usize {
@@ -54,6 +103,10 @@ impl VirtualMachine {
         }
     }
 
+    pub fn aff_output(&self, player_idx: usize) -> String {
+        self.0.aff_text(player_idx)
+    }
+
     pub fn processes_at(&self, idx: usize) -> ProcessCollection {
         let cell_processes = self.0.processes.iter().filter(|p| p.pc.addr() == idx);
 
@@ -61,13 +114,14 @@ impl VirtualMachine {
     }
 
     pub fn decode(&self, idx: usize) -> DecodeResult {
-        DecodeResult::read(&self.0.memory, idx)
+        DecodeResult::read(&self.0.memory, idx, &self.0.rules)
     }
 
     pub fn memory(&self) -> Memory {
         let mem = &self.0.memory;
 
         Memory {
+            size: mem.size(),
             values_ptr: mem.values.as_ptr(),
             ages_ptr: mem.ages.as_ptr(),
             owners_ptr: mem.owners.as_ptr(),
@@ -90,6 +144,13 @@ impl VirtualMachine {
         Coverages { values, unowned }
     }
 
+    fn record_hits(&mut self, status: &TickStatus) {
+        self.1.clear();
+        if let TickStatus::Break(hits) = status {
+            self.1.extend(hits.iter().map(|hit| hit.id));
+        }
+    }
+
     // Forces virtual machine to be dropped
     // This can help avoid leaks on the js-side, when building multiple VMs
     pub fn release(self) {}
@@ -109,28 +170,69 @@ impl Coverages {
 }
 
 #[wasm_bindgen]
-pub struct VMBuilder {
-    players: Vec<(PlayerId, Vec<u8>)>,
+pub struct MatchOutcome(outcome::MatchOutcome);
+
+#[wasm_bindgen]
+impl MatchOutcome {
+    pub fn winner(&self) -> Option<PlayerId> {
+        self.0.winner
+    }
+
+    pub fn cycles(&self) -> u32 {
+        self.0.cycles
+    }
+
+    pub fn last_live(&self, player_idx: usize) -> u32 {
+        *self.0.last_lives.get(player_idx).unwrap_or(&0)
+    }
+
+    pub fn cycle_limit_reached(&self) -> bool {
+        self.0.reason == outcome::EndReason::CycleLimitReached
+    }
 }
 
+#[wasm_bindgen]
+pub struct VMBuilder(VmBuilder);
+
 #[wasm_bindgen]
 impl VMBuilder {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Self {
-        Self {
-            players: Vec::with_capacity(4),
-        }
+        Self(VMImpl::builder())
+    }
+
+    pub fn with_rules(self, preset: &str) -> Result<VMBuilder, JsValue> {
+        let rules = preset
+            .parse()
+            .map_err(|e| JsValue::from(format!("Invalid rule set: {}", e)))?;
+        Ok(Self(self.0.rules(rules)))
+    }
+
+    /// Adds a player, loaded at `address` if specified
+    pub fn with_player(
+        self,
+        player_id: PlayerId,
+        champion: Vec<u8>,
+        address: Option<usize>,
+    ) -> VMBuilder {
+        Self(self.0.player_with_address(player_id, champion, address))
     }
 
-    pub fn with_player(mut self, player_id: PlayerId, champion: Vec<u8>) -> VMBuilder {
-        self.players.push((player_id, champion));
-        self
+    /// Places the players without an address randomly, from `seed`
+    pub fn with_random_placement(self, seed: u64, min_separation: usize) -> VMBuilder {
+        Self(self.0.placement_mode(PlacementMode::Random {
+            seed,
+            min_separation,
+        }))
     }
 
-    pub fn finish(self) -> VirtualMachine {
-        let mut vm = VMImpl::new();
-        vm.load_players(&self.players);
-        VirtualMachine(vm)
+    pub fn finish(self) -> Result<VirtualMachine, JsValue> {
+        let mut vm = self
+            .0
+            .build()
+            .map_err(|e| JsValue::from(format!("{}", e)))?;
+        vm.enable_history(HISTORY_CHECKPOINTS);
+        Ok(VirtualMachine(vm, Vec::new()))
     }
 }
 
@@ -4,18 +4,25 @@ use corewa_rs::vm::VirtualMachine;
 
 fn fight_cycles(players: &[(i32, Vec<u8>)]) -> u32 {
     let mut vm = VirtualMachine::new();
-    vm.load_players(players);
+    vm.load_players(players).unwrap();
 
-    while !vm.processes.is_empty() {
+    vm.run_to_completion(None).cycles
+}
+
+/// Runs the fight one tick at a time, without skipping the idle cycles
+fn ticked_fight_cycles(players: &[(i32, Vec<u8>)]) -> u32 {
+    let mut vm = VirtualMachine::new();
+    vm.load_players(players).unwrap();
+
+    while !vm.is_over() {
         vm.tick();
     }
-
     vm.cycles
 }
 
 fn fast_fight(c: &mut Criterion) {
     c.bench_function("zork alone", |b| {
-        b.iter(|| fight_cycles(&[(1, include_bytes!("../../champs/examples/zork.cor").to_vec())]))
+        b.iter(|| fight_cycles(&[(1, include_bytes!("../tests/vm/samples/zork.cor").to_vec())]))
     });
 }
 
@@ -33,6 +40,21 @@ fn fun_fight(c: &mut Criterion) {
     });
 }
 
+fn fast_forward(c: &mut Criterion) {
+    let players = [
+        (
+            1,
+            include_bytes!("../tests/vm/samples/sweepmaster.cor").to_vec(),
+        ),
+        (2, include_bytes!("../tests/vm/samples/kappa.cor").to_vec()),
+    ];
+
+    let mut group = c.benchmark_group("fast forward, sweepmaster + kappa");
+    group.bench_function("tick loop", |b| b.iter(|| ticked_fight_cycles(&players)));
+    group.bench_function("run to completion", |b| b.iter(|| fight_cycles(&players)));
+    group.finish();
+}
+
 fn slow_fight(c: &mut Criterion) {
     c.bench_function("maj_windows alone", |b| {
         b.iter(|| {
@@ -73,6 +95,6 @@ criterion_group!(benches, fast_fight);
 criterion_group! {
     name = slower_benches;
     config = Criterion::default().sample_size(10);
-    targets = best_fight, fun_fight, slow_fight
+    targets = best_fight, fun_fight, fast_forward, slow_fight
 }
 criterion_main!(benches, slower_benches);
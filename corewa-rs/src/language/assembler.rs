@@ -1,10 +1,29 @@
-use super::{parser::ParsedLine, types::Op};
+use super::{
+    diagnostic::Span,
+    parser::ParsedLine,
+    types::{Expr, Op},
+};
 
 #[derive(Debug)]
 pub struct Champion {
     pub name: String,
     pub comment: String,
     pub instructions: Vec<ParsedInstruction>,
+    /// The span of each instruction in the source.
+    /// Empty when the instructions were not read from a source
+    pub spans: Vec<Span>,
+    /// The macro invocations each instruction was expanded from, innermost
+    /// first. Empty when the instructions were not read from a source
+    pub call_sites: Vec<Vec<Span>>,
+}
+
+/// Where the label declaration and the statement of a line are in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineSpans {
+    pub label: Span,
+    pub statement: Span,
+    /// The macro invocations the line was expanded from, innermost first
+    pub call_sites: Vec<Span>,
 }
 
 #[derive(Default)]
@@ -12,6 +31,8 @@ pub struct ChampionBuilder {
     name: Option<String>,
     comment: Option<String>,
     instructions: Vec<ParsedInstruction>,
+    spans: Vec<Span>,
+    call_sites: Vec<Vec<Span>>,
 }
 
 impl ChampionBuilder {
@@ -31,22 +52,46 @@ impl ChampionBuilder {
         }
     }
 
-    fn add_instr(&mut self, instr_data: impl Into<ParsedInstruction>) -> &mut Self {
+    fn add_instr(
+        &mut self,
+        instr_data: impl Into<ParsedInstruction>,
+        span: &Span,
+        call_sites: &[Span],
+    ) -> &mut Self {
         self.instructions.push(instr_data.into());
+        self.spans.push(span.clone());
+        self.call_sites.push(call_sites.to_vec());
         self
     }
 
-    pub fn assemble(&mut self, parsed_line: ParsedLine) -> AssembleResult<&mut Self> {
+    pub fn assemble(
+        &mut self,
+        parsed_line: ParsedLine,
+        spans: &LineSpans,
+    ) -> AssembleResult<&mut Self> {
         use ParsedLine::*;
 
+        let LineSpans {
+            label: label_span,
+            statement,
+            call_sites,
+        } = spans;
+
         match parsed_line {
             ChampionName(name) => self.with_name(name),
             ChampionComment(comment) => self.with_comment(comment),
 
-            Code(bytes) => Ok(self.add_instr(bytes)),
-            Op(op) => Ok(self.add_instr(op)),
-            Label(label) => Ok(self.add_instr(label)),
-            LabelAndOp(label, op) => Ok(self.add_instr(label).add_instr(op)),
+            Code(values) => Ok(self.add_instr(values, statement, call_sites)),
+            Constant(name, value) => Ok(self.add_instr(
+                ParsedInstruction::Constant(name, value),
+                statement,
+                call_sites,
+            )),
+            Op(op) => Ok(self.add_instr(op, statement, call_sites)),
+            Label(label) => Ok(self.add_instr(label, label_span, call_sites)),
+            LabelAndOp(label, op) => Ok(self
+                .add_instr(label, label_span, call_sites)
+                .add_instr(op, statement, call_sites)),
 
             Empty => Ok(self),
         }
@@ -57,6 +102,8 @@ impl ChampionBuilder {
             name: self.name.ok_or(AssembleError::MissingName)?,
             comment: self.comment.ok_or(AssembleError::MissingComment)?,
             instructions: self.instructions,
+            spans: self.spans,
+            call_sites: self.call_sites,
         })
     }
 }
@@ -67,7 +114,10 @@ type AssembleResult<T> = Result<T, AssembleError>;
 pub enum ParsedInstruction {
     Label(String),
     Op(Op),
-    RawCode(Vec<u8>),
+    /// The values of a `.code` directive, each written on a byte
+    RawCode(Vec<i64>),
+    #[from(ignore)]
+    Constant(String, Expr),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -2,7 +2,7 @@ use super::{
     assembler::{Champion, ParsedInstruction},
     types::*,
 };
-use crate::spec::*;
+use crate::{rules::RuleSet, spec::*};
 
 use std::{
     collections::{hash_map::Entry, HashMap},
@@ -11,24 +11,104 @@ use std::{
 
 type CompileResult<T> = Result<T, CompileError>;
 
-pub fn compile_champion(out: impl Write + Seek, mut champion: Champion) -> CompileResult<usize> {
-    let mut state = State::new(out)?;
+/// Settings of the compiler that don't depend on the VM
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompileOptions {
+    /// Numbers and label offsets that don't fit their encoding are truncated
+    /// instead of rejected
+    pub wrap_numbers: bool,
+}
+
+/// Compiles with the default [`CompileOptions`], stopping at the first error
+pub fn compile_champion(
+    out: impl Write + Seek,
+    champion: Champion,
+    rules: &RuleSet,
+) -> CompileResult<usize> {
+    compile_champion_exhaustive(out, champion, rules, &CompileOptions::default()).map_err(
+        |errors| {
+            errors
+                .into_iter()
+                .next()
+                .expect("Compilation failed without errors")
+                .error
+        },
+    )
+}
+
+/// A compile error and the index of the instruction that caused it, if any
+#[derive(Debug)]
+pub struct LocatedError {
+    pub instruction: Option<usize>,
+    pub error: CompileError,
+    /// The instructions defining the constants that were being evaluated when
+    /// the error occurred, innermost first
+    pub constants: Vec<usize>,
+}
 
-    for instr in champion.instructions.drain(..) {
-        match instr {
-            ParsedInstruction::Op(op) => state.write_op(op)?,
-            ParsedInstruction::Label(label) => state.register_label(label)?,
-            ParsedInstruction::RawCode(bytes) => state.add_raw_code(&bytes)?,
+/// Same as [`compile_champion`], but carries on after an error to report all
+/// of them, in the order they were found. Only IO errors stop the compilation
+pub fn compile_champion_exhaustive(
+    out: impl Write + Seek,
+    mut champion: Champion,
+    rules: &RuleSet,
+    options: &CompileOptions,
+) -> Result<usize, Vec<LocatedError>> {
+    let at = |instruction| {
+        move |error: CompileError| {
+            vec![LocatedError {
+                instruction,
+                error,
+                constants: Vec::new(),
+            }]
+        }
+    };
+
+    let mut state = State::new(out, rules, options).map_err(at(None))?;
+    let mut errors = Vec::new();
+
+    for (idx, instr) in champion.instructions.drain(..).enumerate() {
+        state.current_instr = idx;
+        let result = match instr {
+            ParsedInstruction::Op(op) => state.write_op(op),
+            ParsedInstruction::Label(label) => state.register_label(label),
+            ParsedInstruction::RawCode(values) => state.add_raw_code(&values),
+            ParsedInstruction::Constant(name, value) => state.define_constant(name, value),
+        };
+        match result {
+            Ok(()) => (),
+            Err(error @ CompileError::IOError(_)) => return Err(at(Some(idx))(error)),
+            Err(error) => errors.push(LocatedError {
+                instruction: Some(idx),
+                error,
+                constants: Vec::new(),
+            }),
         }
     }
 
-    state.write_header(&champion)?;
-    state.resolve_labels()?;
+    match state.write_header(&champion) {
+        Ok(()) => (),
+        Err(error @ CompileError::IOError(_)) => return Err(at(None)(error)),
+        Err(error) => errors.push(LocatedError {
+            instruction: None,
+            error,
+            constants: Vec::new(),
+        }),
+    }
+    errors.extend(state.resolve_labels().map_err(|e| at(None)(e.into()))?);
+
+    if state.size > rules.champ_max_size {
+        errors.push(LocatedError {
+            instruction: None,
+            error: CompileError::ProgramTooLong(state.size, rules.champ_max_size),
+            constants: Vec::new(),
+        });
+    }
 
-    if state.size > CHAMP_MAX_SIZE {
-        Err(CompileError::ProgramTooLong(state.size))
-    } else {
+    if errors.is_empty() {
         Ok(state.size)
+    } else {
+        Err(errors)
     }
 }
 
@@ -68,7 +148,7 @@ fn pcb(op: &Op) -> u8 {
     pcb
 }
 
-fn op_spec(op: &Op) -> OpSpec {
+pub(super) fn op_type(op: &Op) -> OpType {
     use Op::*;
     use OpType as Ty;
 
@@ -92,26 +172,74 @@ fn op_spec(op: &Op) -> OpSpec {
         Aff   (..) => Ty::Aff,
     };
 
-    crate::spec::op_spec(op_type)
+    op_type
+}
+
+fn param_codes(op: &Op, op_spec: &OpSpec) -> [u8; MAX_PARAMS] {
+    let mut codes = [0; MAX_PARAMS];
+
+    if op_spec.has_pcb {
+        let pcb = pcb(op);
+        for (idx, code) in codes.iter_mut().enumerate().take(op_spec.param_count) {
+            *code = (pcb >> (6 - 2 * idx)) & 0b11;
+        }
+    } else {
+        // Ops without a pcb only take a direct parameter
+        codes[0] = DIR_PARAM_CODE;
+    }
+
+    codes
+}
+
+fn check_param_types(op: &Op, op_spec: &OpSpec) -> CompileResult<()> {
+    let codes = param_codes(op, op_spec);
+
+    for (idx, (code, mask)) in codes.iter().zip(op_spec.param_masks).enumerate() {
+        let flag = match *code {
+            REG_PARAM_CODE => T_REG,
+            DIR_PARAM_CODE => T_DIR,
+            IND_PARAM_CODE => T_IND,
+            _ => continue,
+        };
+        if mask & flag == 0 {
+            return Err(CompileError::ForbiddenParamType(op_type(op), idx + 1));
+        }
+    }
+
+    Ok(())
 }
 
-struct State<W> {
+struct State<'r, W> {
     out: W,
+    rules: &'r RuleSet,
+    options: &'r CompileOptions,
     size: usize,
     label_positions: HashMap<String, usize>,
+    /// The positions of the anonymous labels of each number, in order
+    anonymous_positions: HashMap<String, Vec<usize>>,
+    label_scope: LabelScope,
+    /// The value of each constant, with the instruction defining it
+    constants: HashMap<String, (Expr, usize)>,
     labels_to_fill: Vec<LabelPlaceholder>,
     current_op_pos: usize,
+    current_instr: usize,
 }
 
-impl<W: Write + Seek> State<W> {
-    fn new(mut out: W) -> CompileResult<Self> {
+impl<'r, W: Write + Seek> State<'r, W> {
+    fn new(mut out: W, rules: &'r RuleSet, options: &'r CompileOptions) -> CompileResult<Self> {
         out.seek(SeekFrom::Start(HEADER_SIZE as u64))?;
         Ok(Self {
             out,
+            rules,
+            options,
             size: 0,
             label_positions: HashMap::new(),
+            anonymous_positions: HashMap::new(),
+            label_scope: LabelScope::default(),
+            constants: HashMap::new(),
             labels_to_fill: Vec::new(),
             current_op_pos: 0,
+            current_instr: 0,
         })
     }
 
@@ -125,11 +253,16 @@ impl<W: Write + Seek> State<W> {
     }
 
     fn register_label(&mut self, label: String) -> CompileResult<()> {
-        match self.label_positions.entry(label) {
-            Entry::Occupied(entry) => {
-                let (label, _) = entry.remove_entry();
-                Err(CompileError::DuplicateLabel(label))
-            }
+        if is_anonymous_label(&label) {
+            self.anonymous_positions
+                .entry(label)
+                .or_default()
+                .push(self.size);
+            return Ok(());
+        }
+
+        match self.label_positions.entry(self.label_scope.declare(label)) {
+            Entry::Occupied(entry) => Err(CompileError::DuplicateLabel(entry.key().clone())),
             Entry::Vacant(entry) => {
                 entry.insert(self.size);
                 Ok(())
@@ -137,27 +270,157 @@ impl<W: Write + Seek> State<W> {
         }
     }
 
-    fn add_raw_code(&mut self, bytes: &[u8]) -> CompileResult<()> {
-        self.write(bytes)
+    /// The local labels of the value belong to the scope of the definition
+    fn define_constant(&mut self, name: String, mut value: Expr) -> CompileResult<()> {
+        self.label_scope.qualify_expr(&mut value);
+
+        match self.constants.entry(name) {
+            Entry::Occupied(entry) => Err(CompileError::DuplicateConstant(entry.key().clone())),
+            Entry::Vacant(entry) => {
+                entry.insert((value, self.current_instr));
+                Ok(())
+            }
+        }
+    }
+
+    fn add_raw_code(&mut self, values: &[i64]) -> CompileResult<()> {
+        self.check_numbers(values.iter().map(|&value| (value, 1)))?;
+
+        let bytes: Vec<u8> = values.iter().map(|&value| value as u8).collect();
+        self.write(&bytes)
     }
 
-    fn resolve_labels(&mut self) -> CompileResult<()> {
+    /// Evaluates the parameters that depend on labels or constants.
+    /// Returns the expressions that can't be evaluated and the values that
+    /// don't fit their encoding, with the instruction that uses them
+    fn resolve_labels(&mut self) -> Result<Vec<LocatedError>, IOError> {
+        let mut errors = Vec::new();
+
         for placeholder in &self.labels_to_fill {
-            let position = *self
-                .label_positions
-                .get(&placeholder.name)
-                .ok_or_else(|| CompileError::MissingLabel(placeholder.name.clone()))?;
+            let mut expanding = Vec::new();
+            let value = match self.evaluate(&placeholder.value, placeholder.op_pos, &mut expanding)
+            {
+                Ok(value) => value,
+                Err(error) => {
+                    errors.push(LocatedError {
+                        instruction: Some(placeholder.instr),
+                        error,
+                        constants: expanding
+                            .iter()
+                            .rev()
+                            .map(|&name| self.constants[name].1)
+                            .collect(),
+                    });
+                    continue;
+                }
+            };
+            if !self.options.wrap_numbers && !fits(value, placeholder.size) {
+                let error = match &placeholder.value {
+                    Expr::Label(label) => {
+                        CompileError::LabelOffsetOutOfRange(label.clone(), value, placeholder.size)
+                    }
+                    _ => CompileError::NumberOutOfRange(value, placeholder.size),
+                };
+                errors.push(LocatedError {
+                    instruction: Some(placeholder.instr),
+                    error,
+                    constants: Vec::new(),
+                });
+            }
             self.out.seek(SeekFrom::Start(
                 (HEADER_SIZE + placeholder.write_pos) as u64,
             ))?;
-            write_numeric(
-                &mut self.out,
-                ((position as isize) - (placeholder.op_pos as isize)) as u32,
-                placeholder.size,
-            )?;
+            write_numeric(&mut self.out, value as u32, placeholder.size)?;
         }
 
-        Ok(())
+        Ok(errors)
+    }
+
+    /// Labels evaluate to their offset from the op at `op_pos`, including the
+    /// ones of constants. `expanding` holds the constants being evaluated,
+    /// and still does after an error
+    fn evaluate<'s>(
+        &'s self,
+        expr: &'s Expr,
+        op_pos: usize,
+        expanding: &mut Vec<&'s str>,
+    ) -> CompileResult<i64> {
+        use CompileError::*;
+
+        match expr {
+            Expr::Number(n) => Ok(*n),
+            Expr::Label(label) => match anonymous_reference(label) {
+                Some((number, forward)) => self
+                    .anonymous_position(number, forward, op_pos)
+                    .ok_or_else(|| MissingAnonymousLabel(label.clone())),
+                None => self
+                    .label_positions
+                    .get(label)
+                    .copied()
+                    .ok_or_else(|| MissingLabel(label.clone())),
+            }
+            .map(|position| position as i64 - op_pos as i64),
+            Expr::Constant(name) => {
+                if expanding.contains(&name.as_str()) {
+                    return Err(CyclicConstant(name.clone()));
+                }
+                let (value, _) = self
+                    .constants
+                    .get(name)
+                    .ok_or_else(|| MissingConstant(name.clone()))?;
+
+                expanding.push(name);
+                let value = self.evaluate(value, op_pos, expanding)?;
+                expanding.pop();
+                Ok(value)
+            }
+            Expr::Neg(expr) => self
+                .evaluate(expr, op_pos, expanding)?
+                .checked_neg()
+                .ok_or(ArithmeticOverflow),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.evaluate(lhs, op_pos, expanding)?;
+                let rhs = self.evaluate(rhs, op_pos, expanding)?;
+                let result = match op {
+                    BinaryOp::Add => lhs.checked_add(rhs),
+                    BinaryOp::Sub => lhs.checked_sub(rhs),
+                    BinaryOp::Mul => lhs.checked_mul(rhs),
+                    BinaryOp::Div if rhs == 0 => return Err(DivisionByZero),
+                    BinaryOp::Div => lhs.checked_div(rhs),
+                };
+                result.ok_or(ArithmeticOverflow)
+            }
+        }
+    }
+
+    /// The closest anonymous label of `number` after the op at `op_pos`, or
+    /// before it, the op's own label included
+    fn anonymous_position(&self, number: &str, forward: bool, op_pos: usize) -> Option<usize> {
+        let mut positions = self.anonymous_positions.get(number)?.iter();
+
+        if forward {
+            positions.find(|&&position| position > op_pos).copied()
+        } else {
+            positions
+                .rev()
+                .find(|&&position| position <= op_pos)
+                .copied()
+        }
+    }
+
+    /// Rejects the `(value, byte size)` pairs that don't fit, unless numbers wrap
+    fn check_numbers(&self, numbers: impl IntoIterator<Item = (i64, usize)>) -> CompileResult<()> {
+        if self.options.wrap_numbers {
+            return Ok(());
+        }
+
+        match numbers
+            .into_iter()
+            .find(|&(value, size)| !fits(value, size))
+        {
+            Some((value, size)) => Err(CompileError::NumberOutOfRange(value, size)),
+            None => Ok(()),
+        }
     }
 
     fn write(&mut self, buf: &[u8]) -> CompileResult<()> {
@@ -167,12 +430,20 @@ impl<W: Write + Seek> State<W> {
     }
 
     fn write_op(&mut self, op: Op) -> CompileResult<()> {
+        let op_spec = *self.rules.ops.get(op_type(&op));
+        check_param_types(&op, &op_spec)?;
+        self.check_numbers(op.params().into_iter().filter_map(|param| match param {
+            OpParam::Dir(Direct::Numeric(n)) => Some((*n, op_spec.dir_size as usize)),
+            OpParam::Ind(Indirect::Numeric(n)) => Some((*n, IND_SIZE)),
+            _ => None,
+        }))?;
+
         let OpSpec {
             code,
             has_pcb,
             dir_size,
             ..
-        } = op_spec(&op);
+        } = op_spec;
 
         self.current_op_pos = self.size;
 
@@ -226,19 +497,27 @@ impl<W: Write + Seek> State<W> {
         self.write(&[reg.0])
     }
 
+    /// Leaves room for a value that is only known once every label is
+    fn write_placeholder(&mut self, value: Expr, size: usize) -> CompileResult<()> {
+        self.labels_to_fill.push(LabelPlaceholder {
+            write_pos: self.size,
+            op_pos: self.current_op_pos,
+            instr: self.current_instr,
+            value,
+            size,
+        });
+        self.write(&[0; 4][..size])
+    }
+
     fn write_dir(&mut self, dir: Direct, dir_size: DirectSize) -> CompileResult<()> {
         match dir {
             Direct::Label(label) => {
-                self.labels_to_fill.push(LabelPlaceholder {
-                    write_pos: self.size,
-                    op_pos: self.current_op_pos,
-                    name: label,
-                    size: dir_size as _,
-                });
-                self.write(match dir_size {
-                    DirectSize::TwoBytes => &[0; 2],
-                    DirectSize::FourBytes => &[0; 4],
-                })
+                let label = self.label_scope.qualify(label);
+                self.write_placeholder(Expr::Label(label), dir_size as _)
+            }
+            Direct::Expr(mut expr) => {
+                self.label_scope.qualify_expr(&mut expr);
+                self.write_placeholder(expr, dir_size as _)
             }
             Direct::Numeric(n) => {
                 self.size += write_numeric(&mut self.out, n as u32, dir_size as _)?;
@@ -250,13 +529,12 @@ impl<W: Write + Seek> State<W> {
     fn write_ind(&mut self, ind: Indirect) -> CompileResult<()> {
         match ind {
             Indirect::Label(label) => {
-                self.labels_to_fill.push(LabelPlaceholder {
-                    write_pos: self.size,
-                    op_pos: self.current_op_pos,
-                    name: label,
-                    size: IND_SIZE,
-                });
-                self.write(&[0; IND_SIZE])
+                let label = self.label_scope.qualify(label);
+                self.write_placeholder(Expr::Label(label), IND_SIZE)
+            }
+            Indirect::Expr(mut expr) => {
+                self.label_scope.qualify_expr(&mut expr);
+                self.write_placeholder(expr, IND_SIZE)
             }
             Indirect::Numeric(n) => {
                 self.size += write_numeric(&mut self.out, n as u32, IND_SIZE)?;
@@ -295,7 +573,7 @@ impl<W: Write + Seek> State<W> {
     }
 }
 
-fn write_numeric(mut out: impl Write, n: u32, write_size: usize) -> CompileResult<usize> {
+fn write_numeric(mut out: impl Write, n: u32, write_size: usize) -> Result<usize, IOError> {
     let truncated = n << ((4 - write_size) * 8);
     let be_bytes = truncated.to_be_bytes();
     let bytes_to_write = &be_bytes[..write_size];
@@ -304,15 +582,74 @@ fn write_numeric(mut out: impl Write, n: u32, write_size: usize) -> CompileResul
     Ok(bytes_to_write.len())
 }
 
+/// Whether a number can be written on `size` bytes, as a signed or an unsigned
+/// integer
+pub(super) fn fits(value: i64, size: usize) -> bool {
+    let bits = size * 8;
+    (-(1 << (bits - 1))..1 << bits).contains(&value)
+}
+
+/// Local labels, like `.loop`, belong to the last global label declared before
+/// them, and are named after it
+#[derive(Debug, Default)]
+pub(super) struct LabelScope {
+    global: String,
+}
+
+impl LabelScope {
+    /// The full name of a declared label, which starts a new scope when global
+    pub(super) fn declare(&mut self, label: String) -> String {
+        if label.starts_with('.') {
+            format!("{}{}", self.global, label)
+        } else {
+            if !is_anonymous_label(&label) {
+                self.global.clone_from(&label);
+            }
+            label
+        }
+    }
+
+    /// The full name of a referenced label
+    pub(super) fn qualify(&self, label: String) -> String {
+        if label.starts_with('.') {
+            format!("{}{}", self.global, label)
+        } else {
+            label
+        }
+    }
+
+    pub(super) fn qualify_expr(&self, expr: &mut Expr) {
+        expr.visit_labels_mut(&mut |label| *label = self.qualify(std::mem::take(label)));
+    }
+}
+
+/// Anonymous labels are declared with a number, like `1:`, and referenced by
+/// the number followed by `f` for the next one or `b` for the previous one
+pub(super) fn is_anonymous_label(label: &str) -> bool {
+    !label.is_empty() && label.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// The number of an anonymous label reference, and whether it is forward
+fn anonymous_reference(label: &str) -> Option<(&str, bool)> {
+    let (number, forward) = match label.strip_suffix('f') {
+        Some(number) => (number, true),
+        None => (label.strip_suffix('b')?, false),
+    };
+
+    Some((number, forward)).filter(|(number, _)| is_anonymous_label(number))
+}
+
 #[derive(Debug)]
 struct LabelPlaceholder {
     write_pos: usize,
     op_pos: usize,
-    name: String,
+    /// Index of the instruction using the value
+    instr: usize,
+    value: Expr,
     size: usize,
 }
 
-const IND_SIZE: usize = 2;
+pub(super) const IND_SIZE: usize = 2;
 
 impl Header {
     fn from_champion(champion: &Champion, prog_size: u32) -> CompileResult<Self> {
@@ -376,10 +713,30 @@ pub enum CompileError {
     #[error("The label '{0}' has been declared multiple times. A label can only be declared once")]
     DuplicateLabel(String),
     #[error(
-        "The champion's code is too big: {0} bytes (maximum allowed is {})",
-        CHAMP_MAX_SIZE
+        "The reference ':{0}' has no matching anonymous label {}",
+        if .0.ends_with('f') { "after it" } else { "before it" }
+    )]
+    MissingAnonymousLabel(String),
+    #[error("The constant '{0}' is used but has never been defined")]
+    MissingConstant(String),
+    #[error(
+        "The constant '{0}' has been defined multiple times. A constant can only be defined once"
     )]
-    ProgramTooLong(usize),
+    DuplicateConstant(String),
+    #[error("The constant '{0}' depends on itself")]
+    CyclicConstant(String),
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("The result of an expression does not fit on 64 bits")]
+    ArithmeticOverflow,
+    #[error("The champion's code is too big: {0} bytes (maximum allowed is {1})")]
+    ProgramTooLong(usize, usize),
+    #[error("The number {0} does not fit on {1} byte(s)")]
+    NumberOutOfRange(i64, usize),
+    #[error("The offset {1} to the label '{0}' does not fit on {2} bytes")]
+    LabelOffsetOutOfRange(String, i64, usize),
+    #[error("Parameter #{1} of '{0}' has a type that is not allowed by the rule set")]
+    ForbiddenParamType(OpType, usize),
     #[error("Unexpected IO error: {0}")]
     IOError(#[from] IOError),
 }
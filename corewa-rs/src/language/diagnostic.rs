@@ -0,0 +1,98 @@
+use std::{fmt, ops::Range, rc::Rc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A range of columns on a line of the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    /// The included file, `None` for the main source
+    pub file: Option<Rc<str>>,
+    /// Numbered from 1
+    pub line: usize,
+    /// Byte offsets in the line, numbered from 0
+    pub columns: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Problems of the whole champion have no span
+    pub span: Option<Span>,
+    pub message: String,
+    /// The macro invocations that produced the spanned line, innermost first
+    pub call_sites: Vec<Span>,
+    /// The definitions of the constants the error comes from, innermost first
+    pub constant_definitions: Vec<Span>,
+}
+
+impl Diagnostic {
+    pub fn error(span: Option<Span>, message: impl fmt::Display) -> Self {
+        Self {
+            severity: Severity::Error,
+            span,
+            message: message.to_string(),
+            call_sites: Vec::new(),
+            constant_definitions: Vec::new(),
+        }
+    }
+
+    pub fn warning(span: Option<Span>, message: impl fmt::Display) -> Self {
+        Self {
+            severity: Severity::Warning,
+            span,
+            message: message.to_string(),
+            call_sites: Vec::new(),
+            constant_definitions: Vec::new(),
+        }
+    }
+
+    pub fn with_call_sites(mut self, call_sites: Vec<Span>) -> Self {
+        self.call_sites = call_sites;
+        self
+    }
+
+    pub fn with_constant_definitions(mut self, definitions: Vec<Span>) -> Self {
+        self.constant_definitions = definitions;
+        self
+    }
+}
+
+/// `error: line 3, columns 4..7: message`, followed by a line per constant
+/// definition and per call site
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: ")?,
+            Severity::Error => write!(f, "error: ")?,
+        }
+        if let Some(span) = &self.span {
+            write!(f, "{}: ", span)?;
+        }
+        write!(f, "{}", self.message)?;
+        for definition in &self.constant_definitions {
+            write!(f, "\n  in the constant defined on {}", definition)?;
+        }
+        for call_site in &self.call_sites {
+            write!(f, "\n  in the macro invoked on {}", call_site)?;
+        }
+        Ok(())
+    }
+}
+
+/// `lib/file.s: line 3, columns 4..7`, without the file name for the main source
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file)?;
+        }
+        write!(
+            f,
+            "line {}, columns {}..{}",
+            self.line, self.columns.start, self.columns.end
+        )
+    }
+}
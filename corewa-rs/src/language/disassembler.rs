@@ -0,0 +1,265 @@
+use crate::{
+    rules::RuleSet,
+    spec::*,
+    vm::{
+        decoder::{Decode, Read},
+        loader::HeaderField,
+        types::Instruction,
+    },
+};
+
+use byteorder::{BigEndian, ByteOrder};
+use std::{
+    collections::BTreeSet,
+    convert::TryFrom,
+    fmt::{self, Write},
+    ops::Index,
+};
+
+/// Turns a compiled champion back into source code that compiles to the exact
+/// same bytes with the same rules.
+///
+/// The code section is decoded linearly: the bytes that are not a valid
+/// instruction are written with `.code` directives, and the targets of
+/// `zjmp`, `fork` and `lfork` are given labels when they start an instruction
+pub fn disassemble(champion: &[u8], rules: &RuleSet) -> Result<String, DisassembleError> {
+    use DisassembleError::*;
+
+    if champion.len() < HEADER_SIZE {
+        return Err(MissingHeader(champion.len()));
+    }
+    let (header, code) = champion.split_at(HEADER_SIZE);
+
+    let (magic, header) = header.split_at(4);
+    let (prog_name, header) = header.split_at(PROG_NAME_LENGTH + 1);
+    let (prog_size, prog_comment) = header.split_at(4);
+
+    let magic = BigEndian::read_u32(magic);
+    if magic != COREWAR_MAGIC {
+        return Err(InvalidMagic(magic));
+    }
+    // Unlike the VM, a declared size of 0 is rejected: the disassembled
+    // source could not reproduce it
+    let prog_size = BigEndian::read_u32(prog_size) as usize;
+    if prog_size != code.len() {
+        return Err(SizeMismatch {
+            declared: prog_size,
+            actual: code.len(),
+        });
+    }
+    if code.len() > rules.champ_max_size {
+        return Err(ChampionTooBig(code.len(), rules.champ_max_size));
+    }
+
+    let name = read_string(prog_name, HeaderField::Name)?;
+    let comment = read_string(prog_comment, HeaderField::Comment)?;
+
+    let lines = decode_lines(code, rules);
+
+    // Labels can only be declared between lines, or after the last one
+    let boundaries: BTreeSet<usize> = lines
+        .iter()
+        .map(Line::addr)
+        .chain(std::iter::once(code.len()))
+        .collect();
+    let labels: BTreeSet<usize> = lines
+        .iter()
+        .filter_map(|line| match line {
+            Line::Instr { addr, instr } => jump_target(*addr, instr),
+            Line::Code { .. } => None,
+        })
+        .filter(|target| boundaries.contains(target))
+        .collect();
+
+    let mut source = String::new();
+    write_source(&mut source, &name, &comment, &lines, &labels, code.len())
+        .expect("Writing to a String cannot fail");
+
+    Ok(source)
+}
+
+enum Line {
+    Instr { addr: usize, instr: Instruction },
+    Code { addr: usize, bytes: Vec<u8> },
+}
+
+impl Line {
+    fn addr(&self) -> usize {
+        match self {
+            Line::Instr { addr, .. } | Line::Code { addr, .. } => *addr,
+        }
+    }
+}
+
+fn decode_lines(code: &[u8], rules: &RuleSet) -> Vec<Line> {
+    let section = CodeSection(code);
+    let mut lines = Vec::new();
+    let mut addr = 0;
+
+    while addr < code.len() {
+        let instr = section
+            .decode_op(addr)
+            .ok()
+            .and_then(|op| section.decode_instr(op, addr, rules).ok())
+            .filter(|instr| addr + instr.byte_size <= code.len() && is_writable(instr));
+
+        match (instr, lines.last_mut()) {
+            (Some(instr), _) => {
+                lines.push(Line::Instr { addr, instr });
+                addr += instr.byte_size;
+            }
+            (None, Some(Line::Code { bytes, .. })) => {
+                bytes.push(code[addr]);
+                addr += 1;
+            }
+            (None, _) => {
+                lines.push(Line::Code {
+                    addr,
+                    bytes: vec![code[addr]],
+                });
+                addr += 1;
+            }
+        }
+    }
+
+    lines
+}
+
+/// The rules can allow parameter types that the syntax of an op can't express
+fn is_writable(instr: &Instruction) -> bool {
+    let syntax = op_spec(instr.kind);
+
+    instr
+        .params
+        .iter()
+        .zip(syntax.param_masks)
+        .take(syntax.param_count)
+        .all(|(param, mask)| {
+            let flag = match param.kind {
+                ParamType::Register => T_REG,
+                ParamType::Direct => T_DIR,
+                ParamType::Indirect => T_IND,
+            };
+            mask & flag != 0
+        })
+}
+
+/// The offset from the start of the code that a jump or a fork leads to
+fn jump_target(addr: usize, instr: &Instruction) -> Option<usize> {
+    match instr.kind {
+        OpType::Zjmp | OpType::Fork | OpType::Lfork => {
+            let target = addr as i64 + i64::from(instr.params[0].value);
+            usize::try_from(target).ok()
+        }
+        _ => None,
+    }
+}
+
+fn label_name(addr: usize) -> String {
+    format!("label_{}", addr)
+}
+
+fn write_source(
+    out: &mut impl Write,
+    name: &str,
+    comment: &str,
+    lines: &[Line],
+    labels: &BTreeSet<usize>,
+    code_size: usize,
+) -> fmt::Result {
+    writeln!(out, ".name \"{}\"", name)?;
+    writeln!(out, ".comment \"{}\"", comment)?;
+    writeln!(out)?;
+
+    for line in lines {
+        if labels.contains(&line.addr()) {
+            writeln!(out, "{}:", label_name(line.addr()))?;
+        }
+
+        match line {
+            Line::Instr { addr, instr } => {
+                write!(out, "\t{}", instr.kind.to_string().to_lowercase())?;
+                for (idx, param) in instr.params[..op_spec(instr.kind).param_count]
+                    .iter()
+                    .enumerate()
+                {
+                    write!(out, "{}", if idx == 0 { " " } else { ", " })?;
+                    match jump_target(*addr, instr).filter(|target| labels.contains(target)) {
+                        Some(target) if idx == 0 => write!(out, "%:{}", label_name(target))?,
+                        _ => write!(out, "{}", param)?,
+                    }
+                }
+                writeln!(out)?;
+            }
+            Line::Code { bytes, .. } => {
+                write!(out, "\t.code")?;
+                for byte in bytes {
+                    write!(out, " 0x{:02x}", byte)?;
+                }
+                writeln!(out)?;
+            }
+        }
+    }
+
+    if labels.contains(&code_size) {
+        writeln!(out, "{}:", label_name(code_size))?;
+    }
+
+    Ok(())
+}
+
+/// Reads a NUL padded header string that can be written between quotes
+fn read_string(bytes: &[u8], field: HeaderField) -> Result<String, DisassembleError> {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    let (string, padding) = bytes.split_at(len);
+
+    if padding.iter().any(|&b| b != 0) {
+        return Err(DisassembleError::TrailingData(field));
+    }
+    let string =
+        String::from_utf8(string.to_vec()).map_err(|_| DisassembleError::InvalidUtf8(field))?;
+    if string.contains(&['"', '\n'][..]) {
+        return Err(DisassembleError::UnwritableString(field));
+    }
+
+    Ok(string)
+}
+
+/// The code of a champion, read as zeros past its end
+struct CodeSection<'a>(&'a [u8]);
+
+impl Index<usize> for CodeSection<'_> {
+    type Output = u8;
+
+    fn index(&self, index: usize) -> &u8 {
+        self.0.get(index).unwrap_or(&0)
+    }
+}
+
+impl Read for CodeSection<'_> {
+    fn read_i32(&self, at: usize) -> i32 {
+        i32::from_be_bytes([self[at], self[at + 1], self[at + 2], self[at + 3]])
+    }
+
+    fn read_i16(&self, at: usize) -> i16 {
+        i16::from_be_bytes([self[at], self[at + 1]])
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DisassembleError {
+    #[error("The file is too short to contain a header ({0} bytes)")]
+    MissingHeader(usize),
+    #[error("Invalid magic number {0:#010x}")]
+    InvalidMagic(u32),
+    #[error("The header announces {declared} bytes of code but there are {actual}")]
+    SizeMismatch { declared: usize, actual: usize },
+    #[error("The champion is too big ({0} bytes, maximum allowed is {1})")]
+    ChampionTooBig(usize, usize),
+    #[error("The {0} is not valid UTF8")]
+    InvalidUtf8(HeaderField),
+    #[error("The {0} has data after its terminating NUL")]
+    TrailingData(HeaderField),
+    #[error("The {0} contains a quote or a newline and cannot be written as a string")]
+    UnwritableString(HeaderField),
+}
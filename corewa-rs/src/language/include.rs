@@ -0,0 +1,184 @@
+//! Inlining of the files named by `.include "path"` directives.
+//!
+//! Paths are relative to the directory of the file that includes them, and the
+//! files are read by a [`FileResolver`] so that they don't have to live on disk
+
+use super::{
+    diagnostic::Span,
+    lexer::{Term, Token, Tokenizer},
+    line_spans, parse_error_span,
+    parser::{parse_include, ParseError},
+};
+
+use std::{
+    collections::HashMap,
+    io::{Error as IOError, ErrorKind},
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+/// Reads the files of the `.include` directives
+pub trait FileResolver {
+    /// `path` is the included path joined to the directory of the including
+    /// file, normalized
+    fn read_file(&self, path: &Path) -> Result<String, IOError>;
+}
+
+/// Reads the files from disk, relative to the current directory
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileSystem;
+
+impl FileResolver for FileSystem {
+    fn read_file(&self, path: &Path) -> Result<String, IOError> {
+        std::fs::read_to_string(path)
+    }
+}
+
+/// Fails to read any file
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoFiles;
+
+impl FileResolver for NoFiles {
+    fn read_file(&self, _path: &Path) -> Result<String, IOError> {
+        Err(IOError::new(
+            ErrorKind::NotFound,
+            "files cannot be included here",
+        ))
+    }
+}
+
+/// Virtual files, by normalized path
+impl FileResolver for HashMap<PathBuf, String> {
+    fn read_file(&self, path: &Path) -> Result<String, IOError> {
+        self.get(&normalize(path))
+            .cloned()
+            .ok_or_else(|| IOError::new(ErrorKind::NotFound, "no such file"))
+    }
+}
+
+/// A line of the main source or of an included file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLine {
+    /// `None` for the main source
+    pub file: Option<Rc<str>>,
+    /// Numbered from 1
+    pub line: usize,
+    pub text: String,
+}
+
+/// The lines of `input` and of the files it includes, recursively, in order.
+/// The directives that could not be resolved are replaced by their error.
+/// `path` is the location of `input`, if it was read from a file
+pub fn inline_includes(
+    input: &str,
+    path: Option<&Path>,
+    resolver: &dyn FileResolver,
+) -> Vec<Result<SourceLine, IncludeError>> {
+    let mut lines = Vec::new();
+    let mut including = path.map(normalize).into_iter().collect();
+
+    push_lines(input, None, path, resolver, &mut including, &mut lines);
+
+    lines
+}
+
+/// `including` holds the files being read, to detect cycles
+fn push_lines(
+    text: &str,
+    file: Option<Rc<str>>,
+    path: Option<&Path>,
+    resolver: &dyn FileResolver,
+    including: &mut Vec<PathBuf>,
+    out: &mut Vec<Result<SourceLine, IncludeError>>,
+) {
+    for (line, line_no) in text.lines().zip(1..) {
+        if !matches!(
+            Tokenizer::new(line).next(),
+            Some(Ok(Token {
+                term: Term::IncludeCmd,
+                ..
+            }))
+        ) {
+            out.push(Ok(SourceLine {
+                file: file.clone(),
+                line: line_no,
+                text: String::from(line),
+            }));
+            continue;
+        }
+
+        let included = match parse_include(line) {
+            Ok(included) => included,
+            Err(e) => {
+                let span = parse_error_span(file.clone(), line_no, line, &e);
+                out.push(Err(IncludeErrorKind::InvalidDirective(e).at(span)));
+                continue;
+            }
+        };
+        let statement = line_spans(file.clone(), line_no, line).statement;
+        let directory = path.and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+        let resolved = normalize(&directory.join(&included));
+        let name = resolved.to_string_lossy().into_owned();
+
+        if including.contains(&resolved) {
+            out.push(Err(IncludeErrorKind::Cycle(name).at(statement)));
+            continue;
+        }
+        match resolver.read_file(&resolved) {
+            Ok(content) => {
+                including.push(resolved.clone());
+                let file = Some(Rc::from(name));
+                push_lines(&content, file, Some(&resolved), resolver, including, out);
+                including.pop();
+            }
+            Err(e) => out.push(Err(IncludeErrorKind::Unreadable(name, e).at(statement))),
+        }
+    }
+}
+
+/// Removes the `.` and `..` components that can be, without touching the
+/// file system
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{kind}")]
+pub struct IncludeError {
+    pub kind: IncludeErrorKind,
+    /// The `.include` directive
+    pub span: Span,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IncludeErrorKind {
+    #[error("{0}")]
+    InvalidDirective(ParseError),
+    #[error("Cannot read '{0}': {1}")]
+    Unreadable(String, IOError),
+    #[error("'{0}' is already being included")]
+    Cycle(String),
+}
+
+impl IncludeErrorKind {
+    fn at(self, span: Span) -> IncludeError {
+        IncludeError { kind: self, span }
+    }
+}
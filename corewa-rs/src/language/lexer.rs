@@ -30,13 +30,18 @@ impl Iterator for Tokenizer<'_> {
             ':' => self.lex_label_use(idx),
             ',' => self.lex_single(Term::ParamSeparator, idx),
             '%' => self.lex_single(Term::DirectChar, idx),
+            '+' => self.lex_single(Term::Plus, idx),
+            '*' => self.lex_single(Term::Star, idx),
+            '/' => self.lex_single(Term::Slash, idx),
+            '(' => self.lex_single(Term::OpenParen, idx),
+            ')' => self.lex_single(Term::CloseParen, idx),
 
             '.' => self.lex_directive(idx),
             '"' => self.lex_quoted_string(idx),
             '#' => self.lex_comment(idx),
             '-' => self.lex_negative_number(idx),
 
-            c if c.is_digit(10) => self.lex_number(idx),
+            c if c.is_ascii_digit() => self.lex_number(idx),
             c if IDENT_CHARS.contains(c) => self.lex_ident(idx),
 
             _ => {
@@ -61,13 +66,17 @@ impl Tokenizer<'_> {
     where
         F: Fn(&(usize, char)) -> bool,
     {
-        while self.chars.peek().map_or(false, &skipper) {
+        while self.chars.peek().is_some_and(&skipper) {
             self.chars.next();
         }
     }
 
+    /// Local label references are prefixed by a `.`, as in `:.loop`
     fn lex_label_use(&mut self, idx_start: usize) -> TokenResult {
         self.chars.next(); // consume
+        if let Some((_, '.')) = self.chars.peek() {
+            self.chars.next();
+        }
         match self.chars.peek() {
             Some((_, c)) if IDENT_CHARS.contains(*c) => {
                 self.skip_while(|&(_, c)| IDENT_CHARS.contains(c));
@@ -83,11 +92,25 @@ impl Tokenizer<'_> {
         Ok(term.at(idx_start..self.peek_idx()))
     }
 
+    /// Local label declarations, like `.loop:`, are told apart from the
+    /// directives by their colon
     fn lex_directive(&mut self, idx_start: usize) -> TokenResult {
-        const DIRECTIVES: [(&str, Term); 3] = [
+        let name_len = self.input[idx_start + 1..]
+            .find(|c| !IDENT_CHARS.contains(c))
+            .unwrap_or(self.input.len() - idx_start - 1);
+        if name_len > 0 && self.input[idx_start + 1 + name_len..].starts_with(':') {
+            self.chars.nth(name_len + 1);
+            return Ok(Term::LabelDef.at(idx_start..idx_start + name_len + 2));
+        }
+
+        const DIRECTIVES: [(&str, Term); 7] = [
             (".name", Term::ChampionNameCmd),
             (".comment", Term::ChampionCommentCmd),
             (".code", Term::CodeCmd),
+            (".equ", Term::ConstantCmd),
+            (".macro", Term::MacroCmd),
+            (".endm", Term::EndMacroCmd),
+            (".include", Term::IncludeCmd),
         ];
 
         let current_str = &self.input[idx_start..];
@@ -132,12 +155,15 @@ impl Tokenizer<'_> {
         Ok(Term::Comment.at(idx_start..self.input.len()))
     }
 
+    /// A minus sign directly followed by digits is part of the number,
+    /// otherwise it is an operator
     fn lex_negative_number(&mut self, idx_start: usize) -> TokenResult {
         self.chars.next(); // consume -
 
         match self.chars.peek() {
-            Some((_, c)) if c.is_digit(10) => self.lex_number(idx_start),
-            _ => Err(LexerErrorKind::NoNumberAfterMinus.at(idx_start..idx_start + 1)),
+            Some((_, c)) if c.is_ascii_digit() => self.lex_number(idx_start),
+            Some(_) => Ok(Term::Minus.at(idx_start..idx_start + 1)),
+            None => Err(LexerErrorKind::NoNumberAfterMinus.at(idx_start..idx_start + 1)),
         }
     }
 
@@ -159,7 +185,20 @@ impl Tokenizer<'_> {
         };
 
         self.skip_while(|(_, c)| c.is_digit(base.radix()));
-        Ok(Term::Number { base }.at(idx_start..self.peek_idx()))
+
+        // Anonymous label declarations are plain decimal numbers, like `1:`
+        let idx_end = self.peek_idx();
+        let is_plain = self.input[idx_start..idx_end]
+            .bytes()
+            .all(|b| b.is_ascii_digit());
+        if is_plain {
+            if let Some((_, ':')) = self.chars.peek() {
+                self.chars.next();
+                return Ok(Term::LabelDef.at(idx_start..idx_end + 1));
+            }
+        }
+
+        Ok(Term::Number { base }.at(idx_start..idx_end))
     }
 
     fn lex_ident(&mut self, idx_start: usize) -> TokenResult {
@@ -192,6 +231,14 @@ pub enum Term {
     ChampionCommentCmd,
     #[display(fmt = "Code directive")]
     CodeCmd,
+    #[display(fmt = "Constant directive")]
+    ConstantCmd,
+    #[display(fmt = "Macro directive")]
+    MacroCmd,
+    #[display(fmt = "End of macro directive")]
+    EndMacroCmd,
+    #[display(fmt = "Include directive")]
+    IncludeCmd,
     #[display(fmt = "Quoted string")]
     QuotedString,
     #[display(fmt = "Comment")]
@@ -204,6 +251,18 @@ pub enum Term {
     ParamSeparator,
     #[display(fmt = "Direct character")]
     DirectChar,
+    #[display(fmt = "Plus sign")]
+    Plus,
+    #[display(fmt = "Minus sign")]
+    Minus,
+    #[display(fmt = "Multiplication sign")]
+    Star,
+    #[display(fmt = "Division sign")]
+    Slash,
+    #[display(fmt = "Opening parenthesis")]
+    OpenParen,
+    #[display(fmt = "Closing parenthesis")]
+    CloseParen,
     #[display(fmt = "Number")]
     Number { base: NumberBase },
     #[display(fmt = "Identifier")]
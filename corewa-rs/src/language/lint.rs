@@ -0,0 +1,219 @@
+use super::{
+    assembler::{Champion, ParsedInstruction},
+    compiler::{fits, is_anonymous_label, op_type, CompileOptions, LabelScope, IND_SIZE},
+    diagnostic::Diagnostic,
+    types::*,
+};
+use crate::{
+    rules::{RuleSet, Semantics},
+    spec::MAX_PLAYERS,
+};
+
+use std::collections::{HashMap, HashSet};
+
+/// Reports the suspicious constructs of a champion that still compiles.
+/// The numbers that don't fit their encoding are only reported when they wrap,
+/// since they are compile errors otherwise
+pub fn lint(champion: &Champion, rules: &RuleSet, options: &CompileOptions) -> Vec<Diagnostic> {
+    let mut warnings = Vec::new();
+
+    unused_names(champion, &mut warnings);
+    unreachable_code(champion, rules, &mut warnings);
+
+    for (idx, instr) in champion.instructions.iter().enumerate() {
+        let warn = |message: String| warning(champion, idx, message);
+
+        match instr {
+            ParsedInstruction::RawCode(values) if options.wrap_numbers => {
+                for &value in values {
+                    if !fits(value, 1) {
+                        warnings.push(warn(format!(
+                            "The .code value {} does not fit on a byte and is truncated to {}",
+                            value, value as u8
+                        )));
+                    }
+                }
+            }
+            ParsedInstruction::Op(op) => {
+                let dir_size = rules.ops.get(op_type(op)).dir_size as usize;
+
+                for param in op.params().into_iter().filter(|_| options.wrap_numbers) {
+                    let (value, size) = match param {
+                        OpParam::Dir(Direct::Numeric(n)) => (*n, dir_size),
+                        OpParam::Ind(Indirect::Numeric(n)) => (*n, IND_SIZE),
+                        _ => continue,
+                    };
+                    if !fits(value, size) {
+                        warnings.push(warn(format!(
+                            "The literal {} does not fit on {} bytes and is encoded as {}",
+                            value,
+                            size,
+                            truncate(value, size)
+                        )));
+                    }
+                }
+
+                if let Op::Live(Direct::Numeric(id)) = op {
+                    if !is_plausible_player_id(*id) {
+                        warnings.push(warn(format!(
+                            "live reports the player id {}, but players are usually numbered \
+                             from 1 to {max} or from -1 to -{max}",
+                            id,
+                            max = MAX_PLAYERS
+                        )));
+                    }
+                }
+            }
+            ParsedInstruction::RawCode(_)
+            | ParsedInstruction::Label(_)
+            | ParsedInstruction::Constant(..) => (),
+        }
+    }
+
+    warnings
+}
+
+/// A warning located on an instruction, with the macro invocations it comes
+/// from
+fn warning(champion: &Champion, idx: usize, message: String) -> Diagnostic {
+    Diagnostic::warning(champion.spans.get(idx).cloned(), message)
+        .with_call_sites(champion.call_sites.get(idx).cloned().unwrap_or_default())
+}
+
+/// Reports the labels and constants that no parameter or constant refers to.
+/// Anonymous labels are left out, their references depending on positions
+fn unused_names(champion: &Champion, warnings: &mut Vec<Diagnostic>) {
+    let mut scope = LabelScope::default();
+    let mut declared_labels = HashMap::new();
+    let mut used_labels = HashSet::new();
+    let mut used_constants = HashSet::new();
+
+    for (idx, instr) in champion.instructions.iter().enumerate() {
+        let mut expressions = Vec::new();
+
+        match instr {
+            ParsedInstruction::Label(label) if !is_anonymous_label(label) => {
+                declared_labels.insert(idx, scope.declare(label.clone()));
+            }
+            ParsedInstruction::Op(op) => {
+                for param in op.params() {
+                    match param {
+                        OpParam::Dir(Direct::Label(label))
+                        | OpParam::Ind(Indirect::Label(label)) => {
+                            used_labels.insert(scope.qualify(label.clone()));
+                        }
+                        OpParam::Dir(Direct::Expr(expr)) | OpParam::Ind(Indirect::Expr(expr)) => {
+                            expressions.push(expr)
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            ParsedInstruction::Constant(_, value) => expressions.push(value),
+            _ => (),
+        }
+
+        for expr in expressions {
+            expr.visit_names(&mut |name| match name {
+                Expr::Label(label) => {
+                    used_labels.insert(scope.qualify(label.clone()));
+                }
+                Expr::Constant(constant) => {
+                    used_constants.insert(constant);
+                }
+                _ => (),
+            });
+        }
+    }
+
+    for (idx, instr) in champion.instructions.iter().enumerate() {
+        let message = match (instr, declared_labels.get(&idx)) {
+            (ParsedInstruction::Label(_), Some(label)) if !used_labels.contains(label) => {
+                format!("The label '{}' is never used", label)
+            }
+            (ParsedInstruction::Constant(name, _), _) if !used_constants.contains(name) => {
+                format!("The constant '{}' is never used", name)
+            }
+            _ => continue,
+        };
+        warnings.push(warning(champion, idx, message));
+    }
+}
+
+/// Reports the first op following a `zjmp` that always jumps, the code being
+/// reachable again from the next label
+fn unreachable_code(champion: &Champion, rules: &RuleSet, warnings: &mut Vec<Diagnostic>) {
+    let mut zf_is_set = false;
+    let mut unreachable = false;
+    let mut reported = false;
+
+    for (idx, instr) in champion.instructions.iter().enumerate() {
+        match instr {
+            ParsedInstruction::Label(_) => {
+                zf_is_set = false;
+                unreachable = false;
+                reported = false;
+            }
+            ParsedInstruction::Op(op) if unreachable => {
+                if !reported {
+                    warnings.push(warning(
+                        champion,
+                        idx,
+                        format!(
+                            "Unreachable {}: the previous zjmp always jumps",
+                            op_type(op).to_string().to_lowercase()
+                        ),
+                    ));
+                    reported = true;
+                }
+            }
+            ParsedInstruction::RawCode(_) => zf_is_set = false,
+            ParsedInstruction::Constant(..) => (),
+            ParsedInstruction::Op(Op::Zjmp(_)) => unreachable = zf_is_set,
+            ParsedInstruction::Op(op) if affects_zf(op, rules) => zf_is_set = always_sets_zf(op),
+            ParsedInstruction::Op(_) => (),
+        }
+    }
+}
+
+fn affects_zf(op: &Op, rules: &RuleSet) -> bool {
+    match op {
+        Op::Ld(..)
+        | Op::Add(..)
+        | Op::Sub(..)
+        | Op::And(..)
+        | Op::Or(..)
+        | Op::Xor(..)
+        | Op::Lld(..)
+        | Op::Lldi(..) => true,
+        Op::Ldi(..) => rules.semantics == Semantics::Compat,
+        _ => false,
+    }
+}
+
+/// Whether an op always sets the zero flag, whatever the state of the process
+fn always_sets_zf(op: &Op) -> bool {
+    fn is_zero(param: &AnyParam) -> bool {
+        *param == AnyParam::Dir(Direct::Numeric(0))
+    }
+
+    match op {
+        Op::Ld(DirInd::Dir(Direct::Numeric(0)), _)
+        | Op::Lld(DirInd::Dir(Direct::Numeric(0)), _) => true,
+        Op::Sub(lhs, rhs, _) => lhs == rhs,
+        Op::And(lhs, rhs, _) => is_zero(lhs) || is_zero(rhs),
+        Op::Or(lhs, rhs, _) => is_zero(lhs) && is_zero(rhs),
+        Op::Xor(lhs, rhs, _) => lhs == rhs,
+        _ => false,
+    }
+}
+
+fn is_plausible_player_id(id: i64) -> bool {
+    (1..=MAX_PLAYERS as u64).contains(&id.unsigned_abs())
+}
+
+/// The value read back from the `size` low bytes of `value`
+fn truncate(value: i64, size: usize) -> i64 {
+    let shift = 64 - size * 8;
+    (value << shift) >> shift
+}
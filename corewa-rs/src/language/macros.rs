@@ -0,0 +1,420 @@
+//! Expansion of the `.macro NAME param, ... / .endm` definitions.
+//!
+//! Macros are expanded line by line, before parsing: the identifiers of a body
+//! that name a parameter are replaced with the text of the matching argument,
+//! and the labels declared in a body are renamed for each expansion so that a
+//! macro can be invoked several times. A macro can invoke the macros defined
+//! before it is invoked, but not itself
+
+use super::{
+    assembler::LineSpans,
+    compiler::is_anonymous_label,
+    diagnostic::Span,
+    include::SourceLine,
+    lexer::{Term, Token, Tokenizer},
+    line_spans, parse_error_span,
+    parser::{is_op_mnemonic, parse_macro_end, parse_macro_start, ParseError},
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    rc::Rc,
+};
+
+/// A line to parse, after macro expansion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpandedLine {
+    pub text: String,
+    /// The file of `line`, `None` for the main source
+    pub file: Option<Rc<str>>,
+    /// The line of the source the text comes from: a line of a macro body for
+    /// the expanded ones
+    pub line: usize,
+    /// The macro invocations the line was expanded from, innermost first
+    pub call_sites: Vec<Span>,
+    /// The parts of `text` that differ from the source line, in order
+    replacements: Vec<Replacement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Replacement {
+    expanded: Range<usize>,
+    source: Range<usize>,
+}
+
+impl ExpandedLine {
+    fn unchanged(source: &SourceLine) -> Self {
+        Self {
+            text: source.text.clone(),
+            file: source.file.clone(),
+            line: source.line,
+            call_sites: Vec::new(),
+            replacements: Vec::new(),
+        }
+    }
+
+    /// Locates columns of `text` in the source line. A column inside a
+    /// replaced part is moved to the edge of the replaced source
+    pub fn span(&self, columns: Range<usize>) -> Span {
+        Span {
+            file: self.file.clone(),
+            line: self.line,
+            columns: self.source_column(columns.start, false)
+                ..self.source_column(columns.end, true),
+        }
+    }
+
+    /// The spans of the line in the source, with its call sites
+    pub fn line_spans(&self) -> LineSpans {
+        let spans = line_spans(self.file.clone(), self.line, &self.text);
+
+        LineSpans {
+            label: self.span(spans.label.columns),
+            statement: self.span(spans.statement.columns),
+            call_sites: self.call_sites.clone(),
+        }
+    }
+
+    fn source_column(&self, column: usize, is_end: bool) -> usize {
+        let mut source_column = column;
+
+        for replacement in &self.replacements {
+            if column < replacement.expanded.start {
+                break;
+            }
+            if column < replacement.expanded.end {
+                return if is_end {
+                    replacement.source.end
+                } else {
+                    replacement.source.start
+                };
+            }
+            source_column = replacement.source.end + (column - replacement.expanded.end);
+        }
+
+        source_column
+    }
+}
+
+#[derive(Debug)]
+struct Macro {
+    name: String,
+    params: Vec<String>,
+    body: Vec<SourceLine>,
+    /// The labels declared in the body
+    labels: HashSet<String>,
+}
+
+/// Expands the lines of a source one at a time
+#[derive(Debug, Default)]
+pub struct MacroExpander {
+    macros: HashMap<String, Rc<Macro>>,
+    /// The `.macro` directive of the body being read
+    defining: Option<Span>,
+    /// The macro being defined, `None` while skipping the body of an invalid
+    /// definition
+    definition: Option<Macro>,
+    expansions: usize,
+}
+
+impl MacroExpander {
+    /// Consumes a line of the source and returns the lines to parse in its
+    /// place
+    pub fn expand(&mut self, source: &SourceLine) -> Result<Vec<ExpandedLine>, MacroError> {
+        let line = source.text.as_str();
+        let first_term = Tokenizer::new(line)
+            .next()
+            .and_then(Result::ok)
+            .map(|token| token.term);
+        let statement = || line_spans(source.file.clone(), source.line, line).statement;
+        let parse_error = |e| parse_error(source, e);
+
+        if self.defining.is_some() {
+            match first_term {
+                Some(Term::EndMacroCmd) => {
+                    self.defining = None;
+                    let definition = self.definition.take();
+                    parse_macro_end(line).map_err(parse_error)?;
+
+                    if let Some(definition) = definition {
+                        self.macros
+                            .insert(definition.name.clone(), Rc::new(definition));
+                    }
+                }
+                Some(Term::MacroCmd) => {
+                    return Err(MacroErrorKind::NestedDefinition.at(statement()));
+                }
+                _ => {
+                    if let Some(definition) = &mut self.definition {
+                        if let Some(label) = declared_label(line) {
+                            definition.labels.insert(String::from(label));
+                        }
+                        definition.body.push(source.clone());
+                    }
+                }
+            }
+            return Ok(Vec::new());
+        }
+
+        match first_term {
+            Some(Term::MacroCmd) => {
+                self.defining = Some(statement());
+                let (name, params) = parse_macro_start(line).map_err(parse_error)?;
+                self.check_definition(&name, &params)
+                    .map_err(|kind| kind.at(statement()))?;
+
+                let definition = Macro {
+                    name,
+                    params,
+                    body: Vec::new(),
+                    labels: HashSet::new(),
+                };
+                self.definition = Some(definition);
+                Ok(Vec::new())
+            }
+            Some(Term::EndMacroCmd) => Err(MacroErrorKind::UnexpectedEnd.at(statement())),
+            _ => {
+                let mut lines = Vec::new();
+                self.expand_line(ExpandedLine::unchanged(source), &mut Vec::new(), &mut lines)?;
+                Ok(lines)
+            }
+        }
+    }
+
+    /// Checks that the last macro definition was closed
+    pub fn finish(self) -> Result<(), MacroError> {
+        match self.defining {
+            Some(span) => Err(MacroErrorKind::UnclosedMacro.at(span)),
+            None => Ok(()),
+        }
+    }
+
+    fn check_definition(&self, name: &str, params: &[String]) -> Result<(), MacroErrorKind> {
+        if is_op_mnemonic(name) {
+            return Err(MacroErrorKind::ReservedName(String::from(name)));
+        }
+        if self.macros.contains_key(name) {
+            return Err(MacroErrorKind::DuplicateMacro(String::from(name)));
+        }
+        let mut seen = HashSet::new();
+        match params.iter().find(|param| !seen.insert(*param)) {
+            Some(param) => Err(MacroErrorKind::DuplicateParameter(param.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Pushes the line to `out`, or its expansion if it invokes a macro.
+    /// `expanding` holds the macros whose bodies the line comes from
+    fn expand_line(
+        &mut self,
+        line: ExpandedLine,
+        expanding: &mut Vec<Rc<Macro>>,
+        out: &mut Vec<ExpandedLine>,
+    ) -> Result<(), MacroError> {
+        let tokens: Vec<Token> = Tokenizer::new(&line.text)
+            .filter_map(Result::ok)
+            .take_while(|token| token.term != Term::Comment)
+            .collect();
+        let (label, rest) = match tokens.split_first() {
+            Some((label, rest)) if label.term == Term::LabelDef => (Some(label), rest),
+            _ => (None, &tokens[..]),
+        };
+        let invoked = match rest.first() {
+            Some(token) if token.term == Term::Ident => {
+                self.macros.get(&line.text[token.range.clone()]).cloned()
+            }
+            _ => None,
+        };
+        let invoked = match invoked {
+            Some(invoked) => invoked,
+            None => {
+                out.push(line);
+                return Ok(());
+            }
+        };
+
+        let call_site = line.line_spans().statement;
+        let error_at_call_site = |kind: MacroErrorKind| MacroError {
+            kind,
+            span: call_site.clone(),
+            call_sites: line.call_sites.clone(),
+        };
+
+        if expanding.iter().any(|macro_| Rc::ptr_eq(macro_, &invoked)) {
+            return Err(error_at_call_site(MacroErrorKind::RecursiveMacro(
+                invoked.name.clone(),
+            )));
+        }
+
+        let args = arguments(&line.text, rest);
+        if args.len() != invoked.params.len() {
+            return Err(error_at_call_site(MacroErrorKind::ArgumentCount {
+                name: invoked.name.clone(),
+                expected: invoked.params.len(),
+                actual: args.len(),
+            }));
+        }
+
+        if let Some(label) = label {
+            out.push(ExpandedLine {
+                text: String::from(&line.text[..label.range.end]),
+                ..line.clone()
+            });
+        }
+
+        self.expansions += 1;
+        let mut call_sites = vec![call_site.clone()];
+        call_sites.extend(line.call_sites.iter().cloned());
+
+        expanding.push(Rc::clone(&invoked));
+        for body_line in &invoked.body {
+            let expanded = substitute(&invoked, body_line, &args, self.expansions, &call_sites);
+            self.expand_line(expanded, expanding, out)?;
+        }
+        expanding.pop();
+
+        Ok(())
+    }
+}
+
+/// The label declared at the start of a line, unless it is anonymous: those
+/// don't need renaming since their references find the closest one
+fn declared_label(line: &str) -> Option<&str> {
+    match Tokenizer::new(line).next() {
+        Some(Ok(token)) if token.term == Term::LabelDef => {
+            Some(&line[token.range.start..token.range.end - 1])
+                .filter(|label| !is_anonymous_label(label))
+        }
+        _ => None,
+    }
+}
+
+/// The comma separated texts following the macro name
+fn arguments<'a>(text: &'a str, tokens: &[Token]) -> Vec<&'a str> {
+    let (name, tokens) = match tokens.split_first() {
+        Some(split) => split,
+        None => return Vec::new(),
+    };
+    let end = match tokens.last() {
+        Some(last) => last.range.end,
+        None => return Vec::new(),
+    };
+
+    let mut args = Vec::new();
+    let mut start = name.range.end;
+    for separator in tokens.iter().filter(|t| t.term == Term::ParamSeparator) {
+        args.push(text[start..separator.range.start].trim());
+        start = separator.range.end;
+    }
+    args.push(text[start..end].trim());
+
+    args
+}
+
+/// Writes a line of the body of `invoked` with the given arguments
+fn substitute(
+    invoked: &Macro,
+    source_line: &SourceLine,
+    args: &[&str],
+    expansion: usize,
+    call_sites: &[Span],
+) -> ExpandedLine {
+    let body_line = source_line.text.as_str();
+    let mut text = String::with_capacity(body_line.len());
+    let mut replacements = Vec::new();
+    let mut copied = 0;
+
+    for token in Tokenizer::new(body_line).filter_map(Result::ok) {
+        let source = &body_line[token.range.clone()];
+        let replacement = match token.term {
+            Term::Ident => invoked
+                .params
+                .iter()
+                .position(|param| param == source)
+                .map(|idx| String::from(args[idx])),
+            Term::LabelDef if invoked.labels.contains(&source[..source.len() - 1]) => Some(
+                format!("{}:", local_label(&source[..source.len() - 1], expansion)),
+            ),
+            Term::LabelUse if invoked.labels.contains(&source[1..]) => {
+                Some(format!(":{}", local_label(&source[1..], expansion)))
+            }
+            _ => None,
+        };
+
+        if let Some(replacement) = replacement {
+            text.push_str(&body_line[copied..token.range.start]);
+            let start = text.len();
+            text.push_str(&replacement);
+            replacements.push(Replacement {
+                expanded: start..text.len(),
+                source: token.range.clone(),
+            });
+            copied = token.range.end;
+        }
+    }
+    text.push_str(&body_line[copied..]);
+
+    ExpandedLine {
+        text,
+        file: source_line.file.clone(),
+        line: source_line.line,
+        call_sites: call_sites.to_vec(),
+        replacements,
+    }
+}
+
+/// The name a label of a macro body takes in an expansion
+fn local_label(label: &str, expansion: usize) -> String {
+    format!("{}__{}", label, expansion)
+}
+
+fn parse_error(source: &SourceLine, error: ParseError) -> MacroError {
+    let span = parse_error_span(source.file.clone(), source.line, &source.text, &error);
+    MacroErrorKind::InvalidDefinition(error).at(span)
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{kind}")]
+pub struct MacroError {
+    pub kind: MacroErrorKind,
+    pub span: Span,
+    /// The macro invocations that produced the line, innermost first
+    pub call_sites: Vec<Span>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MacroErrorKind {
+    #[error("{0}")]
+    InvalidDefinition(ParseError),
+    #[error("Macros cannot be defined inside other macros")]
+    NestedDefinition,
+    #[error("The macro is missing its '.endm' directive")]
+    UnclosedMacro,
+    #[error("'.endm' outside of a macro definition")]
+    UnexpectedEnd,
+    #[error("The macro '{0}' has already been defined")]
+    DuplicateMacro(String),
+    #[error("'{0}' is an operation and cannot be the name of a macro")]
+    ReservedName(String),
+    #[error("The parameter '{0}' is declared multiple times")]
+    DuplicateParameter(String),
+    #[error("The macro '{name}' takes {expected} argument(s) but was given {actual}")]
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("The macro '{0}' invokes itself")]
+    RecursiveMacro(String),
+}
+
+impl MacroErrorKind {
+    fn at(self, span: Span) -> MacroError {
+        MacroError {
+            kind: self,
+            span,
+            call_sites: Vec::new(),
+        }
+    }
+}
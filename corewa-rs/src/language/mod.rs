@@ -1,52 +1,280 @@
 pub mod assembler;
 pub mod compiler;
+pub mod diagnostic;
+pub mod disassembler;
+pub mod include;
 pub mod lexer;
+pub mod lint;
+pub mod macros;
 pub mod parser;
 pub mod types;
 
+pub use disassembler::disassemble;
 pub use parser::error_range;
 
-use assembler::{AssembleError, Champion, ChampionBuilder};
-use compiler::{compile_champion, CompileError};
+use assembler::{AssembleError, Champion, ChampionBuilder, LineSpans};
+use compiler::{compile_champion, compile_champion_exhaustive, CompileError, CompileOptions};
+use diagnostic::{Diagnostic, Span};
+use include::{inline_includes, FileResolver, IncludeError, NoFiles};
+use lexer::{Term, Tokenizer};
+use macros::{MacroError, MacroExpander};
 use parser::{parse_line, ParseError};
 
-use std::io::{BufRead, BufReader, Cursor, Error as IOError, Read, Write};
+use crate::rules::RuleSet;
 
+use std::{
+    io::{Cursor, Error as IOError, Read, Write},
+    path::Path,
+    rc::Rc,
+};
+
+/// Reads a champion that cannot include other files
 pub fn read_champion(input: impl Read) -> Result<Champion, ReadError> {
-    let mut reader = BufReader::new(input);
-    let mut buffer = String::with_capacity(128);
-    let mut line_no = 1;
+    read_champion_with_includes(input, None, &NoFiles)
+}
+
+/// Reads a champion, with the files it includes read by `resolver`.
+/// `path` is the location of `input`, the included paths being relative to it
+pub fn read_champion_with_includes(
+    mut input: impl Read,
+    path: Option<&Path>,
+    resolver: &dyn FileResolver,
+) -> Result<Champion, ReadError> {
+    let mut source = String::with_capacity(4096);
+    input.read_to_string(&mut source)?;
 
     let mut champ_builder = ChampionBuilder::default();
+    let mut expander = MacroExpander::default();
 
-    while reader.read_line(&mut buffer)? > 0 {
-        let parsed_line = parse_line(&buffer).map_err(|e| ReadError::ParseError(e, line_no))?;
-        champ_builder.assemble(parsed_line)?;
-        line_no += 1;
-        buffer.clear();
+    for source_line in inline_includes(&source, path, resolver) {
+        for line in expander.expand(&source_line?)? {
+            let parsed_line = parse_line(&line.text).map_err(|e| {
+                let span = parse_error_span(line.file.clone(), line.line, &line.text, &e);
+                ReadError::ParseError(e, span)
+            })?;
+            champ_builder.assemble(parsed_line, &line.line_spans())?;
+        }
     }
+    expander.finish()?;
 
     Ok(champ_builder.finish()?)
 }
 
-pub fn write_champion(mut output: impl Write, champion: Champion) -> Result<usize, WriteError> {
+pub fn write_champion(
+    mut output: impl Write,
+    champion: Champion,
+    rules: &RuleSet,
+) -> Result<usize, WriteError> {
     let mut seek_vec = Cursor::new(Vec::with_capacity(8192));
 
-    compile_champion(&mut seek_vec, champion)?;
+    compile_champion(&mut seek_vec, champion, rules)?;
 
     let data = seek_vec.get_ref();
     output.write_all(data)?;
     Ok(data.len())
 }
 
+/// A successfully compiled champion
+#[derive(Debug)]
+pub struct Compilation {
+    pub name: String,
+    pub comment: String,
+    /// The header included
+    pub byte_code: Vec<u8>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Reads, lints and compiles a champion source that cannot include other files.
+/// Unlike [`read_champion`] and [`write_champion`], every line is read even
+/// after an error so that all the diagnostics are reported together, sorted by
+/// position. Compile errors and warnings are only reported once every line
+/// could be read
+pub fn compile_source(
+    input: &str,
+    rules: &RuleSet,
+    options: &CompileOptions,
+) -> Result<Compilation, Vec<Diagnostic>> {
+    compile_with_includes(input, None, &NoFiles, rules, options)
+}
+
+/// [`compile_source`], with the files included by `input` read by `resolver`.
+/// `path` is the location of `input`, the included paths being relative to it
+pub fn compile_with_includes(
+    input: &str,
+    path: Option<&Path>,
+    resolver: &dyn FileResolver,
+    rules: &RuleSet,
+    options: &CompileOptions,
+) -> Result<Compilation, Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+    let mut champ_builder = ChampionBuilder::default();
+    let mut expander = MacroExpander::default();
+
+    for source_line in inline_includes(input, path, resolver) {
+        let expanded_lines = match source_line {
+            Ok(line) => match expander.expand(&line) {
+                Ok(expanded_lines) => expanded_lines,
+                Err(e) => {
+                    diagnostics.push(e.into());
+                    continue;
+                }
+            },
+            Err(e) => {
+                diagnostics.push(e.into());
+                continue;
+            }
+        };
+
+        for line in expanded_lines {
+            let spans = line.line_spans();
+
+            match parse_line(&line.text) {
+                Ok(parsed_line) => {
+                    if let Err(e) = champ_builder.assemble(parsed_line, &spans) {
+                        diagnostics.push(
+                            Diagnostic::error(Some(spans.statement), e)
+                                .with_call_sites(spans.call_sites),
+                        );
+                    }
+                }
+                Err(e) => {
+                    let span = line.span(error_columns(&line.text, &e));
+                    diagnostics
+                        .push(Diagnostic::error(Some(span), e).with_call_sites(spans.call_sites));
+                }
+            }
+        }
+    }
+    if let Err(e) = expander.finish() {
+        diagnostics.push(e.into());
+    }
+
+    match champ_builder.finish() {
+        Err(e) => diagnostics.push(Diagnostic::error(None, e)),
+        Ok(champion) if diagnostics.is_empty() => {
+            let name = champion.name.clone();
+            let comment = champion.comment.clone();
+            let spans = champion.spans.clone();
+            let call_sites = champion.call_sites.clone();
+            diagnostics.extend(lint::lint(&champion, rules, options));
+
+            let mut out = Cursor::new(Vec::with_capacity(8192));
+            match compile_champion_exhaustive(&mut out, champion, rules, options) {
+                Ok(_) => {
+                    return Ok(Compilation {
+                        name,
+                        comment,
+                        byte_code: out.into_inner(),
+                        warnings: sorted(diagnostics),
+                    })
+                }
+                Err(errors) => diagnostics.extend(errors.into_iter().map(|e| {
+                    let span = e.instruction.and_then(|idx| spans.get(idx).cloned());
+                    let call_sites = e.instruction.and_then(|idx| call_sites.get(idx).cloned());
+                    let definitions = e
+                        .constants
+                        .iter()
+                        .filter_map(|&idx| spans.get(idx).cloned())
+                        .collect();
+                    Diagnostic::error(span, e.error)
+                        .with_call_sites(call_sites.unwrap_or_default())
+                        .with_constant_definitions(definitions)
+                })),
+            }
+        }
+        Ok(_) => (),
+    }
+
+    Err(sorted(diagnostics))
+}
+
+/// Champion wide diagnostics come first, then those of the main source and
+/// those of the included files, by file name
+fn sorted(mut diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    diagnostics.sort_by_key(|d| {
+        d.span
+            .as_ref()
+            .map(|span| (span.file.clone(), span.line, span.columns.start))
+    });
+    diagnostics
+}
+
+/// The columns of `line` that a parse error points to: up to the end of the
+/// line when the error has no end
+fn error_columns(line: &str, error: &ParseError) -> std::ops::Range<usize> {
+    let (start, end) = error_range(error);
+    start..end.unwrap_or_else(|| line.trim_end().len())
+}
+
+fn parse_error_span(file: Option<Rc<str>>, line_no: usize, line: &str, error: &ParseError) -> Span {
+    Span {
+        file,
+        line: line_no,
+        columns: error_columns(line, error),
+    }
+}
+
+/// Locates the label declared on a line, and the statement that follows it.
+/// Both are the whole line, comments and surrounding whitespace excluded,
+/// when there is no label
+fn line_spans(file: Option<Rc<str>>, line_no: usize, line: &str) -> LineSpans {
+    let mut tokens = Vec::new();
+    let mut content_end = line.len();
+    for token in Tokenizer::new(line).filter_map(Result::ok) {
+        if token.term == Term::Comment {
+            content_end = token.range.start;
+            break;
+        }
+        tokens.push(token);
+    }
+    let content_end = line[..content_end].trim_end().len();
+    let span = |columns| Span {
+        file: file.clone(),
+        line: line_no,
+        columns,
+    };
+
+    match tokens.split_first() {
+        Some((label, rest)) if label.term == Term::LabelDef => LineSpans {
+            label: span(label.range.clone()),
+            statement: span(rest.first().map_or(label.range.end, |t| t.range.start)..content_end),
+            call_sites: Vec::new(),
+        },
+        _ => {
+            let columns = tokens.first().map_or(content_end, |t| t.range.start)..content_end;
+            LineSpans {
+                label: span(columns.clone()),
+                statement: span(columns),
+                call_sites: Vec::new(),
+            }
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ReadError {
     #[error("IO error while reading champion: {0}")]
     IOError(#[from] IOError),
-    #[error("Parse error on line {1}: {0}")]
-    ParseError(ParseError, usize),
+    #[error("Parse error on {1}: {0}")]
+    ParseError(ParseError, Span),
     #[error("Error assembling champion: {0}")]
     AssembleError(#[from] AssembleError),
+    #[error("Macro error on {}: {0}", .0.span)]
+    MacroError(#[from] MacroError),
+    #[error("Include error on {}: {0}", .0.span)]
+    IncludeError(#[from] IncludeError),
+}
+
+impl From<IncludeError> for Diagnostic {
+    fn from(error: IncludeError) -> Self {
+        Diagnostic::error(Some(error.span), error.kind)
+    }
+}
+
+impl From<MacroError> for Diagnostic {
+    fn from(error: MacroError) -> Self {
+        Diagnostic::error(Some(error.span), error.kind).with_call_sites(error.call_sites)
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
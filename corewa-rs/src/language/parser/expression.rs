@@ -0,0 +1,98 @@
+//! Operand expressions: `*` and `/` bind tighter than `+` and `-`, all of them
+//! being left associative
+
+use super::{combinator::*, label_param, number, ParseError, ParseResult, TokenStream};
+use crate::language::{
+    lexer::{Term, Token},
+    types::{BinaryOp, Expr},
+};
+
+pub(super) fn expression(input: &mut TokenStream<'_>) -> ParseResult<Expr> {
+    let mut lhs = product(input)?;
+
+    loop {
+        let op = match peek_term(input) {
+            Some(Term::Plus) => BinaryOp::Add,
+            Some(Term::Minus) => BinaryOp::Sub,
+            // `:end -4` is lexed as a label followed by the number `-4`,
+            // which starts the next product
+            Some(Term::Number { .. }) if peek_str(input).starts_with('-') => {
+                let first = Expr::Number(number(input)?);
+                lhs = Expr::binary(BinaryOp::Add, lhs, product_from(input, first)?);
+                continue;
+            }
+            _ => return Ok(lhs),
+        };
+        input.tokens.next();
+        lhs = Expr::binary(op, lhs, product(input)?);
+    }
+}
+
+fn product(input: &mut TokenStream<'_>) -> ParseResult<Expr> {
+    let first = operand(input)?;
+    product_from(input, first)
+}
+
+/// The rest of a product whose first operand is `lhs`
+fn product_from(input: &mut TokenStream<'_>, mut lhs: Expr) -> ParseResult<Expr> {
+    loop {
+        let op = match peek_term(input) {
+            Some(Term::Star) => BinaryOp::Mul,
+            Some(Term::Slash) => BinaryOp::Div,
+            _ => return Ok(lhs),
+        };
+        input.tokens.next();
+        lhs = Expr::binary(op, lhs, operand(input)?);
+    }
+}
+
+fn operand(input: &mut TokenStream<'_>) -> ParseResult<Expr> {
+    number
+        .map(Expr::Number)
+        .or(label_param.map(Expr::Label))
+        .or(constant.map(Expr::Constant))
+        .or(parenthesized)
+        .or(negated)
+        .map_err(|((((e1, e2), e3), e4), e5)| ParseError::ExpectedOneOf(vec![e1, e2, e3, e4, e5]))
+        .parse(input)
+}
+
+/// Identifiers made of an `r` and digits are kept for registers, even the
+/// invalid ones
+pub(super) fn constant(input: &mut TokenStream<'_>) -> ParseResult<String> {
+    let (tok, name) = input.next_with_token(Term::Ident)?;
+
+    match name.strip_prefix('r') {
+        Some(digits) if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => {
+            Err(ParseError::RegisterAsConstant(tok))
+        }
+        _ => Ok(String::from(name)),
+    }
+}
+
+fn parenthesized(input: &mut TokenStream<'_>) -> ParseResult<Expr> {
+    input.next(Term::OpenParen)?;
+    let expr = expression(input)?;
+    input.next(Term::CloseParen)?;
+    Ok(expr)
+}
+
+fn negated(input: &mut TokenStream<'_>) -> ParseResult<Expr> {
+    input.next(Term::Minus)?;
+    operand(input).map(|expr| Expr::Neg(Box::new(expr)))
+}
+
+fn peek_term(input: &mut TokenStream<'_>) -> Option<Term> {
+    match input.peek() {
+        Some(Ok(Token { term, .. })) => Some(*term),
+        _ => None,
+    }
+}
+
+fn peek_str<'a>(input: &mut TokenStream<'a>) -> &'a str {
+    let source = input.input;
+    match input.peek() {
+        Some(Ok(Token { range, .. })) => &source[range.clone()],
+        _ => "",
+    }
+}
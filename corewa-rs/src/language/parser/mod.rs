@@ -1,16 +1,19 @@
 mod combinator;
+mod expression;
 
 use super::{
     lexer::{LexerError, NumberBase, Term, Token, TokenResult, Tokenizer},
     types::*,
 };
 use combinator::*;
+use expression::{constant, expression};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum ParsedLine {
     ChampionName(String),
     ChampionComment(String),
-    Code(Vec<u8>),
+    Code(Vec<i64>),
+    Constant(String, Expr),
     Op(Op),
     Label(String),
     LabelAndOp(String, Op),
@@ -29,6 +32,9 @@ pub fn parse_line(input: &str) -> Result<ParsedLine, ParseError> {
         Term::ChampionNameCmd => champion_name(&mut tokens).map(ParsedLine::ChampionName),
         Term::ChampionCommentCmd => champion_comment(&mut tokens).map(ParsedLine::ChampionComment),
         Term::CodeCmd => code(&mut tokens).map(ParsedLine::Code),
+        Term::ConstantCmd => {
+            constant_definition(&mut tokens).map(|(name, value)| ParsedLine::Constant(name, value))
+        }
         Term::LabelDef => {
             let label = label(&mut tokens)?;
 
@@ -48,13 +54,73 @@ pub fn parse_line(input: &str) -> Result<ParsedLine, ParseError> {
         _ => return Err(ParseError::Unexpected(first_tok)),
     }?;
 
-    // Remaining input besides comments => Error
+    end_of_line(&mut tokens)?;
+    Ok(parse_result)
+}
+
+/// Parses the first line of a macro definition, `.macro NAME param, ...`,
+/// into the name of the macro and its parameters
+pub fn parse_macro_start(input: &str) -> Result<(String, Vec<String>), ParseError> {
+    let mut tokens = TokenStream::new(input);
+
+    tokens.next(Term::MacroCmd)?;
+    let name = constant(&mut tokens)?;
+    let mut params = Vec::new();
+    if let Some(Ok(Token {
+        term: Term::Ident, ..
+    })) = tokens.peek()
+    {
+        params.push(constant(&mut tokens)?);
+        while let Some(Ok(Token {
+            term: Term::ParamSeparator,
+            ..
+        })) = tokens.peek()
+        {
+            tokens.next(Term::ParamSeparator)?;
+            params.push(constant(&mut tokens)?);
+        }
+    }
+
+    end_of_line(&mut tokens)?;
+    Ok((name, params))
+}
+
+pub fn parse_macro_end(input: &str) -> Result<(), ParseError> {
+    let mut tokens = TokenStream::new(input);
+
+    tokens.next(Term::EndMacroCmd)?;
+    end_of_line(&mut tokens)
+}
+
+/// Parses an `.include "path"` line into the included path
+pub fn parse_include(input: &str) -> Result<String, ParseError> {
+    let mut tokens = TokenStream::new(input);
+
+    tokens.next(Term::IncludeCmd)?;
+    let path = tokens.next(Term::QuotedString).map(String::from)?;
+
+    end_of_line(&mut tokens)?;
+    Ok(path)
+}
+
+/// Whether `ident` is the mnemonic of an operation
+pub fn is_op_mnemonic(ident: &str) -> bool {
+    MNEMONICS.contains(&ident)
+}
+
+const MNEMONICS: [&str; 16] = [
+    "live", "ld", "st", "add", "sub", "and", "or", "xor", "zjmp", "ldi", "sti", "fork", "lld",
+    "lldi", "lfork", "aff",
+];
+
+/// Only comments can remain
+fn end_of_line(tokens: &mut TokenStream<'_>) -> ParseResult<()> {
     match tokens.peek() {
         None
         | Some(Ok(Token {
             term: Term::Comment,
             ..
-        })) => Ok(parse_result),
+        })) => Ok(()),
         Some(Ok(token)) => Err(ParseError::RemainingInput(token.clone())),
         Some(Err(lex_error)) => Err(ParseError::LexerError(lex_error.clone())),
     }
@@ -72,12 +138,16 @@ fn champion_comment(input: &mut TokenStream<'_>) -> ParseResult<String> {
     input.next(Term::QuotedString).map(String::from)
 }
 
-fn code(input: &mut TokenStream<'_>) -> ParseResult<Vec<u8>> {
+fn code(input: &mut TokenStream<'_>) -> ParseResult<Vec<i64>> {
     input.next(Term::CodeCmd)?;
-    let numbers = number.many().parse(input)?;
-    // TODO: Better enforce numeric limit invariants
-    let as_bytes = numbers.into_iter().map(|x| x as u8).collect();
-    Ok(as_bytes)
+    number.many().parse(input)
+}
+
+fn constant_definition(input: &mut TokenStream<'_>) -> ParseResult<(String, Expr)> {
+    input.next(Term::ConstantCmd)?;
+    let name = constant(input)?;
+    input.next(Term::ParamSeparator)?;
+    Ok((name, expression(input)?))
 }
 
 fn label(input: &mut TokenStream<'_>) -> ParseResult<String> {
@@ -161,19 +231,11 @@ fn register(input: &mut TokenStream<'_>) -> ParseResult<Register> {
 
 fn direct(input: &mut TokenStream<'_>) -> ParseResult<Direct> {
     input.next(Term::DirectChar)?;
-    label_param
-        .map(Direct::Label)
-        .or(number.map(Direct::Numeric))
-        .map_err(expected_either)
-        .parse(input)
+    expression(input).map(Direct::from)
 }
 
 fn indirect(input: &mut TokenStream<'_>) -> ParseResult<Indirect> {
-    label_param
-        .map(Indirect::Label)
-        .or(number.map(Indirect::Numeric))
-        .map_err(expected_either)
-        .parse(input)
+    expression(input).map(Indirect::from)
 }
 
 fn reg_dir(input: &mut TokenStream<'_>) -> ParseResult<RegDir> {
@@ -295,6 +357,7 @@ pub enum ParseError {
     ParseIntError(std::num::ParseIntError, Token),
     RegisterParseIntError(std::num::ParseIntError, Token),
     InvalidOpMnemonic(String, Token),
+    RegisterAsConstant(Token),
 }
 
 fn expected_either((e1, e2): (ParseError, ParseError)) -> ParseError {
@@ -355,6 +418,7 @@ impl fmt::Display for ParseError {
             ParseIntError(err, _) => write!(f, "Invalid number: {}", err),
             RegisterParseIntError(err, _) => write!(f, "Invalid register number: {}", err),
             InvalidOpMnemonic(mnemonic, _) => write!(f, "'{}' is not a valid operation", mnemonic),
+            RegisterAsConstant(_) => write!(f, "Register names cannot be used as constants"),
         }
     }
 }
@@ -386,6 +450,7 @@ pub fn error_range(err: &ParseError) -> (usize, Option<usize>) {
         | MissingRegisterPrefix(token)
         | ParseIntError(_, token)
         | RegisterParseIntError(_, token)
-        | InvalidOpMnemonic(_, token) => (token.range.start, Some(token.range.end)),
+        | InvalidOpMnemonic(_, token)
+        | RegisterAsConstant(token) => (token.range.start, Some(token.range.end)),
     }
 }
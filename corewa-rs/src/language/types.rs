@@ -30,11 +30,90 @@ pub struct Register(pub u8);
 pub enum Direct {
     Label(String),
     Numeric(i64),
+    /// Any other expression, bare labels and numbers excluded
+    #[from(ignore)]
+    Expr(Expr),
 }
 #[derive(Debug, PartialEq, Eq, From)]
 pub enum Indirect {
     Label(String),
     Numeric(i64),
+    /// Any other expression, bare labels and numbers excluded
+    #[from(ignore)]
+    Expr(Expr),
+}
+
+/// The value of a parameter, computed once every label is known
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    /// The offset from the op to the label, like a label parameter
+    Label(String),
+    /// A name defined with `.equ`
+    Constant(String),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    /// Rounds toward zero
+    Div,
+}
+
+impl Expr {
+    pub fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Self {
+        Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+    }
+
+    /// Calls `f` on every label and constant of the expression, in order
+    pub fn visit_names<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        match self {
+            Expr::Number(_) => (),
+            Expr::Label(_) | Expr::Constant(_) => f(self),
+            Expr::Neg(expr) => expr.visit_names(f),
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.visit_names(f);
+                rhs.visit_names(f);
+            }
+        }
+    }
+
+    /// Calls `f` on the name of every label of the expression, in order
+    pub fn visit_labels_mut(&mut self, f: &mut impl FnMut(&mut String)) {
+        match self {
+            Expr::Label(label) => f(label),
+            Expr::Number(_) | Expr::Constant(_) => (),
+            Expr::Neg(expr) => expr.visit_labels_mut(f),
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.visit_labels_mut(f);
+                rhs.visit_labels_mut(f);
+            }
+        }
+    }
+}
+
+impl From<Expr> for Direct {
+    fn from(expr: Expr) -> Self {
+        match expr {
+            Expr::Number(n) => Direct::Numeric(n),
+            Expr::Label(label) => Direct::Label(label),
+            expr => Direct::Expr(expr),
+        }
+    }
+}
+
+impl From<Expr> for Indirect {
+    fn from(expr: Expr) -> Self {
+        match expr {
+            Expr::Number(n) => Indirect::Numeric(n),
+            Expr::Label(label) => Indirect::Label(label),
+            expr => Indirect::Expr(expr),
+        }
+    }
 }
 
 #[enum_dispatch(ToParamCode)]
@@ -86,3 +165,88 @@ impl ToParamCode for Indirect {
         IND_PARAM_CODE
     }
 }
+
+/// A parameter of an [`Op`], whatever the types it accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpParam<'a> {
+    Reg(&'a Register),
+    Dir(&'a Direct),
+    Ind(&'a Indirect),
+}
+
+impl<'a> From<&'a Register> for OpParam<'a> {
+    fn from(reg: &'a Register) -> Self {
+        OpParam::Reg(reg)
+    }
+}
+
+impl<'a> From<&'a Direct> for OpParam<'a> {
+    fn from(dir: &'a Direct) -> Self {
+        OpParam::Dir(dir)
+    }
+}
+
+impl<'a> From<&'a RegDir> for OpParam<'a> {
+    fn from(rd: &'a RegDir) -> Self {
+        match rd {
+            RegDir::Reg(reg) => OpParam::Reg(reg),
+            RegDir::Dir(dir) => OpParam::Dir(dir),
+        }
+    }
+}
+
+impl<'a> From<&'a RegInd> for OpParam<'a> {
+    fn from(ri: &'a RegInd) -> Self {
+        match ri {
+            RegInd::Reg(reg) => OpParam::Reg(reg),
+            RegInd::Ind(ind) => OpParam::Ind(ind),
+        }
+    }
+}
+
+impl<'a> From<&'a DirInd> for OpParam<'a> {
+    fn from(di: &'a DirInd) -> Self {
+        match di {
+            DirInd::Dir(dir) => OpParam::Dir(dir),
+            DirInd::Ind(ind) => OpParam::Ind(ind),
+        }
+    }
+}
+
+impl<'a> From<&'a AnyParam> for OpParam<'a> {
+    fn from(any: &'a AnyParam) -> Self {
+        match any {
+            AnyParam::Reg(reg) => OpParam::Reg(reg),
+            AnyParam::Dir(dir) => OpParam::Dir(dir),
+            AnyParam::Ind(ind) => OpParam::Ind(ind),
+        }
+    }
+}
+
+impl Op {
+    pub fn params(&self) -> Vec<OpParam<'_>> {
+        use Op::*;
+
+        #[rustfmt::skip]
+        let params = match self {
+            Live  (d         ) => vec![d.into()                      ],
+            Ld    (di, r     ) => vec![di.into(), r.into()           ],
+            St    (r,  ri    ) => vec![r.into(),  ri.into()          ],
+            Add   (r1, r2, r3) => vec![r1.into(), r2.into(), r3.into()],
+            Sub   (r1, r2, r3) => vec![r1.into(), r2.into(), r3.into()],
+            And   (a1, a2, r ) => vec![a1.into(), a2.into(), r.into() ],
+            Or    (a1, a2, r ) => vec![a1.into(), a2.into(), r.into() ],
+            Xor   (a1, a2, r ) => vec![a1.into(), a2.into(), r.into() ],
+            Zjmp  (d         ) => vec![d.into()                      ],
+            Ldi   (a,  rd, r ) => vec![a.into(),  rd.into(), r.into() ],
+            Sti   (r,  a,  rd) => vec![r.into(),  a.into(),  rd.into()],
+            Fork  (d         ) => vec![d.into()                      ],
+            Lld   (di, r     ) => vec![di.into(), r.into()           ],
+            Lldi  (a,  rd, r ) => vec![a.into(),  rd.into(), r.into() ],
+            Lfork (d         ) => vec![d.into()                      ],
+            Aff   (r         ) => vec![r.into()                      ],
+        };
+
+        params
+    }
+}
@@ -1,5 +1,7 @@
 #![forbid(unsafe_code)]
 
 pub mod language;
+pub mod rules;
 pub mod spec;
+pub mod tournament;
 pub mod vm;
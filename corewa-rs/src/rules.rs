@@ -0,0 +1,307 @@
+use crate::spec::*;
+
+use std::{fs, io::Error as IOError, path::Path, str::FromStr};
+
+/// The largest memory size allowed, which is also the largest one accepted
+/// when reading a snapshot
+pub const MAX_MEM_SIZE: usize = 1 << 20;
+
+/// The tunable parameters of a match.
+/// The default rule set uses the values from [`crate::spec`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSet {
+    pub mem_size: usize,
+    pub idx_mod: usize,
+    pub champ_max_size: usize,
+
+    pub check_interval: u32,
+    pub cycle_delta: u32,
+    pub nbr_live: u32,
+    pub max_checks: u32,
+
+    /// Cannot exceed [`REG_COUNT`] which is the register capacity of processes
+    pub reg_count: usize,
+
+    pub ops: OpTable,
+
+    pub semantics: Semantics,
+}
+
+/// How the VM resolves the behaviors where implementations disagree
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Semantics {
+    /// The semantics documented in the README
+    #[default]
+    Native,
+    /// Reproduces the quirks of the reference 42 VM:
+    /// - an instruction that fails to decode moves the pc past the size
+    ///   computed from its pcb instead of a single byte
+    /// - `ldi` affects `zf` like `lldi`
+    /// - the live-check only kills processes that didn't report any live since
+    ///   the previous one, or every process once the check interval reaches 0
+    /// - forked processes inherit the last live of their parent
+    ///
+    /// The processes still run in the native order, reproducing the process
+    /// execution order of the reference VM is out of scope
+    Compat,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            mem_size: MEM_SIZE,
+            idx_mod: IDX_MOD,
+            champ_max_size: CHAMP_MAX_SIZE,
+
+            check_interval: CHECK_INTERVAL,
+            cycle_delta: CYCLE_DELTA,
+            nbr_live: NBR_LIVE,
+            max_checks: MAX_CHECKS,
+
+            reg_count: REG_COUNT,
+
+            ops: OpTable::default(),
+
+            semantics: Semantics::Native,
+        }
+    }
+}
+
+impl RuleSet {
+    /// The default rules with the semantics of the reference VM
+    pub fn compat() -> Self {
+        Self {
+            semantics: Semantics::Compat,
+            ..Self::default()
+        }
+    }
+
+    /// Loads a preset file. See [`RuleSet::from_str`] for the format
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RuleSetError> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    pub fn validate(&self) -> Result<(), RuleSetError> {
+        use RuleSetError::*;
+
+        if self.mem_size == 0 {
+            return Err(EmptyMemory);
+        }
+        if self.mem_size > MAX_MEM_SIZE {
+            return Err(MemoryTooBig(self.mem_size));
+        }
+        if self.idx_mod == 0 || self.idx_mod > self.mem_size {
+            return Err(InvalidIdxMod(self.idx_mod));
+        }
+        if self.champ_max_size > self.mem_size {
+            return Err(ChampionTooBig(self.champ_max_size));
+        }
+        if !(1..=REG_COUNT).contains(&self.reg_count) {
+            return Err(InvalidRegCount(self.reg_count));
+        }
+        // Without a decrement, champions reporting lives would never die
+        if self.cycle_delta == 0 {
+            return Err(NoCycleDelta);
+        }
+        if self.max_checks == 0 {
+            return Err(NoMaxChecks);
+        }
+
+        for op_type in OP_TYPES {
+            let spec = self.ops.get(op_type);
+            let masks = &spec.param_masks[..spec.param_count];
+
+            if spec.cycles == 0 || masks.contains(&0) {
+                return Err(InvalidOpSpec(op_type));
+            }
+
+            let supported_masks = supported_param_masks(op_type);
+            let unsupported = masks
+                .iter()
+                .zip(supported_masks)
+                .any(|(mask, supported)| mask & !supported != 0);
+            if unsupported {
+                return Err(UnsupportedParamTypes(op_type));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        fn parse<T: FromStr>(value: &str) -> Result<T, String> {
+            value
+                .parse()
+                .map_err(|_| format!("invalid value '{}'", value))
+        }
+
+        match key {
+            "mem_size" => self.mem_size = parse(value)?,
+            "idx_mod" => self.idx_mod = parse(value)?,
+            "champ_max_size" => self.champ_max_size = parse(value)?,
+            "check_interval" => self.check_interval = parse(value)?,
+            "cycle_delta" => self.cycle_delta = parse(value)?,
+            "nbr_live" => self.nbr_live = parse(value)?,
+            "max_checks" => self.max_checks = parse(value)?,
+            "reg_count" => self.reg_count = parse(value)?,
+            "semantics" => {
+                self.semantics = match value {
+                    "native" => Semantics::Native,
+                    "compat" => Semantics::Compat,
+                    _ => return Err(format!("invalid value '{}'", value)),
+                }
+            }
+            _ => return self.set_op(key, value),
+        }
+
+        Ok(())
+    }
+
+    fn set_op(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let unknown_rule = || format!("unknown rule '{}'", key);
+
+        let (mnemonic, field) = key.split_once('.').ok_or_else(unknown_rule)?;
+        let op_type = OP_TYPES
+            .iter()
+            .copied()
+            .find(|op_type| op_type.to_string().eq_ignore_ascii_case(mnemonic))
+            .ok_or_else(unknown_rule)?;
+        let spec = self.ops.get_mut(op_type);
+        let invalid_value = || format!("invalid value '{}'", value);
+
+        match field {
+            "cycles" => spec.cycles = value.parse().map_err(|_| invalid_value())?,
+            "dir_size" => {
+                spec.dir_size = match value {
+                    "2" => DirectSize::TwoBytes,
+                    "4" => DirectSize::FourBytes,
+                    _ => return Err(invalid_value()),
+                }
+            }
+            "reach" => {
+                spec.reach = match value {
+                    "limited" => OffsetType::Limited,
+                    "long" => OffsetType::Long,
+                    _ => return Err(invalid_value()),
+                }
+            }
+            "params" => {
+                let masks = value.split(',').map(|types| {
+                    types.trim().chars().try_fold(0, |mask, c| match c {
+                        'R' => Some(mask | T_REG),
+                        'D' => Some(mask | T_DIR),
+                        'I' => Some(mask | T_IND),
+                        _ => None,
+                    })
+                });
+
+                let mut param_masks = [0; MAX_PARAMS];
+                let mut count = 0;
+                for mask in masks {
+                    let slot = param_masks.get_mut(count).ok_or_else(invalid_value)?;
+                    *slot = mask.ok_or_else(invalid_value)?;
+                    count += 1;
+                }
+                if count != spec.param_count {
+                    return Err(format!(
+                        "'{}' takes {} parameters",
+                        mnemonic, spec.param_count
+                    ));
+                }
+                spec.param_masks = param_masks;
+            }
+            _ => return Err(unknown_rule()),
+        }
+
+        Ok(())
+    }
+}
+
+/// The parameter types that each operation is able to execute with.
+/// Registers that are written to or read as registers cannot change type, and
+/// ops without a pcb have their parameter types implied by the encoding.
+/// Any other parameter is read as a value and can be of any type
+fn supported_param_masks(op_type: OpType) -> [u8; MAX_PARAMS] {
+    use OpType::*;
+
+    const ANY: u8 = T_REG | T_DIR | T_IND;
+
+    match op_type {
+        Live | Zjmp | Fork | Lfork => op_spec(op_type).param_masks,
+        Ld | Lld => [ANY, T_REG, 0],
+        St => [T_REG, T_REG | T_IND, 0],
+        Add | Sub => [T_REG, T_REG, T_REG],
+        And | Or | Xor | Ldi | Lldi => [ANY, ANY, T_REG],
+        Sti => [T_REG, ANY, ANY],
+        Aff => [T_REG, 0, 0],
+    }
+}
+
+/// Parses a preset made of `key = value` lines.
+/// Keys are the names of the [`RuleSet`] fields, `#` starts a line comment
+/// and every missing key keeps its default value.
+/// Operations can be tweaked with `<mnemonic>.<field>` keys, where field is
+/// one of `cycles`, `dir_size` (2 or 4), `reach` (limited or long) and
+/// `params` (comma separated combinations of `R`, `D` and `I`).
+/// `semantics` is either `native` or `compat`, see [`Semantics`]:
+/// ```text
+/// # A smaller arena
+/// mem_size = 2048
+/// idx_mod  = 256
+///
+/// # Cheaper forks and long reaching stores
+/// fork.cycles = 400
+/// sti.reach   = long
+/// sti.params  = R, RDI, RDI
+/// ```
+impl FromStr for RuleSet {
+    type Err = RuleSetError;
+
+    fn from_str(preset: &str) -> Result<Self, Self::Err> {
+        let mut rules = Self::default();
+
+        for (line, line_no) in preset.lines().zip(1..) {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| RuleSetError::InvalidLine(line_no, String::from("missing '='")))?;
+
+            rules
+                .set(key.trim(), value.trim())
+                .map_err(|reason| RuleSetError::InvalidLine(line_no, reason))?;
+        }
+
+        rules.validate()?;
+        Ok(rules)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RuleSetError {
+    #[error("IO error while reading rule set: {0}")]
+    IOError(#[from] IOError),
+    #[error("Invalid rule on line {0}: {1}")]
+    InvalidLine(usize, String),
+    #[error("The memory size cannot be 0")]
+    EmptyMemory,
+    #[error("The memory size ({0}) cannot exceed {}", MAX_MEM_SIZE)]
+    MemoryTooBig(usize),
+    #[error("Invalid IDX_MOD: {0} (must be between 1 and the memory size)")]
+    InvalidIdxMod(usize),
+    #[error("The maximum champion size ({0}) cannot exceed the memory size")]
+    ChampionTooBig(usize),
+    #[error("Invalid register count: {0} (must be between 1 and {})", REG_COUNT)]
+    InvalidRegCount(usize),
+    #[error("The cycle delta cannot be 0, matches would never end")]
+    NoCycleDelta,
+    #[error("The maximum number of checks cannot be 0")]
+    NoMaxChecks,
+    #[error("Some parameter types of '{0}' are not supported")]
+    UnsupportedParamTypes(OpType),
+    #[error("Invalid spec for '{0}': it must take at least 1 cycle and accept every parameter")]
+    InvalidOpSpec(OpType),
+}
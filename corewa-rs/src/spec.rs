@@ -6,7 +6,7 @@ pub const PROG_COMMENT_LENGTH: usize = 2048;
 pub type ProgName = [u8; PROG_NAME_LENGTH + 1];
 pub type ProgComment = [u8; PROG_COMMENT_LENGTH + 1];
 
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Header {
     pub magic: u32,
     pub prog_name: ProgName,
@@ -34,7 +34,7 @@ pub const MAX_CHECKS: u32 = 10;
 pub const REG_COUNT: usize = 16;
 pub const MAX_PARAMS: usize = 3;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct OpSpec {
     pub code: u8,
     pub cycles: u32,
@@ -42,15 +42,22 @@ pub struct OpSpec {
     pub param_masks: [u8; MAX_PARAMS],
     pub has_pcb: bool,
     pub dir_size: DirectSize,
+    pub reach: OffsetType,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DirectSize {
     TwoBytes = 2,
     FourBytes = 4,
 }
 
-#[derive(Debug, Clone, Copy, derive_more::Display)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffsetType {
+    Limited,
+    Long,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
 pub enum OpType {
     Live = 1,
     Ld,
@@ -70,8 +77,9 @@ pub enum OpType {
     Aff,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum ParamType {
+    #[default]
     Register,
     Direct,
     Indirect,
@@ -81,8 +89,37 @@ pub const T_REG: u8 = 1;
 pub const T_DIR: u8 = 2;
 pub const T_IND: u8 = 4;
 
+pub const OP_TYPES: [OpType; 16] = {
+    use OpType::*;
+    [
+        Live, Ld, St, Add, Sub, And, Or, Xor, Zjmp, Ldi, Sti, Fork, Lld, Lldi, Lfork, Aff,
+    ]
+};
+
+/// An instruction set, indexed by [`OpType`].
+/// The default table is the one described by [`op_spec`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpTable([OpSpec; OP_TYPES.len()]);
+
+impl OpTable {
+    pub fn get(&self, op_type: OpType) -> &OpSpec {
+        &self.0[op_type as usize - 1]
+    }
+
+    pub fn get_mut(&mut self, op_type: OpType) -> &mut OpSpec {
+        &mut self.0[op_type as usize - 1]
+    }
+}
+
+impl Default for OpTable {
+    fn default() -> Self {
+        Self(OP_TYPES.map(op_spec))
+    }
+}
+
 pub const fn op_spec(op_type: OpType) -> OpSpec {
     use DirectSize::*;
+    use OffsetType::*;
     use OpType::*;
 
     let code = op_type as u8;
@@ -95,6 +132,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_DIR, 0, 0],
             has_pcb: false,
             dir_size: FourBytes,
+            reach: Limited,
         },
         Ld => OpSpec {
             code,
@@ -103,6 +141,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_DIR | T_IND, T_REG, 0],
             has_pcb: true,
             dir_size: FourBytes,
+            reach: Limited,
         },
         St => OpSpec {
             code,
@@ -111,6 +150,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_REG, T_REG | T_IND, 0],
             has_pcb: true,
             dir_size: FourBytes,
+            reach: Limited,
         },
         Add => OpSpec {
             code,
@@ -119,6 +159,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_REG, T_REG, T_REG],
             has_pcb: true,
             dir_size: FourBytes,
+            reach: Limited,
         },
         Sub => OpSpec {
             code,
@@ -127,6 +168,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_REG, T_REG, T_REG],
             has_pcb: true,
             dir_size: FourBytes,
+            reach: Limited,
         },
         And => OpSpec {
             code,
@@ -135,6 +177,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_REG | T_DIR | T_IND, T_REG | T_DIR | T_IND, T_REG],
             has_pcb: true,
             dir_size: FourBytes,
+            reach: Limited,
         },
         Or => OpSpec {
             code,
@@ -143,6 +186,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_REG | T_DIR | T_IND, T_REG | T_DIR | T_IND, T_REG],
             has_pcb: true,
             dir_size: FourBytes,
+            reach: Limited,
         },
         Xor => OpSpec {
             code,
@@ -151,6 +195,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_REG | T_DIR | T_IND, T_REG | T_DIR | T_IND, T_REG],
             has_pcb: true,
             dir_size: FourBytes,
+            reach: Limited,
         },
         Zjmp => OpSpec {
             code,
@@ -159,6 +204,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_DIR, 0, 0],
             has_pcb: false,
             dir_size: TwoBytes,
+            reach: Limited,
         },
         Ldi => OpSpec {
             code,
@@ -167,6 +213,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_REG | T_DIR | T_IND, T_REG | T_DIR, T_REG],
             has_pcb: true,
             dir_size: TwoBytes,
+            reach: Limited,
         },
         Sti => OpSpec {
             code,
@@ -175,6 +222,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_REG, T_REG | T_DIR | T_IND, T_REG | T_DIR],
             has_pcb: true,
             dir_size: TwoBytes,
+            reach: Limited,
         },
         Fork => OpSpec {
             code,
@@ -183,6 +231,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_DIR, 0, 0],
             has_pcb: false,
             dir_size: TwoBytes,
+            reach: Limited,
         },
         Lld => OpSpec {
             code,
@@ -191,6 +240,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_DIR | T_IND, T_REG, 0],
             has_pcb: true,
             dir_size: FourBytes,
+            reach: Long,
         },
         Lldi => OpSpec {
             code,
@@ -199,6 +249,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_REG | T_DIR | T_IND, T_REG | T_DIR, T_REG],
             has_pcb: true,
             dir_size: TwoBytes,
+            reach: Long,
         },
         Lfork => OpSpec {
             code,
@@ -207,6 +258,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_DIR, 0, 0],
             has_pcb: false,
             dir_size: TwoBytes,
+            reach: Long,
         },
         Aff => OpSpec {
             code,
@@ -215,6 +267,7 @@ pub const fn op_spec(op_type: OpType) -> OpSpec {
             param_masks: [T_REG, 0, 0],
             has_pcb: true,
             dir_size: FourBytes,
+            reach: Limited,
         },
     }
 }
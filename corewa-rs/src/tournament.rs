@@ -0,0 +1,340 @@
+//! Round-robin tournaments between champions
+
+use crate::{
+    rules::{RuleSet, RuleSetError},
+    spec::MAX_PLAYERS,
+    vm::{
+        builder::RandomSetup, loader::LoadError, outcome::EndReason, types::PlayerId,
+        VirtualMachine,
+    },
+};
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+/// The Elo rating of every entrant before their first match
+pub const INITIAL_ELO: f64 = 1500.0;
+/// The maximum rating change of a match
+const ELO_K_FACTOR: f64 = 32.0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entrant {
+    pub name: String,
+    /// The compiled champion, header included
+    pub champion: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TournamentConfig {
+    pub rules: RuleSet,
+    pub cycle_limit: Option<u32>,
+    /// Every combination of 2 up to `max_players` entrants plays, from 2 to 4
+    pub max_players: usize,
+    /// Number of matches running in parallel
+    pub threads: usize,
+    /// Every seating is played once per seed, with the starting conditions of
+    /// [`RandomSetup::new`] except for the shuffled slots, the seat orders
+    /// being already covered. Without seeds, every seating is played once with
+    /// the players evenly spread across the memory
+    pub seeds: Vec<u64>,
+}
+
+impl Default for TournamentConfig {
+    fn default() -> Self {
+        Self {
+            rules: RuleSet::default(),
+            cycle_limit: None,
+            max_players: 2,
+            threads: thread::available_parallelism().map_or(1, |count| count.get()),
+            seeds: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchResult {
+    /// Indices of the entrants, by seat order
+    pub seats: Vec<usize>,
+    /// The winning entrant, `None` for a draw
+    pub winner: Option<usize>,
+    pub cycles: u32,
+    /// The seed of the starting conditions, if any
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standing {
+    pub entrant: usize,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    pub elo: f64,
+}
+
+/// The results of the 2-player matches between two entrants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Matchup {
+    pub entrant: usize,
+    pub opponent: usize,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TournamentResults {
+    /// By increasing number of players, then by combination of entrants, each
+    /// one being followed by its reversed seat order, then by seed
+    pub matches: Vec<MatchResult>,
+    /// Sorted by decreasing Elo rating
+    pub standings: Vec<Standing>,
+    /// One per ordered pair of entrants that met in a 2-player match
+    pub matchups: Vec<Matchup>,
+}
+
+/// Plays every combination of entrants, each in both seat orders.
+///
+/// A match is a draw when it reaches the cycle limit, otherwise it is won by
+/// the winner declared by the VM, see [`MatchOutcome::winner`]: when several
+/// players were last reported alive during the same cycle, the one seated
+/// last wins.
+/// In matches of more than 2 players, the winner beats every other player
+/// and the others neither win nor lose against each other.
+///
+/// The Elo ratings are updated one match at a time, in the fixed order of
+/// [`TournamentResults::matches`], which the final ratings slightly depend on
+///
+/// [`MatchOutcome::winner`]: crate::vm::outcome::MatchOutcome::winner
+pub fn run_tournament(
+    entrants: &[Entrant],
+    config: &TournamentConfig,
+) -> Result<TournamentResults, TournamentError> {
+    if !(2..=MAX_PLAYERS).contains(&config.max_players) {
+        return Err(TournamentError::InvalidPlayerCount(config.max_players));
+    }
+    config.rules.validate()?;
+    for entrant in entrants {
+        let mut vm = VirtualMachine::with_rules(config.rules.clone());
+        vm.load_players(&[(1, entrant.champion.clone())])
+            .map_err(|e| TournamentError::InvalidChampion(entrant.name.clone(), e))?;
+    }
+
+    let seeds: Vec<_> = if config.seeds.is_empty() {
+        vec![None]
+    } else {
+        config.seeds.iter().copied().map(Some).collect()
+    };
+    let mut seatings = Vec::new();
+    for player_count in 2..=config.max_players.min(entrants.len()) {
+        for combination in combinations(entrants.len(), player_count) {
+            let mut reversed = combination.clone();
+            reversed.reverse();
+            for seats in [combination, reversed] {
+                seatings.extend(seeds.iter().map(|&seed| (seats.clone(), seed)));
+            }
+        }
+    }
+
+    let matches = play_all(entrants, &seatings, config)?;
+
+    let mut standings: Vec<_> = (0..entrants.len())
+        .map(|entrant| Standing {
+            entrant,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            elo: INITIAL_ELO,
+        })
+        .collect();
+    let mut matchups = Vec::new();
+
+    for result in &matches {
+        for &seat in &result.seats {
+            let standing = &mut standings[seat];
+            match result.winner {
+                None => standing.draws += 1,
+                Some(winner) if winner == seat => standing.wins += 1,
+                Some(_) => standing.losses += 1,
+            }
+        }
+        update_elo(&mut standings, result);
+
+        if let [first, second] = result.seats[..] {
+            for (entrant, opponent) in [(first, second), (second, first)] {
+                let matchup = match matchups
+                    .iter_mut()
+                    .find(|m: &&mut Matchup| m.entrant == entrant && m.opponent == opponent)
+                {
+                    Some(matchup) => matchup,
+                    None => {
+                        matchups.push(Matchup {
+                            entrant,
+                            opponent,
+                            wins: 0,
+                            losses: 0,
+                            draws: 0,
+                        });
+                        matchups.last_mut().expect("A matchup was just pushed")
+                    }
+                };
+                match result.winner {
+                    None => matchup.draws += 1,
+                    Some(winner) if winner == entrant => matchup.wins += 1,
+                    Some(_) => matchup.losses += 1,
+                }
+            }
+        }
+    }
+
+    standings.sort_by(|lhs, rhs| rhs.elo.total_cmp(&lhs.elo));
+    matchups.sort_by_key(|matchup| (matchup.entrant, matchup.opponent));
+
+    Ok(TournamentResults {
+        matches,
+        standings,
+        matchups,
+    })
+}
+
+/// Plays the matches on `config.threads` threads, the results keeping the
+/// order of `seatings`
+fn play_all(
+    entrants: &[Entrant],
+    seatings: &[(Vec<usize>, Option<u64>)],
+    config: &TournamentConfig,
+) -> Result<Vec<MatchResult>, TournamentError> {
+    let next_match = AtomicUsize::new(0);
+    let results: Mutex<Vec<_>> = Mutex::new(seatings.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..config.threads.max(1) {
+            scope.spawn(|| loop {
+                let idx = next_match.fetch_add(1, Ordering::Relaxed);
+                let (seats, seed) = match seatings.get(idx) {
+                    Some(seating) => seating,
+                    None => break,
+                };
+                let result = play(entrants, seats, *seed, config);
+                results.lock().expect("A match thread panicked")[idx] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .expect("A match thread panicked")
+        .into_iter()
+        .map(|result| result.expect("Every match is played"))
+        .collect()
+}
+
+fn play(
+    entrants: &[Entrant],
+    seats: &[usize],
+    seed: Option<u64>,
+    config: &TournamentConfig,
+) -> Result<MatchResult, TournamentError> {
+    let mut builder = VirtualMachine::builder().rules(config.rules.clone());
+    for (&entrant, id) in seats.iter().zip(1..) {
+        builder = builder.player(id as PlayerId, entrants[entrant].champion.clone());
+    }
+    if let Some(seed) = seed {
+        builder = builder.randomized(RandomSetup {
+            shuffle_slots: false,
+            ..RandomSetup::new(seed)
+        });
+    }
+
+    let mut vm = builder.build().map_err(|e| {
+        let names: Vec<_> = seats
+            .iter()
+            .map(|&entrant| entrants[entrant].name.as_str())
+            .collect();
+        TournamentError::InvalidMatch(names.join(" vs "), e)
+    })?;
+    let outcome = vm.run_to_completion(config.cycle_limit);
+
+    let winner = match outcome.reason {
+        EndReason::CycleLimitReached => None,
+        EndReason::AllProcessesKilled => outcome.winner.map(|id| seats[id as usize - 1]),
+    };
+
+    Ok(MatchResult {
+        seats: seats.to_vec(),
+        winner,
+        cycles: outcome.cycles,
+        seed,
+    })
+}
+
+/// Every pair of players scores as a match of its own, all of them being
+/// computed from the ratings before the match
+fn update_elo(standings: &mut [Standing], result: &MatchResult) {
+    let rating = |entrant: usize| standings[entrant].elo;
+    let k_factor = ELO_K_FACTOR / (result.seats.len() - 1) as f64;
+
+    let mut changes = vec![0.0; result.seats.len()];
+    for (i, &player) in result.seats.iter().enumerate() {
+        for &opponent in &result.seats {
+            if player == opponent {
+                continue;
+            }
+            let score = match result.winner {
+                None => 0.5,
+                Some(winner) if winner == player => 1.0,
+                Some(winner) if winner == opponent => 0.0,
+                // Two losers do not affect each other
+                Some(_) => continue,
+            };
+            let expected = 1.0 / (1.0 + 10f64.powf((rating(opponent) - rating(player)) / 400.0));
+            changes[i] += k_factor * (score - expected);
+        }
+    }
+
+    for (&player, change) in result.seats.iter().zip(changes) {
+        standings[player].elo += change;
+    }
+}
+
+/// Every increasing sequence of `k` indices below `n`
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    let mut combinations = Vec::new();
+    if k > n {
+        return combinations;
+    }
+    let mut current: Vec<usize> = (0..k).collect();
+
+    loop {
+        combinations.push(current.clone());
+
+        // Increments the rightmost index that can still move, and resets the
+        // following ones right after it
+        let movable = (0..k).rev().find(|&i| current[i] < n - k + i);
+        match movable {
+            Some(i) => {
+                current[i] += 1;
+                for j in i + 1..k {
+                    current[j] = current[j - 1] + 1;
+                }
+            }
+            None => return combinations,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TournamentError {
+    #[error("Invalid number of players per match: {0}, expected 2 to 4")]
+    InvalidPlayerCount(usize),
+    #[error("Invalid champion '{0}': {1}")]
+    InvalidChampion(String, LoadError),
+    #[error("Cannot load the match {0}: {1}")]
+    InvalidMatch(String, LoadError),
+    #[error("Invalid rule set: {0}")]
+    InvalidRules(#[from] RuleSetError),
+}
@@ -0,0 +1,139 @@
+use super::{
+    loader::{LoadError, Placement, PlacementMode},
+    rng::Rng,
+    types::PlayerId,
+    VirtualMachine,
+};
+use crate::rules::RuleSet;
+
+/// Configures and loads a [`VirtualMachine`]:
+/// ```
+/// # use corewa_rs::vm::{VirtualMachine, loader::PlacementMode};
+/// # fn build(zork: Vec<u8>, kappa: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+/// let vm = VirtualMachine::builder()
+///     .player_at(1, zork, 2048)
+///     .player(2, kappa)
+///     .placement_mode(PlacementMode::Random { seed: 42, min_separation: 512 })
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct VmBuilder {
+    rules: RuleSet,
+    players: Vec<(PlayerId, Vec<u8>)>,
+    placement: Placement,
+    setup: Option<RandomSetup>,
+}
+
+/// Randomized starting conditions, fully determined by `seed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RandomSetup {
+    pub seed: u64,
+    /// Shuffles the order in which the players are loaded
+    pub shuffle_slots: bool,
+    /// Places the players without an explicit address randomly, with this
+    /// minimum separation
+    pub random_placement: Option<usize>,
+    /// Fills the memory with random bytes before loading the players
+    pub noise: bool,
+}
+
+impl RandomSetup {
+    /// Every randomization enabled
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            shuffle_slots: true,
+            random_placement: Some(0),
+            noise: true,
+        }
+    }
+}
+
+impl VirtualMachine {
+    pub fn builder() -> VmBuilder {
+        VmBuilder::default()
+    }
+}
+
+impl VmBuilder {
+    pub fn rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Adds a player placed according to the placement mode
+    pub fn player(self, player_id: PlayerId, program: Vec<u8>) -> Self {
+        self.player_with_address(player_id, program, None)
+    }
+
+    /// Adds a player loaded at `addr`
+    pub fn player_at(self, player_id: PlayerId, program: Vec<u8>, addr: usize) -> Self {
+        self.player_with_address(player_id, program, Some(addr))
+    }
+
+    pub fn player_with_address(
+        mut self,
+        player_id: PlayerId,
+        program: Vec<u8>,
+        addr: Option<usize>,
+    ) -> Self {
+        self.players.push((player_id, program));
+        self.placement.addresses.push(addr);
+        self
+    }
+
+    /// How the players without an explicit address are placed
+    pub fn placement_mode(mut self, mode: PlacementMode) -> Self {
+        self.placement.mode = mode;
+        self
+    }
+
+    pub fn randomized(mut self, setup: RandomSetup) -> Self {
+        self.setup = Some(setup);
+        self
+    }
+
+    pub fn build(mut self) -> Result<VirtualMachine, LoadError> {
+        self.rules.validate()?;
+        let mut vm = VirtualMachine::with_rules(self.rules);
+
+        if let Some(setup) = self.setup {
+            // Every randomization draws from its own generator so that
+            // toggling one does not change the outcome of the others
+            let mut rng = Rng::new(setup.seed);
+            let mut slots_rng = Rng::new(rng.next_u64());
+            let placement_seed = rng.next_u64();
+            let mut noise_rng = Rng::new(rng.next_u64());
+
+            if setup.shuffle_slots {
+                let mut slots: Vec<_> = self
+                    .players
+                    .into_iter()
+                    .zip(self.placement.addresses)
+                    .collect();
+                slots_rng.shuffle(&mut slots);
+                let (players, addresses) = slots.into_iter().unzip();
+                self.players = players;
+                self.placement.addresses = addresses;
+            }
+
+            if let Some(min_separation) = setup.random_placement {
+                self.placement.mode = PlacementMode::Random {
+                    seed: placement_seed,
+                    min_separation,
+                };
+            }
+
+            if setup.noise {
+                for byte in vm.memory.values.inner_mut() {
+                    *byte = noise_rng.next_u64() as u8;
+                }
+            }
+        }
+
+        vm.load_players_with(&self.players, &self.placement)?;
+        Ok(vm)
+    }
+}
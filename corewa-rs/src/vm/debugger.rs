@@ -0,0 +1,358 @@
+use super::{
+    memory::Owner,
+    observer::{VmEvent, VmObserver},
+    outcome::TickStatus,
+    types::*,
+    VirtualMachine,
+};
+use crate::spec::{OpType, OP_TYPES, REG_COUNT};
+
+use std::{fmt, str::FromStr};
+
+pub type BreakpointId = u32;
+
+/// A condition that interrupts the VM at the end of the cycle during which it
+/// became true
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// The pc of a process moves to `addr`, or a process is forked at `addr`
+    Address(usize),
+    /// Process `pid` executes an instruction of kind `op`
+    Op { pid: Pid, op: OpType },
+    /// One of the `len` bytes starting at `addr` is written,
+    /// by a process of `owner` if specified
+    MemoryWrite {
+        addr: usize,
+        len: usize,
+        owner: Option<Owner>,
+    },
+    /// Register `reg` (numbered from 1) of a process changes to `value`
+    Register { reg: usize, value: Register },
+}
+
+/// A breakpoint that fired during a cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakpointHit {
+    pub id: BreakpointId,
+    pub cycle: u32,
+    /// The process that triggered the breakpoint
+    pub pid: Pid,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Breakpoints {
+    next_id: BreakpointId,
+    breakpoints: Vec<(BreakpointId, Breakpoint)>,
+}
+
+impl Breakpoints {
+    /// Breakpoints are added through [`VirtualMachine::add_breakpoint`], which
+    /// checks them against the memory size
+    pub(super) fn add(&mut self, breakpoint: Breakpoint) -> BreakpointId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push((id, breakpoint));
+        id
+    }
+
+    /// Returns the removed breakpoint, if it existed
+    pub fn remove(&mut self, id: BreakpointId) -> Option<Breakpoint> {
+        let idx = self
+            .breakpoints
+            .iter()
+            .position(|(bp_id, _)| *bp_id == id)?;
+        Some(self.breakpoints.remove(idx).1)
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear()
+    }
+
+    pub fn get(&self, id: BreakpointId) -> Option<&Breakpoint> {
+        self.iter()
+            .find(|(bp_id, _)| *bp_id == id)
+            .map(|(_, breakpoint)| breakpoint)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BreakpointId, &Breakpoint)> {
+        self.breakpoints.iter().map(|(id, bp)| (*id, bp))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    fn watches_processes(&self) -> bool {
+        self.iter().any(|(_, breakpoint)| {
+            matches!(
+                breakpoint,
+                Breakpoint::Address(_) | Breakpoint::Register { .. }
+            )
+        })
+    }
+}
+
+impl VirtualMachine {
+    /// Fails if the breakpoint watches addresses outside the memory, as it
+    /// would never fire
+    pub fn add_breakpoint(
+        &mut self,
+        breakpoint: Breakpoint,
+    ) -> Result<BreakpointId, AddressOutOfRange> {
+        let mem_size = self.rules.mem_size;
+        let last_addr = match breakpoint {
+            Breakpoint::Address(addr) => Some(addr),
+            Breakpoint::MemoryWrite { addr, len, .. } => {
+                Some(addr.saturating_add(len.saturating_sub(1)))
+            }
+            _ => None,
+        };
+        match last_addr {
+            Some(addr) if addr >= mem_size => Err(AddressOutOfRange { addr, mem_size }),
+            _ => Ok(self.breakpoints.add(breakpoint)),
+        }
+    }
+
+    /// Ticks until a breakpoint fires, the match ends or `cycle_limit` is
+    /// reached, in which case [`TickStatus::Running`] is returned
+    pub fn run_until_break(&mut self, cycle_limit: Option<u32>) -> TickStatus {
+        loop {
+            if cycle_limit.is_some_and(|limit| self.cycles >= limit) {
+                return TickStatus::Running;
+            }
+            match self.tick() {
+                TickStatus::Running => (),
+                status => return status,
+            }
+        }
+    }
+
+    /// Runs a cycle while checking the breakpoints
+    pub(super) fn run_watched_cycle(
+        &mut self,
+        observer: &mut dyn VmObserver,
+    ) -> Vec<BreakpointHit> {
+        let breakpoints = std::mem::take(&mut self.breakpoints);
+        let cycle = self.cycles;
+
+        let previous_states: Vec<(Pid, usize, Registers)> = if breakpoints.watches_processes() {
+            self.processes
+                .iter()
+                .map(|process| (process.pid, process.pc.addr(), process.registers))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut watcher = Watcher {
+            breakpoints: &breakpoints,
+            mem_size: self.rules.mem_size,
+            inner: observer,
+            hits: Vec::new(),
+        };
+        self.run_cycle(&mut watcher);
+        let mut hits = watcher.hits;
+
+        if breakpoints.watches_processes() {
+            // Processes are always sorted by pid since forks get increasing
+            // pids and are appended, and kills preserve the order
+            for process in &self.processes {
+                let previous = previous_states
+                    .binary_search_by_key(&process.pid, |(pid, ..)| *pid)
+                    .ok()
+                    .map(|idx| &previous_states[idx]);
+
+                for (id, breakpoint) in breakpoints.iter() {
+                    let fired = match (breakpoint, previous) {
+                        (Breakpoint::Address(addr), Some((_, pc, _))) => {
+                            *pc != process.pc.addr() && *addr == process.pc.addr()
+                        }
+                        (Breakpoint::Address(addr), None) => *addr == process.pc.addr(),
+                        (Breakpoint::Register { reg, value }, Some((_, _, registers))) => {
+                            let previous = reg.checked_sub(1).and_then(|idx| registers.get(idx));
+                            let current = reg
+                                .checked_sub(1)
+                                .and_then(|idx| process.registers.get(idx));
+                            previous != Some(value) && current == Some(value)
+                        }
+                        _ => false,
+                    };
+                    if fired {
+                        hits.push(BreakpointHit {
+                            id,
+                            cycle,
+                            pid: process.pid,
+                        });
+                    }
+                }
+            }
+        }
+
+        self.breakpoints = breakpoints;
+        hits
+    }
+}
+
+/// Checks the events of a cycle against the breakpoints before forwarding them
+struct Watcher<'a> {
+    breakpoints: &'a Breakpoints,
+    mem_size: usize,
+    inner: &'a mut dyn VmObserver,
+    hits: Vec<BreakpointHit>,
+}
+
+impl VmObserver for Watcher<'_> {
+    fn notify(&mut self, event: VmEvent) {
+        for (id, breakpoint) in self.breakpoints.iter() {
+            let hit = match (breakpoint, &event) {
+                (
+                    Breakpoint::Op { pid, op },
+                    VmEvent::InstructionExecuted {
+                        cycle,
+                        pid: event_pid,
+                        instr,
+                        ..
+                    },
+                ) if pid == event_pid && *op == instr.kind => Some((*cycle, *pid)),
+                (
+                    Breakpoint::MemoryWrite { addr, len, owner },
+                    VmEvent::MemoryWrite {
+                        cycle,
+                        pid,
+                        owner: event_owner,
+                        addr: event_addr,
+                        len: event_len,
+                        ..
+                    },
+                ) => {
+                    let owner_matches = owner.is_none_or(|owner| owner == *event_owner);
+                    let overlaps = (0..*event_len).any(|offset| {
+                        let written = (event_addr + offset) % self.mem_size;
+                        (written + self.mem_size - addr % self.mem_size) % self.mem_size < *len
+                    });
+                    if owner_matches && overlaps {
+                        Some((*cycle, *pid))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+
+            if let Some((cycle, pid)) = hit {
+                self.hits.push(BreakpointHit { id, cycle, pid });
+            }
+        }
+
+        self.inner.notify(event)
+    }
+
+    fn wants_registers(&self) -> bool {
+        self.inner.wants_registers()
+    }
+}
+
+/// Parses the textual form of a breakpoint:
+/// - `pc=ADDR`
+/// - `op=PID:MNEMONIC`
+/// - `write=START..END` or `write=START..END:OWNER`, `OWNER` being a player index
+/// - `reg=rN:VALUE`
+impl FromStr for Breakpoint {
+    type Err = BreakpointParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn parse<T: FromStr>(value: &str) -> Result<T, BreakpointParseError> {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| BreakpointParseError::InvalidValue(value.trim().to_owned()))
+        }
+
+        let (kind, spec) = s
+            .split_once('=')
+            .ok_or_else(|| BreakpointParseError::InvalidFormat(s.to_owned()))?;
+        let missing_colon = || BreakpointParseError::InvalidFormat(s.to_owned());
+
+        match kind.trim() {
+            "pc" => Ok(Breakpoint::Address(parse(spec)?)),
+            "op" => {
+                let (pid, mnemonic) = spec.split_once(':').ok_or_else(missing_colon)?;
+                let op = OP_TYPES
+                    .iter()
+                    .copied()
+                    .find(|op| op.to_string().eq_ignore_ascii_case(mnemonic.trim()))
+                    .ok_or_else(|| BreakpointParseError::InvalidValue(mnemonic.to_owned()))?;
+                Ok(Breakpoint::Op {
+                    pid: parse(pid)?,
+                    op,
+                })
+            }
+            "write" => {
+                let (range, owner) = match spec.split_once(':') {
+                    Some((range, owner)) => (range, Some(parse(owner)?)),
+                    None => (spec, None),
+                };
+                let (start, end) = range
+                    .split_once("..")
+                    .ok_or_else(|| BreakpointParseError::InvalidFormat(s.to_owned()))?;
+                let (start, end): (usize, usize) = (parse(start)?, parse(end)?);
+                if end <= start {
+                    return Err(BreakpointParseError::InvalidValue(range.to_owned()));
+                }
+                Ok(Breakpoint::MemoryWrite {
+                    addr: start,
+                    len: end - start,
+                    owner,
+                })
+            }
+            "reg" => {
+                let (reg, value) = spec.split_once(':').ok_or_else(missing_colon)?;
+                let reg = reg
+                    .trim()
+                    .strip_prefix('r')
+                    .and_then(|reg| reg.parse().ok())
+                    .filter(|reg| (1..=REG_COUNT).contains(reg))
+                    .ok_or_else(|| BreakpointParseError::InvalidValue(reg.to_owned()))?;
+                Ok(Breakpoint::Register {
+                    reg,
+                    value: parse(value)?,
+                })
+            }
+            _ => Err(BreakpointParseError::InvalidFormat(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Address(addr) => write!(f, "pc={}", addr),
+            Breakpoint::Op { pid, op } => {
+                write!(f, "op={}:{}", pid, op.to_string().to_lowercase())
+            }
+            Breakpoint::MemoryWrite { addr, len, owner } => {
+                write!(f, "write={}..{}", addr, addr + len)?;
+                match owner {
+                    Some(owner) => write!(f, ":{}", owner),
+                    None => Ok(()),
+                }
+            }
+            Breakpoint::Register { reg, value } => write!(f, "reg=r{}:{}", reg, value),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Breakpoint address {addr} is out of the memory ({mem_size} bytes)")]
+pub struct AddressOutOfRange {
+    pub addr: usize,
+    pub mem_size: usize,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BreakpointParseError {
+    #[error("Invalid breakpoint '{0}', expected one of pc=ADDR, op=PID:OP, write=START..END[:OWNER] or reg=rN:VALUE")]
+    InvalidFormat(String),
+    #[error("Invalid breakpoint value '{0}'")]
+    InvalidValue(String),
+}
@@ -1,5 +1,5 @@
 use super::types::*;
-use crate::spec::*;
+use crate::{rules::RuleSet, spec::*};
 
 pub trait Read: std::ops::Index<usize, Output = u8> {
     fn read_i32(&self, at: usize) -> i32;
@@ -13,18 +13,23 @@ pub trait Decode: Read {
         op_from_code(op_code).ok_or(InvalidOpCode(op_code))
     }
 
-    fn decode_instr(&self, op: OpType, addr: usize) -> Result<Instruction, InstrDecodeError> {
+    fn decode_instr(
+        &self,
+        op: OpType,
+        addr: usize,
+        rules: &RuleSet,
+    ) -> Result<Instruction, InstrDecodeError> {
         // Decode the operation's parameter types and start counting the
         // instruction's total byte size:
         // If the op has a pcb, decode it and start counting at 2 bytes:
         // op code and pcb each on one byte.
         // If the op has no pcb, then the parameter types are known and start
         // counting at 1 byte: op code on one byte.
-        let op_spec = op_spec(op);
+        let op_spec = rules.ops.get(op);
 
         let (param_types, mut instr_byte_size) = if op_spec.has_pcb {
             let pcb = self[addr + 1];
-            (read_pcb_params(pcb, &op_spec)?, 2)
+            (read_pcb_params(pcb, op_spec)?, 2)
         } else {
             (params_from_unambiguous_masks(op_spec.param_masks), 1)
         };
@@ -40,7 +45,7 @@ pub trait Decode: Read {
         for idx in 0..op_spec.param_count {
             let param_type = param_types[idx];
             let (param, param_byte_size) =
-                self.decode_param(param_type, addr + instr_byte_size, &op_spec.dir_size)?;
+                self.decode_param(param_type, addr + instr_byte_size, &op_spec.dir_size, rules)?;
             instr_byte_size += param_byte_size;
             params[idx] = param;
         }
@@ -52,20 +57,44 @@ pub trait Decode: Read {
         })
     }
 
+    /// The byte size of the instruction at `addr` as the reference VM computes
+    /// it, from the parameter types of its pcb even when they are invalid
+    fn encoded_size(&self, op: OpType, addr: usize, rules: &RuleSet) -> usize {
+        let op_spec = rules.ops.get(op);
+        if !op_spec.has_pcb {
+            return self
+                .decode_instr(op, addr, rules)
+                .map_or(1, |instr| instr.byte_size);
+        }
+
+        let pcb = self[addr + 1];
+        let params_size: usize = (0..op_spec.param_count)
+            .map(|idx| match (pcb >> (6 - 2 * idx)) & 0b00_00_00_11 {
+                REG_PARAM_CODE => 1,
+                DIR_PARAM_CODE => op_spec.dir_size as usize,
+                IND_PARAM_CODE => 2,
+                _ => 0,
+            })
+            .sum();
+        2 + params_size
+    }
+
     fn decode_param(
         &self,
         kind: ParamType,
         addr: usize,
         dir_size: &DirectSize,
+        rules: &RuleSet,
     ) -> Result<(Param, usize), InstrDecodeError> {
         use ParamType::*;
 
         let (value, size) = match (&kind, dir_size) {
             (Register, _) => {
                 let reg = self[addr];
-                match reg as usize {
-                    1..=REG_COUNT => (i32::from(reg), 1),
-                    _ => return Err(InstrDecodeError::InvalidRegNumber(reg)),
+                if (1..=rules.reg_count).contains(&usize::from(reg)) {
+                    (i32::from(reg), 1)
+                } else {
+                    return Err(InstrDecodeError::InvalidRegNumber(reg));
                 }
             }
             (Direct, DirectSize::FourBytes) => (self.read_i32(addr), 4),
@@ -80,12 +109,6 @@ impl<T: Read> Decode for T {}
 
 type ParamTypes = [ParamType; MAX_PARAMS];
 
-impl Default for ParamType {
-    fn default() -> Self {
-        ParamType::Register
-    }
-}
-
 fn params_from_unambiguous_masks(masks: [u8; MAX_PARAMS]) -> ParamTypes {
     fn to_param_type(mask: u8) -> ParamType {
         match mask {
@@ -154,7 +177,7 @@ fn read_type_and_mask_flag(param_code: u8) -> Option<(ParamType, u8)> {
     }
 }
 
-fn op_from_code(code: u8) -> Option<OpType> {
+pub(crate) fn op_from_code(code: u8) -> Option<OpType> {
     use OpType::*;
 
     let op_type = match code {
@@ -1,7 +1,16 @@
-use super::{process::Process, types::*, PidPool};
-use crate::spec::ParamType;
+use super::{
+    observer::{VmEvent, VmObserver},
+    process::Process,
+    types::*,
+    PidPool,
+};
+use crate::{
+    rules::RuleSet,
+    spec::{OpType, ParamType, MAX_PLAYERS},
+};
 
 use fxhash::FxHashSet as HashSet;
+use std::collections::VecDeque;
 
 pub struct ExecutionContext<'a> {
     pub memory: &'a mut super::memory::Memory,
@@ -11,6 +20,9 @@ pub struct ExecutionContext<'a> {
     pub live_count: &'a mut u32,
     pub pid_pool: &'a mut PidPool,
     pub live_ids: &'a mut HashSet<PlayerId>,
+    pub aff_outputs: &'a mut [VecDeque<AffChar>; MAX_PLAYERS],
+    pub observer: &'a mut dyn VmObserver,
+    pub rules: &'a RuleSet,
 }
 
 impl ExecutionContext<'_> {
@@ -21,12 +33,43 @@ impl ExecutionContext<'_> {
             Register => self.process.registers[param.value as usize - 1],
             Direct => param.value,
             Indirect => {
-                let at = self.process.pc.offset(param.value as isize, offset_type);
+                let at = self.offset(param.value as isize, offset_type);
                 self.memory.read_i32(at)
             }
         }
     }
 
+    pub fn reach(&self, op_type: OpType) -> OffsetType {
+        self.rules.ops.get(op_type).reach
+    }
+
+    pub fn offset(&self, offset: isize, offset_type: OffsetType) -> usize {
+        self.process.pc.offset(offset, offset_type, self.rules)
+    }
+
+    pub fn write_i32(&mut self, value: i32, at: usize) {
+        self.memory.write_i32(value, self.process.owner, at);
+        self.observer.notify(VmEvent::MemoryWrite {
+            cycle: self.cycle,
+            pid: self.process.pid,
+            owner: self.process.owner,
+            addr: at,
+            len: std::mem::size_of::<i32>(),
+            value,
+        });
+    }
+
+    pub fn fork(&mut self, pc: usize) {
+        let child_process = Process::fork(self.pid_pool.get(), pc.into(), self);
+        self.observer.notify(VmEvent::ForkSpawned {
+            cycle: self.cycle,
+            parent: self.process.pid,
+            child: child_process.pid,
+            pc,
+        });
+        self.forks.push(child_process);
+    }
+
     pub fn get_reg(&self, param: &Param) -> i32 {
         debug_assert_eq!(param.kind, ParamType::Register);
         self.process.registers[param.value as usize - 1]
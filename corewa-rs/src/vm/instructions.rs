@@ -1,5 +1,5 @@
-use super::{execution_context::ExecutionContext, process::Process, types::*};
-use crate::spec::ParamType;
+use super::{execution_context::ExecutionContext, observer::VmEvent, types::*};
+use crate::{rules::Semantics, spec::ParamType};
 
 pub fn exec_live(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [player_id_p, _, _] = &instr.params;
@@ -7,12 +7,18 @@ pub fn exec_live(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     *ctx.live_count += 1;
     ctx.process.last_live_cycle = ctx.cycle;
     ctx.live_ids.insert(player_id_p.value);
+    ctx.observer.notify(VmEvent::LiveReported {
+        cycle: ctx.cycle,
+        pid: ctx.process.pid,
+        player_id: player_id_p.value,
+    });
 }
 
 pub fn exec_ld(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [src_p, dst_p, _] = &instr.params;
+    let reach = ctx.reach(instr.kind);
 
-    let value_to_load = ctx.get_param(src_p, OffsetType::Limited);
+    let value_to_load = ctx.get_param(src_p, reach);
     ctx.set_reg(dst_p, value_to_load);
 
     ctx.process.zf = value_to_load == 0;
@@ -20,17 +26,15 @@ pub fn exec_ld(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
 
 pub fn exec_st(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [src_p, dst_p, _] = &instr.params;
+    let reach = ctx.reach(instr.kind);
 
     let value_to_store = ctx.get_reg(src_p);
     match dst_p.kind {
         ParamType::Register => ctx.set_reg(dst_p, value_to_store),
-        ParamType::Indirect => ctx.memory.write_i32(
-            value_to_store,
-            ctx.process.owner,
-            ctx.process
-                .pc
-                .offset(dst_p.value as isize, OffsetType::Limited),
-        ),
+        ParamType::Indirect => {
+            let at = ctx.offset(dst_p.value as isize, reach);
+            ctx.write_i32(value_to_store, at)
+        }
         _ => unreachable!("St Param #2 invariant broken"),
     }
 }
@@ -59,9 +63,10 @@ pub fn exec_sub(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
 
 pub fn exec_and(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [lhs_p, rhs_p, dst_p] = &instr.params;
+    let reach = ctx.reach(instr.kind);
 
-    let lhs = ctx.get_param(lhs_p, OffsetType::Limited);
-    let rhs = ctx.get_param(rhs_p, OffsetType::Limited);
+    let lhs = ctx.get_param(lhs_p, reach);
+    let rhs = ctx.get_param(rhs_p, reach);
     let result = lhs & rhs;
     ctx.set_reg(dst_p, result);
 
@@ -70,9 +75,10 @@ pub fn exec_and(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
 
 pub fn exec_or(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [lhs_p, rhs_p, dst_p] = &instr.params;
+    let reach = ctx.reach(instr.kind);
 
-    let lhs = ctx.get_param(lhs_p, OffsetType::Limited);
-    let rhs = ctx.get_param(rhs_p, OffsetType::Limited);
+    let lhs = ctx.get_param(lhs_p, reach);
+    let rhs = ctx.get_param(rhs_p, reach);
     let result = lhs | rhs;
     ctx.set_reg(dst_p, result);
 
@@ -81,9 +87,10 @@ pub fn exec_or(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
 
 pub fn exec_xor(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [lhs_p, rhs_p, dst_p] = &instr.params;
+    let reach = ctx.reach(instr.kind);
 
-    let lhs = ctx.get_param(lhs_p, OffsetType::Limited);
-    let rhs = ctx.get_param(rhs_p, OffsetType::Limited);
+    let lhs = ctx.get_param(lhs_p, reach);
+    let rhs = ctx.get_param(rhs_p, reach);
     let result = lhs ^ rhs;
     ctx.set_reg(dst_p, result);
 
@@ -92,60 +99,59 @@ pub fn exec_xor(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
 
 pub fn exec_zjmp(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [offset_p, _, _] = &instr.params;
+    let reach = ctx.reach(instr.kind);
 
     if !ctx.process.zf {
         return;
     }
-    let jumped_offet = ctx
-        .process
-        .pc
-        .offset(offset_p.value as isize, OffsetType::Limited);
+    let jumped_offet = ctx.offset(offset_p.value as isize, reach);
     ctx.process.pc = jumped_offet.into();
     // Negating the instruction jump
-    ctx.process.pc.advance(-(instr.byte_size as isize))
+    ctx.process
+        .pc
+        .advance(-(instr.byte_size as isize), ctx.rules)
 }
 
 pub fn exec_ldi(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [lhs_p, rhs_p, dst_p] = &instr.params;
+    let reach = ctx.reach(instr.kind);
 
-    let lhs = ctx.get_param(lhs_p, OffsetType::Limited);
-    let rhs = ctx.get_param(rhs_p, OffsetType::Limited);
+    let lhs = ctx.get_param(lhs_p, reach);
+    let rhs = ctx.get_param(rhs_p, reach);
     let addr = (lhs + rhs) as isize;
-    let value = ctx
-        .memory
-        .read_i32(ctx.process.pc.offset(addr, OffsetType::Limited));
-    ctx.set_reg(dst_p, value)
+    let value = ctx.memory.read_i32(ctx.offset(addr, reach));
+    ctx.set_reg(dst_p, value);
+
+    if ctx.rules.semantics == Semantics::Compat {
+        ctx.process.zf = value == 0;
+    }
 }
 
 pub fn exec_sti(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [src_p, lhs_p, rhs_p] = &instr.params;
+    let reach = ctx.reach(instr.kind);
 
     let value = ctx.get_reg(src_p);
-    let lhs = ctx.get_param(lhs_p, OffsetType::Limited);
-    let rhs = ctx.get_param(rhs_p, OffsetType::Limited);
+    let lhs = ctx.get_param(lhs_p, reach);
+    let rhs = ctx.get_param(rhs_p, reach);
     let offset = lhs + rhs;
-    ctx.memory.write_i32(
-        value,
-        ctx.process.owner,
-        ctx.process.pc.offset(offset as isize, OffsetType::Limited),
-    );
+    let at = ctx.offset(offset as isize, reach);
+    ctx.write_i32(value, at);
 }
 
 pub fn exec_fork(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [offset_p, _, _] = &instr.params;
+    let reach = ctx.reach(instr.kind);
 
-    let forked_pc = ctx
-        .process
-        .pc
-        .offset(offset_p.value as isize, OffsetType::Limited);
-    let child_process = Process::fork(ctx.pid_pool.get(), forked_pc.into(), ctx);
-    ctx.forks.push(child_process);
+    let forked_pc = ctx.offset(offset_p.value as isize, reach);
+    ctx.fork(forked_pc);
 }
 
 pub fn exec_lld(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [src_p, dst_p, _] = &instr.params;
+    let reach = ctx.reach(instr.kind);
 
-    let value_to_load = ctx.get_param(src_p, OffsetType::Long);
+    let value_to_load = ctx.get_param(src_p, reach);
     ctx.set_reg(dst_p, value_to_load);
 
     ctx.process.zf = value_to_load == 0;
@@ -153,13 +159,12 @@ pub fn exec_lld(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
 
 pub fn exec_lldi(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [lhs_p, rhs_p, dst_p] = &instr.params;
+    let reach = ctx.reach(instr.kind);
 
-    let lhs = ctx.get_param(lhs_p, OffsetType::Long);
-    let rhs = ctx.get_param(rhs_p, OffsetType::Long);
+    let lhs = ctx.get_param(lhs_p, reach);
+    let rhs = ctx.get_param(rhs_p, reach);
     let addr = (lhs + rhs) as isize;
-    let value = ctx
-        .memory
-        .read_i32(ctx.process.pc.offset(addr, OffsetType::Long));
+    let value = ctx.memory.read_i32(ctx.offset(addr, reach));
     ctx.set_reg(dst_p, value);
 
     ctx.process.zf = value == 0;
@@ -167,13 +172,19 @@ pub fn exec_lldi(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
 
 pub fn exec_lfork(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
     let [offset_p, _, _] = &instr.params;
+    let reach = ctx.reach(instr.kind);
 
-    let forked_pc = ctx
-        .process
-        .pc
-        .offset(offset_p.value as isize, OffsetType::Long);
-    let child_process = Process::fork(ctx.pid_pool.get(), forked_pc.into(), ctx);
-    ctx.forks.push(child_process);
+    let forked_pc = ctx.offset(offset_p.value as isize, reach);
+    ctx.fork(forked_pc);
 }
 
-pub fn exec_aff(_instr: &Instruction, _ctx: &mut ExecutionContext<'_>) {}
+pub fn exec_aff(instr: &Instruction, ctx: &mut ExecutionContext<'_>) {
+    let [chr_p, _, _] = &instr.params;
+
+    let chr = AffChar {
+        byte: ctx.get_reg(chr_p).rem_euclid(256) as u8,
+        cycle: ctx.cycle,
+        pid: ctx.process.pid,
+    };
+    chr.push_to(&mut ctx.aff_outputs[usize::from(ctx.process.owner)]);
+}
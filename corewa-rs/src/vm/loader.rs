@@ -0,0 +1,277 @@
+use super::{memory::Owner, process::Process, rng::Rng, types::*, VirtualMachine};
+use crate::{rules::RuleSetError, spec::*};
+
+use byteorder::{BigEndian, ByteOrder};
+use std::fmt;
+
+/// Where the champions are loaded in memory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Placement {
+    /// The explicit load address of each player, by load order.
+    /// Players without one are placed according to `mode`
+    pub addresses: Vec<Option<usize>>,
+    pub mode: PlacementMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlacementMode {
+    /// Player `idx` out of `n` is loaded at `idx * mem_size / n`
+    #[default]
+    Even,
+    /// Random addresses drawn from `seed`, leaving at least `min_separation`
+    /// bytes between the code of any two champions
+    Random { seed: u64, min_separation: usize },
+}
+
+/// Number of addresses tried per champion by [`PlacementMode::Random`]
+const RANDOM_PLACEMENT_ATTEMPTS: usize = 1000;
+
+impl VirtualMachine {
+    /// Loads the champions, evenly spread across the memory.
+    /// Every champion is checked before anything is loaded, so the VM is left
+    /// untouched when an error is returned.
+    /// The players must all be loaded at once, a VM with players already
+    /// loaded is rejected
+    pub fn load_players(&mut self, players: &[(PlayerId, Vec<u8>)]) -> Result<(), LoadError> {
+        self.load_players_with(players, &Placement::default())
+    }
+
+    /// Same as [`VirtualMachine::load_players`] with a custom placement
+    pub fn load_players_with(
+        &mut self,
+        players: &[(PlayerId, Vec<u8>)],
+        placement: &Placement,
+    ) -> Result<(), LoadError> {
+        if !self.players.is_empty() {
+            return Err(LoadError::AlreadyLoaded);
+        }
+        if players.len() > MAX_PLAYERS {
+            return Err(LoadError::TooManyPlayers(players.len()));
+        }
+
+        let champions = players
+            .iter()
+            .map(|(player_id, program)| {
+                read_champion(*player_id, program, self.rules.champ_max_size)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let addresses = place_champions(&champions, placement, self.rules.mem_size)?;
+
+        // Checkpoints taken before loading would not contain the new players
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+
+        for (((player, code), at), idx) in champions.into_iter().zip(addresses).zip(0..) {
+            let player_id = player.id;
+            self.players.push(player);
+            self.load_champion(code, player_id, idx, at);
+        }
+
+        Ok(())
+    }
+
+    fn load_champion(&mut self, champion: &[u8], player_id: PlayerId, owner: Owner, at: usize) {
+        self.memory.write(at, champion, owner);
+
+        let mut starting_process = Process::new(self.pid_pool.get(), owner, at.into());
+        starting_process.registers[0] = player_id;
+
+        self.processes.push(starting_process);
+        self.last_lives[usize::from(owner)] = 0;
+        self.process_count_per_cells[at] += 1;
+        self.process_count_by_owner[usize::from(owner)] = 1;
+    }
+}
+
+fn place_champions(
+    champions: &[(Player, &[u8])],
+    placement: &Placement,
+    mem_size: usize,
+) -> Result<Vec<usize>, LoadError> {
+    let explicit_address = |idx: usize| placement.addresses.get(idx).copied().flatten();
+
+    for (idx, (player, _)) in champions.iter().enumerate() {
+        match explicit_address(idx) {
+            Some(addr) if addr >= mem_size => {
+                return Err(LoadError::InvalidAddress(player.id, addr))
+            }
+            _ => (),
+        }
+    }
+
+    let addresses = match placement.mode {
+        PlacementMode::Even => {
+            let player_spacing = mem_size / champions.len().max(1);
+            (0..champions.len())
+                .map(|idx| explicit_address(idx).unwrap_or(idx * player_spacing))
+                .collect()
+        }
+        PlacementMode::Random {
+            seed,
+            min_separation,
+        } => {
+            let mut rng = Rng::new(seed);
+            let mut placed: Vec<(usize, usize)> = champions
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, (player, _))| Some((explicit_address(idx)?, player.size)))
+                .collect();
+
+            let mut addresses = Vec::with_capacity(champions.len());
+            for (idx, (player, _)) in champions.iter().enumerate() {
+                let addr = match explicit_address(idx) {
+                    Some(addr) => addr,
+                    None => {
+                        let addr = (0..RANDOM_PLACEMENT_ATTEMPTS)
+                            .map(|_| rng.below(mem_size))
+                            .find(|&addr| {
+                                placed.iter().all(|&other| {
+                                    are_separated(
+                                        (addr, player.size),
+                                        other,
+                                        min_separation,
+                                        mem_size,
+                                    )
+                                })
+                            })
+                            .ok_or(LoadError::NoRoomForPlacement(player.id))?;
+                        placed.push((addr, player.size));
+                        addr
+                    }
+                };
+                addresses.push(addr);
+            }
+            addresses
+        }
+    };
+
+    for (idx, (player, _)) in champions.iter().enumerate() {
+        for (other_idx, (other, _)) in champions.iter().enumerate().skip(idx + 1) {
+            let champion = (addresses[idx], player.size);
+            let other_champion = (addresses[other_idx], other.size);
+            if !are_separated(champion, other_champion, 0, mem_size) {
+                return Err(LoadError::OverlappingChampions(player.id, other.id));
+            }
+        }
+    }
+
+    Ok(addresses)
+}
+
+/// Whether two `(address, size)` champions are at least `separation` bytes apart
+/// in the circular memory
+fn are_separated(
+    (addr, size): (usize, usize),
+    (other_addr, other_size): (usize, usize),
+    separation: usize,
+    mem_size: usize,
+) -> bool {
+    let distance = (other_addr + mem_size - addr) % mem_size;
+    let other_distance = (addr + mem_size - other_addr) % mem_size;
+    distance >= size + separation && other_distance >= other_size + separation
+}
+
+/// Checks a `.cor` file and splits it into the player info and its code.
+/// A declared code size of 0 is taken as unknown rather than as a mismatch
+fn read_champion(
+    player_id: PlayerId,
+    program: &[u8],
+    champ_max_size: usize,
+) -> Result<(Player, &[u8]), LoadError> {
+    use LoadError::*;
+
+    if program.len() < HEADER_SIZE {
+        return Err(MissingHeader(player_id, program.len()));
+    }
+    let (header, code) = program.split_at(HEADER_SIZE);
+
+    let (magic, header) = header.split_at(4);
+    let (prog_name, header) = header.split_at(PROG_NAME_LENGTH + 1);
+    let (prog_size, prog_comment) = header.split_at(4);
+
+    let magic = BigEndian::read_u32(magic);
+    if magic != COREWAR_MAGIC {
+        return Err(InvalidMagic(player_id, magic));
+    }
+
+    // Headers written with the padded layout of the C struct leave the packed
+    // size at 0, such champions are accepted with whatever code they have
+    let prog_size = BigEndian::read_u32(prog_size) as usize;
+    if prog_size != 0 && prog_size != code.len() {
+        return Err(SizeMismatch {
+            player_id,
+            declared: prog_size,
+            actual: code.len(),
+        });
+    }
+    if code.len() > champ_max_size {
+        return Err(ChampionTooBig(player_id, code.len(), champ_max_size));
+    }
+
+    let read_string = |bytes: &[u8], field| {
+        let len = bytes
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(MissingNul(player_id, field))?;
+        String::from_utf8(bytes[..len].to_vec()).map_err(|_| InvalidUtf8(player_id, field))
+    };
+
+    let player = Player {
+        id: player_id,
+        name: read_string(prog_name, HeaderField::Name)?,
+        comment: read_string(prog_comment, HeaderField::Comment)?,
+        size: code.len(),
+    };
+
+    Ok((player, code))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderField {
+    Name,
+    Comment,
+}
+
+impl fmt::Display for HeaderField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderField::Name => write!(f, "name"),
+            HeaderField::Comment => write!(f, "comment"),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    #[error("Invalid rule set: {0}")]
+    InvalidRules(#[from] RuleSetError),
+    #[error("The players are already loaded")]
+    AlreadyLoaded,
+    #[error("Too many players: {0} (maximum is {})", MAX_PLAYERS)]
+    TooManyPlayers(usize),
+    #[error("Player {0}: the file is too short to contain a header ({1} bytes)")]
+    MissingHeader(PlayerId, usize),
+    #[error("Player {0}: invalid magic number {1:#010x}")]
+    InvalidMagic(PlayerId, u32),
+    #[error("Player {0}: the {1} is not NUL terminated")]
+    MissingNul(PlayerId, HeaderField),
+    #[error("Player {0}: the {1} is not valid UTF8")]
+    InvalidUtf8(PlayerId, HeaderField),
+    #[error(
+        "Player {player_id}: the header announces {declared} bytes of code but there are {actual}"
+    )]
+    SizeMismatch {
+        player_id: PlayerId,
+        declared: usize,
+        actual: usize,
+    },
+    #[error("Player {0}: the champion is too big ({1} bytes, maximum allowed is {2})")]
+    ChampionTooBig(PlayerId, usize, usize),
+    #[error("Player {0}: the load address {1} is out of the memory")]
+    InvalidAddress(PlayerId, usize),
+    #[error("Players {0} and {1} are overlapping")]
+    OverlappingChampions(PlayerId, PlayerId),
+    #[error("Player {0}: could not find a place far enough from the other champions")]
+    NoRoomForPlacement(PlayerId),
+}
@@ -12,28 +12,33 @@ use std::mem;
 pub type Owner = u8;
 pub const NO_OWNER: Owner = Owner::MAX;
 
-type CellAge = u16;
+pub type CellAge = u16;
 const MAX_AGE: CellAge = 1024;
 
-pub struct Memory<const LEN: usize = MEM_SIZE> {
-    pub values: WrappingArray<u8, LEN>,
-    pub ages: WrappingArray<CellAge, LEN>,
-    pub owners: WrappingArray<Owner, LEN>,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Memory {
+    pub values: WrappingArray<u8>,
+    pub ages: WrappingArray<CellAge>,
+    pub owners: WrappingArray<Owner>,
 }
 
 impl Default for Memory {
     fn default() -> Self {
+        Self::new(MEM_SIZE)
+    }
+}
+
+impl Memory {
+    pub fn new(size: usize) -> Self {
         Self {
-            values: [0; MEM_SIZE].into(),
-            ages: [MAX_AGE; MEM_SIZE].into(),
-            owners: [NO_OWNER; MEM_SIZE].into(),
+            values: WrappingArray::filled(0, size),
+            ages: WrappingArray::filled(MAX_AGE, size),
+            owners: WrappingArray::filled(NO_OWNER, size),
         }
     }
-}
 
-impl<const LEN: usize> Memory<LEN> {
     pub fn size(&self) -> usize {
-        LEN
+        self.values.len()
     }
 
     pub fn tick(&mut self) {
@@ -42,10 +47,18 @@ impl<const LEN: usize> Memory<LEN> {
         }
     }
 
+    /// Same as calling [`Memory::tick`] `cycles` times
+    pub fn tick_by(&mut self, cycles: u32) {
+        let cycles = cycles.min(u32::from(MAX_AGE)) as CellAge;
+        for age in self.ages.inner_mut() {
+            *age = age.saturating_sub(cycles)
+        }
+    }
+
     pub fn write(&mut self, at: usize, bytes: &[u8], owner: Owner) {
         let len = bytes.len();
 
-        if at + len > LEN {
+        if at + len > self.size() {
             for (i, byte) in bytes.iter().enumerate() {
                 self.values[at + i] = *byte;
                 self.ages[at + i] = MAX_AGE;
@@ -59,7 +72,7 @@ impl<const LEN: usize> Memory<LEN> {
     }
 
     pub fn read_i32(&self, addr: usize) -> i32 {
-        if addr > LEN - mem::size_of::<i32>() {
+        if addr + mem::size_of::<i32>() > self.size() {
             i32::from_be_bytes([
                 self[addr + 0],
                 self[addr + 1],
@@ -72,7 +85,7 @@ impl<const LEN: usize> Memory<LEN> {
     }
 
     pub fn read_i16(&self, addr: usize) -> i16 {
-        if addr > LEN - mem::size_of::<i16>() {
+        if addr + mem::size_of::<i16>() > self.size() {
             i16::from_be_bytes([self[addr + 0], self[addr + 1]])
         } else {
             BigEndian::read_i16(&self.values.inner()[addr..addr + 2])
@@ -86,7 +99,7 @@ impl<const LEN: usize> Memory<LEN> {
     }
 }
 
-impl<const LEN: usize> std::ops::Index<usize> for Memory<LEN> {
+impl std::ops::Index<usize> for Memory {
     type Output = u8;
 
     fn index(&self, index: usize) -> &u8 {
@@ -94,7 +107,7 @@ impl<const LEN: usize> std::ops::Index<usize> for Memory<LEN> {
     }
 }
 
-impl<const LEN: usize> super::decoder::Read for Memory<LEN> {
+impl super::decoder::Read for Memory {
     fn read_i16(&self, at: usize) -> i16 {
         self.read_i16(at)
     }
@@ -1,25 +1,41 @@
+pub mod builder;
+pub mod debugger;
 pub mod decoder;
+pub mod loader;
 pub mod memory;
+pub mod observer;
+pub mod outcome;
 pub mod process;
+pub mod replay;
+pub mod snapshot;
+pub mod trace;
 pub mod types;
 
 mod execution_context;
 mod instructions;
 mod program_counter;
+mod rng;
 mod wrapping_array;
 
-use crate::spec::*;
+use crate::{
+    rules::{RuleSet, Semantics},
+    spec::*,
+};
+use debugger::Breakpoints;
 use decoder::Decode;
 use execution_context::ExecutionContext;
-use memory::{Memory, Owner};
+use memory::Memory;
+use observer::{RegisterState, VmEvent, VmObserver};
+use outcome::{EndReason, MatchOutcome, TickStatus};
 use process::{Process, ProcessState};
+use snapshot::History;
 use types::*;
 
-use std::ffi::CStr;
-
 use fxhash::FxHashSet as HashSet;
+use std::collections::VecDeque;
 
 pub struct VirtualMachine {
+    pub rules: RuleSet,
     pub players: arrayvec::ArrayVec<Player, MAX_PLAYERS>,
 
     pub memory: Memory,
@@ -34,19 +50,36 @@ pub struct VirtualMachine {
     pub live_count_since_last_check: u32,
     pub checks_without_cycle_decrement: u32,
 
-    pub process_count_per_cells: [u32; MEM_SIZE],
+    pub process_count_per_cells: Vec<u32>,
     pub process_count_by_owner: [u32; MAX_PLAYERS],
 
+    /// Characters emitted by `aff`, by player. Only the last
+    /// [`MAX_AFF_OUTPUT`] characters of each player are kept
+    pub aff_outputs: [VecDeque<AffChar>; MAX_PLAYERS],
+
+    pub breakpoints: Breakpoints,
+
     forks: Vec<Process>,
     live_ids: HashSet<PlayerId>,
+
+    /// Periodic snapshots used to step back in time, off by default
+    history: Option<History>,
 }
 
 impl VirtualMachine {
     pub fn new() -> Self {
+        Self::with_rules(RuleSet::default())
+    }
+
+    /// `rules` must be valid, see [`RuleSet::validate`]: the builder checks
+    /// it and reports an error instead
+    pub fn with_rules(rules: RuleSet) -> Self {
+        debug_assert!(rules.validate().is_ok(), "Invalid rule set: {:?}", rules);
+
         Self {
             players: arrayvec::ArrayVec::new(),
 
-            memory: Memory::default(),
+            memory: Memory::new(rules.mem_size),
             processes: Vec::with_capacity(1 << 20),
             pid_pool: PidPool::default(),
 
@@ -54,86 +87,167 @@ impl VirtualMachine {
 
             cycles: 0,
             last_live_check: 0,
-            check_interval: CHECK_INTERVAL,
+            check_interval: rules.check_interval,
             live_count_since_last_check: 0,
             checks_without_cycle_decrement: 0,
 
-            process_count_per_cells: [0; MEM_SIZE],
+            process_count_per_cells: vec![0; rules.mem_size],
             process_count_by_owner: [0; MAX_PLAYERS],
 
+            aff_outputs: Default::default(),
+
+            breakpoints: Breakpoints::default(),
+
             forks: Vec::with_capacity(1 << 16),
             live_ids: HashSet::with_hasher(Default::default()),
+
+            history: None,
+
+            rules,
         }
     }
 
-    pub fn tick(&mut self) {
+    pub fn tick(&mut self) -> TickStatus {
+        self.tick_with(&mut ())
+    }
+
+    /// Runs a cycle and reports everything that happened during it to `observer`
+    pub fn tick_with(&mut self, observer: &mut dyn VmObserver) -> TickStatus {
         if self.processes.is_empty() {
-            return;
+            return TickStatus::Finished(self.outcome_with(EndReason::AllProcessesKilled));
+        }
+
+        self.record_checkpoint();
+
+        if self.breakpoints.is_empty() {
+            self.run_cycle(observer);
+        } else {
+            let hits = self.run_watched_cycle(observer);
+            if !hits.is_empty() {
+                return TickStatus::Break(hits);
+            }
         }
 
-        self.run_processes();
+        match self.outcome() {
+            Some(outcome) => TickStatus::Finished(outcome),
+            None => TickStatus::Running,
+        }
+    }
+
+    fn run_cycle(&mut self, observer: &mut dyn VmObserver) {
+        self.run_processes(observer);
         self.memory.tick();
         self.cycles += 1;
 
         let should_live_check = self.cycles - self.last_live_check >= self.check_interval;
 
         if should_live_check {
-            self.live_check()
+            self.live_check(observer)
         }
     }
 
-    pub fn load_players(&mut self, players: &[(PlayerId, Vec<u8>)]) {
-        let player_spacing = MEM_SIZE / players.len().max(1);
-        for ((player_id, program), idx) in players.iter().zip(0..) {
-            let header_bytes = &program[..HEADER_SIZE];
-            let header = Header::from_bytes(header_bytes);
-
-            self.players.push(Player {
-                id: *player_id,
-                name: header
-                    .name()
-                    .to_owned()
-                    .into_string()
-                    .expect("Invalid UTF8 in program name"),
-                comment: header
-                    .comment()
-                    .to_owned()
-                    .into_string()
-                    .expect("Invalid UTF8 in program comment"),
-                size: program.len() - HEADER_SIZE,
-            });
+    /// Runs the match until its end, or until `cycle_limit` is reached.
+    /// Cycles during which every process is waiting are skipped at once
+    pub fn run_to_completion(&mut self, cycle_limit: Option<u32>) -> MatchOutcome {
+        self.run_to_completion_with(cycle_limit, &mut ())
+    }
+
+    /// Same as [`VirtualMachine::run_to_completion`], reporting every event
+    /// to `observer`
+    pub fn run_to_completion_with(
+        &mut self,
+        cycle_limit: Option<u32>,
+        observer: &mut dyn VmObserver,
+    ) -> MatchOutcome {
+        loop {
+            if let Some(outcome) = self.outcome() {
+                return outcome;
+            }
+            self.fast_forward(cycle_limit);
+            if cycle_limit.is_some_and(|limit| self.cycles >= limit) {
+                return self.outcome_with(EndReason::CycleLimitReached);
+            }
+            self.tick_with(observer);
+        }
+    }
+
+    /// Jumps to the next cycle where something happens: a process decodes or
+    /// executes an instruction, a live check runs or, with the history
+    /// enabled, a checkpoint is due.
+    /// This has the same effect as ticking through the skipped cycles
+    fn fast_forward(&mut self, cycle_limit: Option<u32>) {
+        // The tick of cycle `c` live checks once `c + 1 - last_live_check`
+        // reaches the check interval
+        let next_live_check = (self.last_live_check + self.check_interval).saturating_sub(1);
+        let mut next_busy_cycle = next_live_check;
+        if self.history.is_some() {
+            let next_checkpoint = self.cycles.next_multiple_of(snapshot::CHECKPOINT_INTERVAL);
+            next_busy_cycle = next_busy_cycle.min(next_checkpoint);
+        }
+        if let Some(limit) = cycle_limit {
+            next_busy_cycle = next_busy_cycle.min(limit);
+        }
+
+        for process in &self.processes {
+            match process.state {
+                ProcessState::Idle => return,
+                ProcessState::Executing { exec_at, .. } if exec_at >= self.cycles => {
+                    next_busy_cycle = next_busy_cycle.min(exec_at)
+                }
+                _ => (),
+            }
+        }
 
-            let champion = &program[HEADER_SIZE..];
-            self.load_champion(champion, *player_id, idx, usize::from(idx) * player_spacing);
+        if next_busy_cycle > self.cycles {
+            self.memory.tick_by(next_busy_cycle - self.cycles);
+            self.cycles = next_busy_cycle;
         }
     }
 
-    fn load_champion(&mut self, champion: &[u8], player_id: PlayerId, owner: Owner, at: usize) {
-        self.memory.write(at, champion, owner);
+    pub fn is_over(&self) -> bool {
+        self.processes.is_empty()
+    }
 
-        let mut starting_process = Process::new(self.pid_pool.get(), owner, at.into());
-        starting_process.registers[0] = player_id;
+    pub fn outcome(&self) -> Option<MatchOutcome> {
+        if self.is_over() {
+            Some(self.outcome_with(EndReason::AllProcessesKilled))
+        } else {
+            None
+        }
+    }
+
+    pub fn aff_text(&self, player_idx: usize) -> String {
+        self.aff_outputs
+            .get(player_idx)
+            .map(|output| output.iter().map(AffChar::chr).collect())
+            .unwrap_or_default()
+    }
 
-        self.processes.push(starting_process);
-        self.last_lives[usize::from(owner)] = 0;
-        self.process_count_per_cells[at] += 1;
-        self.process_count_by_owner[usize::from(owner)] = 1;
+    fn outcome_with(&self, reason: EndReason) -> MatchOutcome {
+        MatchOutcome::new(
+            self.players.iter().map(|player| player.id),
+            &self.last_lives[..self.players.len()],
+            self.cycles,
+            reason,
+        )
     }
 
-    fn run_processes(&mut self) {
+    fn run_processes(&mut self, observer: &mut dyn VmObserver) {
         let forks = &mut self.forks;
         let live_ids = &mut self.live_ids;
+        let rules = &self.rules;
+        let wants_registers = observer.wants_registers();
 
         for process in self.processes.iter_mut().rev() {
             match process.state {
                 // Attempt to read instruction
                 ProcessState::Idle => {
                     if let Ok(op) = self.memory.decode_op(process.pc.addr()) {
-                        let exec_at = self.cycles + op_spec(op).cycles - 1;
+                        let exec_at = self.cycles + rules.ops.get(op).cycles - 1;
                         process.state = ProcessState::Executing { exec_at, op };
                     } else {
                         let pc_start = process.pc.addr();
-                        process.pc.advance(1);
+                        process.pc.advance(1, rules);
                         self.process_count_per_cells[pc_start] -= 1;
                         self.process_count_per_cells[process.pc.addr()] += 1;
                     }
@@ -141,8 +255,9 @@ impl VirtualMachine {
                 // Execute
                 ProcessState::Executing { exec_at, op } if exec_at == self.cycles => {
                     let pc_start = process.pc.addr();
-                    match self.memory.decode_instr(op, pc_start) {
+                    match self.memory.decode_instr(op, pc_start, rules) {
                         Ok(instr) => {
+                            let before = wants_registers.then(|| RegisterState::of(process));
                             let execution_context = ExecutionContext {
                                 memory: &mut self.memory,
                                 process,
@@ -151,11 +266,27 @@ impl VirtualMachine {
                                 live_count: &mut self.live_count_since_last_check,
                                 pid_pool: &mut self.pid_pool,
                                 live_ids,
+                                aff_outputs: &mut self.aff_outputs,
+                                observer: &mut *observer,
+                                rules,
                             };
                             execute_instr(&instr, execution_context);
+                            observer.notify(VmEvent::InstructionExecuted {
+                                cycle: self.cycles,
+                                pid: process.pid,
+                                owner: process.owner,
+                                pc: pc_start,
+                                instr,
+                                before,
+                                after: wants_registers.then(|| RegisterState::of(process)),
+                            });
                         }
                         Err(_e) => {
-                            process.pc.advance(1);
+                            let skipped = match rules.semantics {
+                                Semantics::Native => 1,
+                                Semantics::Compat => self.memory.encoded_size(op, pc_start, rules),
+                            };
+                            process.pc.advance(skipped as isize, rules);
                         }
                     };
                     process.state = ProcessState::Idle;
@@ -183,32 +314,57 @@ impl VirtualMachine {
         live_ids.clear();
     }
 
-    fn live_check(&mut self) {
+    fn live_check(&mut self, observer: &mut dyn VmObserver) {
         let count_per_cells = &mut self.process_count_per_cells;
         let count_by_owner = &mut self.process_count_by_owner;
 
+        let cycle = self.cycles;
         let last_live_check = self.last_live_check;
+        let (semantics, check_interval) = (self.rules.semantics, self.check_interval);
+        let is_dead = |process: &Process| match semantics {
+            Semantics::Native => process.last_live_cycle <= last_live_check,
+            // A last live at cycle 0 means that the process never reported a live
+            Semantics::Compat => {
+                check_interval == 0
+                    || process.last_live_cycle == 0
+                    || process.last_live_cycle < last_live_check
+            }
+        };
         self.processes.retain(|process| {
-            let killed = process.last_live_cycle <= last_live_check;
+            let killed = is_dead(process);
             if killed {
                 count_per_cells[process.pc.addr()] -= 1;
                 count_by_owner[usize::from(process.owner)] -= 1;
+                observer.notify(VmEvent::ProcessKilled {
+                    cycle,
+                    pid: process.pid,
+                    owner: process.owner,
+                });
             }
             !killed
         });
 
-        if self.live_count_since_last_check >= NBR_LIVE {
-            self.check_interval = self.check_interval.saturating_sub(CYCLE_DELTA);
+        let previous_check_interval = self.check_interval;
+
+        if self.live_count_since_last_check >= self.rules.nbr_live {
+            self.check_interval = self.check_interval.saturating_sub(self.rules.cycle_delta);
             self.checks_without_cycle_decrement = 0;
         } else {
             self.checks_without_cycle_decrement += 1;
         }
 
-        if self.checks_without_cycle_decrement >= MAX_CHECKS {
-            self.check_interval = self.check_interval.saturating_sub(CYCLE_DELTA);
+        if self.checks_without_cycle_decrement >= self.rules.max_checks {
+            self.check_interval = self.check_interval.saturating_sub(self.rules.cycle_delta);
             self.checks_without_cycle_decrement = 0;
         }
 
+        if self.check_interval != previous_check_interval {
+            observer.notify(VmEvent::CheckIntervalDecreased {
+                cycle,
+                check_interval: self.check_interval,
+            });
+        }
+
         self.live_count_since_last_check = 0;
         self.last_live_check = self.cycles;
     }
@@ -244,55 +400,10 @@ fn execute_instr(instr: &Instruction, mut ctx: ExecutionContext<'_>) {
     };
 
     exec(instr, &mut ctx);
-    ctx.process.pc.advance(instr.byte_size as isize);
-}
-
-impl Header {
-    fn from_bytes(bytes: &[u8]) -> Self {
-        use byteorder::{BigEndian, ReadBytesExt};
-        use std::io::{Cursor, Read};
-
-        let mut reader = Cursor::new(bytes);
-
-        let magic = reader
-            .read_u32::<BigEndian>()
-            .expect("Failed to read Magic");
-
-        let mut prog_name = [0; PROG_NAME_LENGTH + 1];
-        reader
-            .read_exact(&mut prog_name)
-            .expect("Failed to read program name");
-
-        let prog_size = reader
-            .read_u32::<BigEndian>()
-            .expect("Failed to read program size");
-
-        let mut prog_comment = [0; PROG_COMMENT_LENGTH + 1];
-        reader
-            .read_exact(&mut prog_comment)
-            .expect("Failed to read program name");
-
-        Self {
-            magic,
-            prog_name,
-            prog_size,
-            prog_comment,
-        }
-    }
-
-    fn name(&self) -> &CStr {
-        let idx = self.prog_name.iter().position(|&b| b == 0).unwrap();
-        CStr::from_bytes_with_nul(&self.prog_name[..idx + 1]).expect("Invalid program name")
-    }
-
-    fn comment(&self) -> &CStr {
-        let idx = self.prog_comment.iter().position(|&b| b == 0).unwrap();
-
-        CStr::from_bytes_with_nul(&self.prog_comment[..idx + 1]).expect("Invalid program comment")
-    }
+    ctx.process.pc.advance(instr.byte_size as isize, ctx.rules);
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, derive_more::From)]
 pub struct PidPool(Pid);
 
 impl PidPool {
@@ -300,4 +411,8 @@ impl PidPool {
         let next = self.0 + 1;
         std::mem::replace(&mut self.0, next)
     }
+
+    pub fn peek(&self) -> Pid {
+        self.0
+    }
 }
@@ -0,0 +1,92 @@
+use super::{memory::Owner, process::Process, types::*};
+
+/// Something that happened inside the VM during a tick
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmEvent {
+    /// A process executed a valid instruction that started at `pc`.
+    /// The registers are only copied for the observers that want them, see
+    /// [`VmObserver::wants_registers`]
+    InstructionExecuted {
+        cycle: u32,
+        pid: Pid,
+        owner: Owner,
+        pc: usize,
+        instr: Instruction,
+        before: Option<RegisterState>,
+        after: Option<RegisterState>,
+    },
+    /// `len` bytes of memory starting at `addr` were written by a process,
+    /// holding the big endian encoding of `value`
+    MemoryWrite {
+        cycle: u32,
+        pid: Pid,
+        owner: Owner,
+        addr: usize,
+        len: usize,
+        value: i32,
+    },
+    ForkSpawned {
+        cycle: u32,
+        parent: Pid,
+        child: Pid,
+        pc: usize,
+    },
+    LiveReported {
+        cycle: u32,
+        pid: Pid,
+        player_id: PlayerId,
+    },
+    ProcessKilled {
+        cycle: u32,
+        pid: Pid,
+        owner: Owner,
+    },
+    CheckIntervalDecreased {
+        cycle: u32,
+        check_interval: u32,
+    },
+}
+
+/// The registers and zero flag of a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterState {
+    pub registers: Registers,
+    pub zf: bool,
+}
+
+impl RegisterState {
+    pub fn of(process: &Process) -> Self {
+        Self {
+            registers: process.registers,
+            zf: process.zf,
+        }
+    }
+}
+
+/// Receives the events of a VM, see [`super::VirtualMachine::tick_with`]
+pub trait VmObserver {
+    fn notify(&mut self, event: VmEvent);
+
+    /// Whether [`VmEvent::InstructionExecuted`] should carry the registers of
+    /// the process before and after the instruction, which costs two copies
+    /// per instruction
+    fn wants_registers(&self) -> bool {
+        false
+    }
+}
+
+/// The unit observer ignores every event
+impl VmObserver for () {
+    fn notify(&mut self, _event: VmEvent) {}
+}
+
+/// Collects every event
+impl VmObserver for Vec<VmEvent> {
+    fn notify(&mut self, event: VmEvent) {
+        self.push(event)
+    }
+
+    fn wants_registers(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,67 @@
+use super::{debugger::BreakpointHit, types::PlayerId};
+use crate::spec::MAX_PLAYERS;
+
+use arrayvec::ArrayVec;
+
+/// The result of a finished (or interrupted) match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchOutcome {
+    /// The id of the last player reported alive.
+    /// Ties (including when no player was ever reported alive) go to the
+    /// player that was loaded last.
+    /// `None` only when no player was loaded at all
+    pub winner: Option<PlayerId>,
+    /// The cycle at which each player was last reported alive, by load order
+    pub last_lives: ArrayVec<u32, MAX_PLAYERS>,
+    /// The cycle at which the match ended
+    pub cycles: u32,
+    pub reason: EndReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndReason {
+    /// Every process has been killed during live-checks
+    AllProcessesKilled,
+    /// The match was stopped before its end by a cycle limit
+    CycleLimitReached,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TickStatus {
+    Running,
+    Finished(MatchOutcome),
+    /// Some breakpoints fired during the cycle.
+    /// If the match ended during that cycle as well, the next tick reports it
+    Break(Vec<BreakpointHit>),
+}
+
+impl TickStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, TickStatus::Finished(_))
+    }
+}
+
+impl MatchOutcome {
+    pub(super) fn new(
+        player_ids: impl Iterator<Item = PlayerId>,
+        last_lives: &[u32],
+        cycles: u32,
+        reason: EndReason,
+    ) -> Self {
+        let last_lives: ArrayVec<_, MAX_PLAYERS> = last_lives.iter().copied().collect();
+
+        // `max_by_key` yields the last maximum element, which implements the
+        // tie-breaking rule in favor of the player loaded last
+        let winner = player_ids
+            .zip(&last_lives)
+            .max_by_key(|&(_, last_live)| *last_live)
+            .map(|(id, _)| id);
+
+        Self {
+            winner,
+            last_lives,
+            cycles,
+            reason,
+        }
+    }
+}
@@ -4,9 +4,9 @@ use super::{
     program_counter::ProgramCounter,
     types::{Pid, Registers},
 };
-use crate::spec::OpType;
+use crate::{rules::Semantics, spec::OpType};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Process {
     pub pid: Pid,
     pub owner: Owner,
@@ -16,7 +16,7 @@ pub struct Process {
     pub state: ProcessState,
     pub last_live_cycle: u32,
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProcessState {
     Idle,
     Executing { op: OpType, exec_at: u32 },
@@ -43,7 +43,10 @@ impl Process {
             registers: ctx.process.registers,
             zf: ctx.process.zf,
             state: ProcessState::Idle,
-            last_live_cycle: 0,
+            last_live_cycle: match ctx.rules.semantics {
+                Semantics::Native => 0,
+                Semantics::Compat => ctx.process.last_live_cycle,
+            },
         }
     }
 }
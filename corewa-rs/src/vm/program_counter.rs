@@ -1,25 +1,25 @@
 use super::types::OffsetType;
-use crate::spec::{IDX_MOD, MEM_SIZE};
+use crate::rules::RuleSet;
 
-#[derive(Debug, Default, derive_more::From)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, derive_more::From)]
 pub struct ProgramCounter(usize);
 
-fn mem_offset(at: usize, offset: isize) -> usize {
-    (at as isize + offset + MEM_SIZE as isize) as usize % MEM_SIZE
+fn mem_offset(at: usize, offset: isize, mem_size: usize) -> usize {
+    (at as isize + offset).rem_euclid(mem_size as isize) as usize
 }
 
 impl ProgramCounter {
-    pub fn advance(&mut self, offset: isize) {
-        self.0 = mem_offset(self.0, offset);
+    pub fn advance(&mut self, offset: isize, rules: &RuleSet) {
+        self.0 = mem_offset(self.0, offset, rules.mem_size);
     }
 
-    pub fn offset(&self, offset: isize, offset_type: OffsetType) -> usize {
+    pub fn offset(&self, offset: isize, offset_type: OffsetType, rules: &RuleSet) -> usize {
         let reach = match offset_type {
-            OffsetType::Limited => IDX_MOD,
-            OffsetType::Long => MEM_SIZE,
+            OffsetType::Limited => rules.idx_mod,
+            OffsetType::Long => rules.mem_size,
         };
         let offset = offset % reach as isize;
-        mem_offset(self.0, offset)
+        mem_offset(self.0, offset, rules.mem_size)
     }
 
     pub fn addr(&self) -> usize {
@@ -0,0 +1,644 @@
+use super::{
+    memory::{CellAge, Owner},
+    observer::{VmEvent, VmObserver},
+    outcome::TickStatus,
+    process::Process,
+    snapshot::{
+        read_process, read_rules, valid_owner, write_process, write_rules, Snapshot, SnapshotError,
+        MAX_PROCESSES,
+    },
+    types::*,
+    PidPool, VirtualMachine,
+};
+use crate::{rules::RuleSet, spec::MAX_PLAYERS};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::io::{self, BufReader, Read, Write};
+
+const REPLAY_MAGIC: [u8; 4] = *b"CWRP";
+const REPLAY_VERSION: u16 = 1;
+
+/// Number of cycles between two complete states of the replay, bounding the
+/// number of changes to apply when seeking
+const KEYFRAME_INTERVAL: u32 = 256;
+
+const KEYFRAME_TAG: u8 = 0;
+const DELTA_TAG: u8 = 1;
+
+/// Bound on the size of the decompressed stream, so that a small crafted file
+/// cannot make the reader allocate much
+const MAX_STREAM_SIZE: u64 = 1 << 30;
+
+/// The setup of a recorded match: its rules and its players, in the order they
+/// were loaded. The random bytes a [`super::builder::RandomSetup`] may have
+/// filled the memory with are not part of it: simulating the match again must
+/// start from the first frame, see [`ReplayReader::state`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayHeader {
+    pub rules: RuleSet,
+    pub players: Vec<ReplayPlayer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayPlayer {
+    pub id: PlayerId,
+    /// The complete binary of the champion, header included
+    pub champion: Vec<u8>,
+    /// Where the champion was loaded in memory
+    pub address: usize,
+}
+
+/// Records a match in a replay file, one state change per cycle.
+///
+/// The format is a magic number and a version followed by a zlib stream of
+/// the header and of frames that are either complete [`Snapshot`]s or the
+/// changes since the previous frame.
+///
+/// The VM is ticked through [`ReplayWriter::tick`], the writer observing the
+/// memory cells to record
+pub struct ReplayWriter<W: Write> {
+    out: ZlibEncoder<W>,
+    /// What the last frame holds that the VM does not tell about its changes
+    previous: RecordedState,
+    /// Cells written since the last frame
+    written: Vec<usize>,
+}
+
+struct RecordedState {
+    cycles: u32,
+    /// Sorted by pid, like the processes of the VM
+    processes: Vec<Process>,
+}
+
+impl RecordedState {
+    fn of(vm: &VirtualMachine) -> Self {
+        Self {
+            cycles: vm.cycles,
+            processes: vm.processes.clone(),
+        }
+    }
+}
+
+impl<W: Write> ReplayWriter<W> {
+    /// Starts recording the match of `vm`, which must have just been loaded.
+    /// `champions` are the binaries that were loaded for each player id
+    pub fn new(
+        mut out: W,
+        vm: &VirtualMachine,
+        champions: &[(PlayerId, Vec<u8>)],
+    ) -> Result<Self, ReplayError> {
+        let mut players = Vec::with_capacity(vm.players.len());
+        for (idx, player) in vm.players.iter().enumerate() {
+            let (_, champion) = champions
+                .iter()
+                .find(|(id, _)| *id == player.id)
+                .ok_or(ReplayError::MissingChampion(player.id))?;
+            let address = vm
+                .processes
+                .iter()
+                .find(|process| usize::from(process.owner) == idx)
+                .map_or(0, |process| process.pc.addr());
+            players.push(ReplayPlayer {
+                id: player.id,
+                champion: champion.clone(),
+                address,
+            });
+        }
+
+        out.write_all(&REPLAY_MAGIC)?;
+        out.write_u16::<BigEndian>(REPLAY_VERSION)?;
+
+        let mut out = ZlibEncoder::new(out, Compression::default());
+        write_header(
+            &mut out,
+            &ReplayHeader {
+                rules: vm.rules.clone(),
+                players,
+            },
+        )?;
+
+        out.write_u8(KEYFRAME_TAG)?;
+        vm.snapshot().write_to(&mut out)?;
+
+        Ok(Self {
+            out,
+            previous: RecordedState::of(vm),
+            written: Vec::new(),
+        })
+    }
+
+    /// Ticks `vm` and records its new state.
+    /// When `vm` was ticked without the writer since the last frame, its memory
+    /// changes were not observed and a complete state is recorded instead
+    pub fn tick(&mut self, vm: &mut VirtualMachine) -> io::Result<TickStatus> {
+        let missed_changes = vm.cycles > self.previous.cycles;
+        let status = vm.tick_with(self);
+        self.record(vm, missed_changes)?;
+        Ok(status)
+    }
+
+    fn record(&mut self, vm: &VirtualMachine, missed_changes: bool) -> io::Result<()> {
+        // Cycles already recorded, after stepping back
+        if vm.cycles <= self.previous.cycles {
+            return Ok(());
+        }
+
+        if missed_changes
+            || vm.cycles / KEYFRAME_INTERVAL > self.previous.cycles / KEYFRAME_INTERVAL
+        {
+            self.out.write_u8(KEYFRAME_TAG)?;
+            vm.snapshot().write_to(&mut self.out)?;
+            self.previous = RecordedState::of(vm);
+        } else {
+            let delta = StateDelta::between(&mut self.previous, vm, &mut self.written);
+            self.out.write_u8(DELTA_TAG)?;
+            delta.write_to(&mut self.out)?;
+        }
+        self.written.clear();
+
+        Ok(())
+    }
+
+    /// Completes the compressed stream and returns the underlying writer
+    pub fn finish(self) -> io::Result<W> {
+        self.out.finish()
+    }
+}
+
+impl<W: Write> VmObserver for ReplayWriter<W> {
+    fn notify(&mut self, event: VmEvent) {
+        if let VmEvent::MemoryWrite { addr, len, .. } = event {
+            self.written.extend(addr..addr + len);
+        }
+    }
+}
+
+/// Plays back a replay, seeking to any recorded cycle without simulating
+/// the match
+#[derive(Debug)]
+pub struct ReplayReader {
+    pub header: ReplayHeader,
+    frames: Vec<Frame>,
+    position: usize,
+    state: Snapshot,
+}
+
+#[derive(Debug)]
+enum Frame {
+    Keyframe(Box<Snapshot>),
+    Delta(StateDelta),
+}
+
+impl Frame {
+    fn cycles(&self) -> u32 {
+        match self {
+            Frame::Keyframe(snapshot) => snapshot.cycles,
+            Frame::Delta(delta) => delta.cycles,
+        }
+    }
+}
+
+impl ReplayReader {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        Self::read_from(bytes)
+    }
+
+    pub fn read_from(mut input: impl Read) -> Result<Self, ReplayError> {
+        use ReplayError::*;
+
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if magic != REPLAY_MAGIC {
+            return Err(InvalidMagic);
+        }
+        let version = input.read_u16::<BigEndian>()?;
+        if version != REPLAY_VERSION {
+            return Err(UnsupportedVersion(version));
+        }
+
+        // Decompresses as the frames are read, so that only what they hold
+        // is kept in memory
+        let mut stream = BufReader::new(ZlibDecoder::new(input)).take(MAX_STREAM_SIZE);
+        let too_big = |stream: &io::Take<_>, error| match stream.limit() {
+            0 => InvalidData("replay too big"),
+            _ => error,
+        };
+
+        let header = read_header(&mut stream).map_err(|e| too_big(&stream, e))?;
+        let mut frames = Vec::new();
+        loop {
+            let mut tag = [0];
+            if stream.read(&mut tag)? == 0 {
+                if stream.limit() == 0 {
+                    return Err(InvalidData("replay too big"));
+                }
+                break;
+            }
+            let frame = match tag[0] {
+                KEYFRAME_TAG => Snapshot::read_from(&mut stream)
+                    .map(|snapshot| Frame::Keyframe(Box::new(snapshot)))
+                    .map_err(ReplayError::from),
+                DELTA_TAG => StateDelta::read_from(&mut stream, &header.rules).map(Frame::Delta),
+                _ => Err(InvalidData("invalid frame")),
+            }
+            .map_err(|e| too_big(&stream, e))?;
+            if frames
+                .last()
+                .is_some_and(|previous: &Frame| previous.cycles() >= frame.cycles())
+            {
+                return Err(InvalidData("frames out of order"));
+            }
+            frames.push(frame);
+        }
+
+        let state = match frames.first() {
+            Some(Frame::Keyframe(snapshot)) => Snapshot::clone(snapshot),
+            _ => return Err(InvalidData("missing initial state")),
+        };
+
+        Ok(Self {
+            header,
+            frames,
+            position: 0,
+            state,
+        })
+    }
+
+    /// The state at the current cycle
+    pub fn state(&self) -> &Snapshot {
+        &self.state
+    }
+
+    pub fn first_cycle(&self) -> u32 {
+        self.frames[0].cycles()
+    }
+
+    pub fn last_cycle(&self) -> u32 {
+        self.frames[self.frames.len() - 1].cycles()
+    }
+
+    /// Moves to the last recorded state at or before `cycle`
+    pub fn seek(&mut self, cycle: u32) -> &Snapshot {
+        let target = self
+            .frames
+            .partition_point(|frame| frame.cycles() <= cycle)
+            .saturating_sub(1);
+        let keyframe = self.frames[..=target]
+            .iter()
+            .rposition(|frame| matches!(frame, Frame::Keyframe(_)))
+            .unwrap_or(0);
+
+        // Moving forward from the current state is cheaper than from the
+        // keyframe, as long as no other keyframe is in between
+        if !(keyframe..=target).contains(&self.position) {
+            if let Frame::Keyframe(snapshot) = &self.frames[keyframe] {
+                self.state.clone_from(snapshot);
+            }
+            self.position = keyframe;
+        }
+        while self.position < target {
+            self.position += 1;
+            match &self.frames[self.position] {
+                Frame::Keyframe(snapshot) => self.state.clone_from(snapshot),
+                Frame::Delta(delta) => delta.apply(&mut self.state),
+            }
+        }
+
+        &self.state
+    }
+
+    /// Moves to the next recorded state, returns `false` at the end
+    pub fn step(&mut self) -> bool {
+        match self.frames.get(self.position + 1) {
+            Some(frame) => {
+                let cycle = frame.cycles();
+                self.seek(cycle);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn write_header(mut out: impl Write, header: &ReplayHeader) -> io::Result<()> {
+    write_rules(&mut out, &header.rules)?;
+    out.write_u8(header.players.len() as u8)?;
+    for player in &header.players {
+        out.write_i32::<BigEndian>(player.id)?;
+        out.write_u32::<BigEndian>(player.address as u32)?;
+        out.write_u32::<BigEndian>(player.champion.len() as u32)?;
+        out.write_all(&player.champion)?;
+    }
+    Ok(())
+}
+
+fn read_header(mut input: impl Read) -> Result<ReplayHeader, ReplayError> {
+    let rules = read_rules(&mut input, true)?;
+
+    let player_count = usize::from(input.read_u8()?);
+    if player_count > MAX_PLAYERS {
+        return Err(ReplayError::InvalidData("too many players"));
+    }
+    let mut players = Vec::with_capacity(player_count);
+    for _ in 0..player_count {
+        let id = input.read_i32::<BigEndian>()?;
+        let address = input.read_u32::<BigEndian>()? as usize;
+        let len = input.read_u32::<BigEndian>()?;
+        let mut champion = Vec::new();
+        (&mut input)
+            .take(u64::from(len))
+            .read_to_end(&mut champion)?;
+        if champion.len() != len as usize {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        players.push(ReplayPlayer {
+            id,
+            champion,
+            address,
+        });
+    }
+
+    Ok(ReplayHeader { rules, players })
+}
+
+/// The changes of state between two frames
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct StateDelta {
+    cycles: u32,
+    last_live_check: u32,
+    check_interval: u32,
+    live_count_since_last_check: u32,
+    checks_without_cycle_decrement: u32,
+    next_pid: Pid,
+    last_lives: [u32; MAX_PLAYERS],
+
+    cells: Vec<CellChange>,
+    killed: Vec<Pid>,
+    /// Processes that were created or changed, sorted by pid
+    updated: Vec<Process>,
+    aff_chars: [Vec<AffChar>; MAX_PLAYERS],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CellChange {
+    addr: usize,
+    value: u8,
+    age: CellAge,
+    owner: Owner,
+}
+
+impl StateDelta {
+    /// The changes from `previous` to the state of `vm`, `written` holding
+    /// every cell written in between, which `previous` is updated to
+    fn between(
+        previous: &mut RecordedState,
+        vm: &VirtualMachine,
+        written: &mut Vec<usize>,
+    ) -> Self {
+        let memory = &vm.memory;
+        for addr in written.iter_mut() {
+            *addr %= memory.size();
+        }
+        written.sort_unstable();
+        written.dedup();
+        let cells = written
+            .iter()
+            .map(|&addr| CellChange {
+                addr,
+                value: memory.values[addr],
+                age: memory.ages[addr],
+                owner: memory.owners[addr],
+            })
+            .collect();
+
+        // Processes are always sorted by pid
+        let mut killed = Vec::new();
+        let mut updated = Vec::new();
+        let mut previous_processes = previous.processes.iter().peekable();
+        for process in &vm.processes {
+            while let Some(gone) = previous_processes.next_if(|prev| prev.pid < process.pid) {
+                killed.push(gone.pid);
+            }
+            match previous_processes.next_if(|prev| prev.pid == process.pid) {
+                Some(prev) if prev == process => (),
+                _ => updated.push(process.clone()),
+            }
+        }
+        killed.extend(previous_processes.map(|gone| gone.pid));
+
+        // The outputs stop growing once full, the new characters are told
+        // apart by their cycle: the tick from `previous.cycles` emits them
+        // with that cycle
+        let mut aff_chars: [Vec<AffChar>; MAX_PLAYERS] = Default::default();
+        for (chars, output) in aff_chars.iter_mut().zip(&vm.aff_outputs) {
+            let new_count = output
+                .iter()
+                .rev()
+                .take_while(|chr| chr.cycle >= previous.cycles)
+                .count();
+            chars.extend(output.range(output.len() - new_count..));
+        }
+
+        previous.cycles = vm.cycles;
+        apply_process_changes(&mut previous.processes, &killed, &updated);
+
+        Self {
+            cycles: vm.cycles,
+            last_live_check: vm.last_live_check,
+            check_interval: vm.check_interval,
+            live_count_since_last_check: vm.live_count_since_last_check,
+            checks_without_cycle_decrement: vm.checks_without_cycle_decrement,
+            next_pid: vm.pid_pool.peek(),
+            last_lives: vm.last_lives,
+            cells,
+            killed,
+            updated,
+            aff_chars,
+        }
+    }
+
+    fn apply(&self, state: &mut Snapshot) {
+        state.memory.tick_by(self.cycles - state.cycles);
+        for cell in &self.cells {
+            state.memory.values[cell.addr] = cell.value;
+            state.memory.ages[cell.addr] = cell.age;
+            state.memory.owners[cell.addr] = cell.owner;
+        }
+
+        apply_process_changes(&mut state.processes, &self.killed, &self.updated);
+
+        for (output, chars) in state.aff_outputs.iter_mut().zip(&self.aff_chars) {
+            for &chr in chars {
+                chr.push_to(output);
+            }
+        }
+
+        state.cycles = self.cycles;
+        state.last_live_check = self.last_live_check;
+        state.check_interval = self.check_interval;
+        state.live_count_since_last_check = self.live_count_since_last_check;
+        state.checks_without_cycle_decrement = self.checks_without_cycle_decrement;
+        state.pid_pool = PidPool::from(self.next_pid);
+        state.last_lives = self.last_lives;
+    }
+
+    fn write_to(&self, mut out: impl Write) -> io::Result<()> {
+        for value in [
+            self.cycles,
+            self.last_live_check,
+            self.check_interval,
+            self.live_count_since_last_check,
+            self.checks_without_cycle_decrement,
+            self.next_pid,
+        ] {
+            out.write_u32::<BigEndian>(value)?;
+        }
+        for &last_live in &self.last_lives {
+            out.write_u32::<BigEndian>(last_live)?;
+        }
+
+        out.write_u32::<BigEndian>(self.cells.len() as u32)?;
+        for cell in &self.cells {
+            out.write_u32::<BigEndian>(cell.addr as u32)?;
+            out.write_u8(cell.value)?;
+            out.write_u16::<BigEndian>(cell.age)?;
+            out.write_u8(cell.owner)?;
+        }
+
+        out.write_u32::<BigEndian>(self.killed.len() as u32)?;
+        for &pid in &self.killed {
+            out.write_u32::<BigEndian>(pid)?;
+        }
+        out.write_u32::<BigEndian>(self.updated.len() as u32)?;
+        for process in &self.updated {
+            write_process(&mut out, process)?;
+        }
+
+        for chars in &self.aff_chars {
+            out.write_u32::<BigEndian>(chars.len() as u32)?;
+            for chr in chars {
+                out.write_u8(chr.byte)?;
+                out.write_u32::<BigEndian>(chr.cycle)?;
+                out.write_u32::<BigEndian>(chr.pid)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_from(mut input: impl Read, rules: &RuleSet) -> Result<Self, ReplayError> {
+        use ReplayError::InvalidData;
+
+        let mut counters = [0; 6];
+        for counter in &mut counters {
+            *counter = input.read_u32::<BigEndian>()?;
+        }
+        let [cycles, last_live_check, check_interval, live_count_since_last_check, checks_without_cycle_decrement, next_pid] =
+            counters;
+        let mut last_lives = [0; MAX_PLAYERS];
+        for last_live in &mut last_lives {
+            *last_live = input.read_u32::<BigEndian>()?;
+        }
+
+        let cell_count = input.read_u32::<BigEndian>()?;
+        if cell_count as usize > rules.mem_size {
+            return Err(InvalidData("too many memory changes"));
+        }
+        let mut cells = Vec::new();
+        for _ in 0..cell_count {
+            let cell = CellChange {
+                addr: input.read_u32::<BigEndian>()? as usize,
+                value: input.read_u8()?,
+                age: input.read_u16::<BigEndian>()?,
+                owner: input.read_u8()?,
+            };
+            if cell.addr >= rules.mem_size || !valid_owner(cell.owner) {
+                return Err(InvalidData("invalid memory change"));
+            }
+            cells.push(cell);
+        }
+
+        let killed_count = input.read_u32::<BigEndian>()?;
+        if killed_count > MAX_PROCESSES {
+            return Err(InvalidData("too many processes"));
+        }
+        let mut killed = Vec::new();
+        for _ in 0..killed_count {
+            killed.push(input.read_u32::<BigEndian>()?);
+        }
+        let updated_count = input.read_u32::<BigEndian>()?;
+        if updated_count > MAX_PROCESSES {
+            return Err(InvalidData("too many processes"));
+        }
+        let mut updated = Vec::new();
+        for _ in 0..updated_count {
+            updated.push(read_process(&mut input, rules)?);
+        }
+        let updated_pids: Vec<_> = updated.iter().map(|process| process.pid).collect();
+        if !is_sorted(&killed) || !is_sorted(&updated_pids) {
+            return Err(InvalidData("unsorted processes"));
+        }
+
+        let mut aff_chars: [Vec<AffChar>; MAX_PLAYERS] = Default::default();
+        for chars in &mut aff_chars {
+            let len = input.read_u32::<BigEndian>()?;
+            if len as usize > MAX_AFF_OUTPUT {
+                return Err(InvalidData("too many aff characters"));
+            }
+            for _ in 0..len {
+                chars.push(AffChar {
+                    byte: input.read_u8()?,
+                    cycle: input.read_u32::<BigEndian>()?,
+                    pid: input.read_u32::<BigEndian>()?,
+                });
+            }
+        }
+
+        Ok(Self {
+            cycles,
+            last_live_check,
+            check_interval,
+            live_count_since_last_check,
+            checks_without_cycle_decrement,
+            next_pid,
+            last_lives,
+            cells,
+            killed,
+            updated,
+            aff_chars,
+        })
+    }
+}
+
+/// Removes the `killed` processes and inserts or replaces the `updated` ones,
+/// both being sorted by pid like `processes`
+fn apply_process_changes(processes: &mut Vec<Process>, killed: &[Pid], updated: &[Process]) {
+    processes.retain(|process| killed.binary_search(&process.pid).is_err());
+    for process in updated {
+        match processes.binary_search_by_key(&process.pid, |process| process.pid) {
+            Ok(idx) => processes[idx].clone_from(process),
+            Err(idx) => processes.insert(idx, process.clone()),
+        }
+    }
+}
+
+fn is_sorted(pids: &[Pid]) -> bool {
+    pids.windows(2).all(|pair| pair[0] < pair[1])
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError {
+    #[error("IO error while reading replay: {0}")]
+    IOError(#[from] io::Error),
+    #[error("Not a replay")]
+    InvalidMagic,
+    #[error("Unsupported replay version: {0}")]
+    UnsupportedVersion(u16),
+    #[error("Invalid replay data: {0}")]
+    InvalidData(&'static str),
+    #[error("Invalid replay state: {0}")]
+    InvalidState(#[from] SnapshotError),
+    #[error("No champion was provided for player {0}")]
+    MissingChampion(PlayerId),
+}
@@ -0,0 +1,555 @@
+use super::{
+    decoder::op_from_code,
+    memory::{Memory, Owner, NO_OWNER},
+    process::{Process, ProcessState},
+    types::*,
+    PidPool, VirtualMachine,
+};
+use crate::{
+    rules::{RuleSet, Semantics},
+    spec::{DirectSize, OffsetType, MAX_PARAMS, MAX_PLAYERS, OP_TYPES},
+};
+
+use arrayvec::ArrayVec;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+};
+
+const SNAPSHOT_MAGIC: [u8; 4] = *b"CWVM";
+/// Version 2 added the semantics of the rules, version 1 snapshots are read
+/// with the native semantics
+const SNAPSHOT_VERSION: u16 = 2;
+
+/// Bound on the process count read from a file, so that a small crafted file
+/// cannot make the reader allocate much. The memory size is bounded by
+/// [`RuleSet::validate`]
+pub(super) const MAX_PROCESSES: u32 = 1 << 24;
+
+/// Number of cycles between two checkpoints used by [`VirtualMachine::step_back`]
+pub(super) const CHECKPOINT_INTERVAL: u32 = 256;
+
+/// Periodic snapshots used to step back in time, the oldest ones being
+/// dropped once `capacity` of them are kept
+#[derive(Debug, Clone)]
+pub(super) struct History {
+    checkpoints: VecDeque<Snapshot>,
+    capacity: usize,
+}
+
+impl History {
+    fn new(capacity: usize) -> Self {
+        Self {
+            checkpoints: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.checkpoints.clear();
+    }
+}
+
+/// The complete state of a [`VirtualMachine`] in between two ticks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub rules: RuleSet,
+    pub players: ArrayVec<Player, MAX_PLAYERS>,
+
+    pub memory: Memory,
+    pub processes: Vec<Process>,
+    pub pid_pool: PidPool,
+
+    pub last_lives: [u32; MAX_PLAYERS],
+
+    pub cycles: u32,
+    pub last_live_check: u32,
+    pub check_interval: u32,
+    pub live_count_since_last_check: u32,
+    pub checks_without_cycle_decrement: u32,
+
+    pub aff_outputs: [VecDeque<AffChar>; MAX_PLAYERS],
+}
+
+impl VirtualMachine {
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            rules: self.rules.clone(),
+            players: self.players.clone(),
+
+            memory: self.memory.clone(),
+            processes: self.processes.clone(),
+            pid_pool: self.pid_pool.clone(),
+
+            last_lives: self.last_lives,
+
+            cycles: self.cycles,
+            last_live_check: self.last_live_check,
+            check_interval: self.check_interval,
+            live_count_since_last_check: self.live_count_since_last_check,
+            checks_without_cycle_decrement: self.checks_without_cycle_decrement,
+
+            aff_outputs: self.aff_outputs.clone(),
+        }
+    }
+
+    /// Restores the state of `snapshot`, forgetting the history of the VM
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+        self.restore_state(snapshot);
+    }
+
+    /// Starts keeping a checkpoint every 256 cycles, for [`Self::step_back`].
+    /// Only the `max_checkpoints` most recent ones are kept, which bounds both
+    /// the memory used and how far back the VM can go
+    pub fn enable_history(&mut self, max_checkpoints: usize) {
+        let max_checkpoints = max_checkpoints.max(1);
+        match &mut self.history {
+            Some(history) => {
+                while history.checkpoints.len() > max_checkpoints {
+                    history.checkpoints.pop_front();
+                }
+                history.capacity = max_checkpoints;
+            }
+            None => self.history = Some(History::new(max_checkpoints)),
+        }
+    }
+
+    /// Stops keeping checkpoints and forgets the ones taken so far
+    pub fn disable_history(&mut self) {
+        self.history = None;
+    }
+
+    pub fn is_history_enabled(&self) -> bool {
+        self.history.is_some()
+    }
+
+    /// Rewinds the VM `n` cycles back, in the exact state it was at the time.
+    /// The VM cannot go further back than its oldest checkpoint, nor than the
+    /// loading of the players, and cannot go back at all unless its history
+    /// is enabled, see [`Self::enable_history`].
+    /// Returns the number of cycles actually rewound
+    pub fn step_back(&mut self, n: u32) -> u32 {
+        let mut history = match self.history.take() {
+            Some(history) => history,
+            None => return 0,
+        };
+        let cycles = self.cycles;
+        let target = cycles.saturating_sub(n);
+
+        // Replays from the closest checkpoint, the VM being deterministic
+        let idx = history
+            .checkpoints
+            .iter()
+            .rposition(|checkpoint| checkpoint.cycles <= target)
+            .unwrap_or(0);
+        history.checkpoints.truncate(idx + 1);
+        if let Some(checkpoint) = history.checkpoints.back() {
+            self.restore_state(checkpoint);
+        }
+
+        let breakpoints = std::mem::take(&mut self.breakpoints);
+        while self.cycles < target {
+            self.tick();
+        }
+        self.breakpoints = breakpoints;
+        self.history = Some(history);
+
+        cycles - self.cycles
+    }
+
+    pub(super) fn record_checkpoint(&mut self) {
+        let history = match &self.history {
+            Some(history) => history,
+            None => return,
+        };
+        let is_recorded = history
+            .checkpoints
+            .back()
+            .is_some_and(|checkpoint| checkpoint.cycles >= self.cycles);
+        let is_due =
+            self.cycles.is_multiple_of(CHECKPOINT_INTERVAL) || history.checkpoints.is_empty();
+        if is_due && !is_recorded {
+            let checkpoint = self.snapshot();
+            if let Some(history) = &mut self.history {
+                if history.checkpoints.len() == history.capacity {
+                    history.checkpoints.pop_front();
+                }
+                history.checkpoints.push_back(checkpoint);
+            }
+        }
+    }
+
+    fn restore_state(&mut self, snapshot: &Snapshot) {
+        self.rules.clone_from(&snapshot.rules);
+        self.players.clone_from(&snapshot.players);
+
+        self.memory.clone_from(&snapshot.memory);
+        self.processes.clone_from(&snapshot.processes);
+        self.pid_pool.clone_from(&snapshot.pid_pool);
+
+        self.last_lives = snapshot.last_lives;
+
+        self.cycles = snapshot.cycles;
+        self.last_live_check = snapshot.last_live_check;
+        self.check_interval = snapshot.check_interval;
+        self.live_count_since_last_check = snapshot.live_count_since_last_check;
+        self.checks_without_cycle_decrement = snapshot.checks_without_cycle_decrement;
+
+        self.aff_outputs.clone_from(&snapshot.aff_outputs);
+
+        // Derived state
+        self.process_count_per_cells.clear();
+        self.process_count_per_cells.resize(self.rules.mem_size, 0);
+        self.process_count_by_owner = [0; MAX_PLAYERS];
+        for process in &self.processes {
+            self.process_count_per_cells[process.pc.addr()] += 1;
+            self.process_count_by_owner[usize::from(process.owner)] += 1;
+        }
+
+        self.forks.clear();
+        self.live_ids.clear();
+    }
+
+    pub fn from_snapshot(snapshot: &Snapshot) -> Self {
+        let mut vm = Self::with_rules(snapshot.rules.clone());
+        vm.restore(snapshot);
+        vm
+    }
+}
+
+impl Snapshot {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 * self.memory.size());
+        self.write_to(&mut bytes)
+            .expect("Writing to a Vec cannot fail");
+        bytes
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let snapshot = Self::read_from(&mut bytes)?;
+        if bytes.is_empty() {
+            Ok(snapshot)
+        } else {
+            Err(SnapshotError::TrailingBytes(bytes.len()))
+        }
+    }
+
+    /// Writes the snapshot in a stable, versioned, big endian binary format
+    pub fn write_to(&self, mut out: impl Write) -> io::Result<()> {
+        out.write_all(&SNAPSHOT_MAGIC)?;
+        out.write_u16::<BigEndian>(SNAPSHOT_VERSION)?;
+
+        write_rules(&mut out, &self.rules)?;
+
+        out.write_u8(self.players.len() as u8)?;
+        for player in &self.players {
+            out.write_i32::<BigEndian>(player.id)?;
+            write_string(&mut out, &player.name)?;
+            write_string(&mut out, &player.comment)?;
+            out.write_u32::<BigEndian>(player.size as u32)?;
+        }
+
+        out.write_all(self.memory.values.inner())?;
+        for &age in self.memory.ages.inner() {
+            out.write_u16::<BigEndian>(age)?;
+        }
+        out.write_all(self.memory.owners.inner())?;
+
+        out.write_u32::<BigEndian>(self.processes.len() as u32)?;
+        for process in &self.processes {
+            write_process(&mut out, process)?;
+        }
+        out.write_u32::<BigEndian>(self.pid_pool.peek())?;
+
+        for &last_live in &self.last_lives {
+            out.write_u32::<BigEndian>(last_live)?;
+        }
+
+        for value in [
+            self.cycles,
+            self.last_live_check,
+            self.check_interval,
+            self.live_count_since_last_check,
+            self.checks_without_cycle_decrement,
+        ] {
+            out.write_u32::<BigEndian>(value)?;
+        }
+
+        for output in &self.aff_outputs {
+            out.write_u32::<BigEndian>(output.len() as u32)?;
+            for chr in output {
+                out.write_u8(chr.byte)?;
+                out.write_u32::<BigEndian>(chr.cycle)?;
+                out.write_u32::<BigEndian>(chr.pid)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from(mut input: impl Read) -> Result<Self, SnapshotError> {
+        use SnapshotError::*;
+
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if magic != SNAPSHOT_MAGIC {
+            return Err(InvalidMagic);
+        }
+        let version = input.read_u16::<BigEndian>()?;
+        if version == 0 || version > SNAPSHOT_VERSION {
+            return Err(UnsupportedVersion(version));
+        }
+
+        let rules = read_rules(&mut input, version >= 2)?;
+
+        let player_count = usize::from(input.read_u8()?);
+        if player_count > MAX_PLAYERS {
+            return Err(InvalidData("too many players"));
+        }
+        let mut players = ArrayVec::new();
+        for _ in 0..player_count {
+            players.push(Player {
+                id: input.read_i32::<BigEndian>()?,
+                name: read_string(&mut input)?,
+                comment: read_string(&mut input)?,
+                size: input.read_u32::<BigEndian>()? as usize,
+            });
+        }
+
+        let mut memory = Memory::new(rules.mem_size);
+        input.read_exact(memory.values.inner_mut())?;
+        for age in memory.ages.inner_mut() {
+            *age = input.read_u16::<BigEndian>()?;
+        }
+        input.read_exact(memory.owners.inner_mut())?;
+        if !memory
+            .owners
+            .inner()
+            .iter()
+            .all(|&owner| valid_owner(owner))
+        {
+            return Err(InvalidData("invalid memory owner"));
+        }
+
+        let process_count = input.read_u32::<BigEndian>()?;
+        if process_count > MAX_PROCESSES {
+            return Err(InvalidData("too many processes"));
+        }
+        let processes = (0..process_count)
+            .map(|_| read_process(&mut input, &rules))
+            .collect::<Result<_, _>>()?;
+        let pid_pool = PidPool::from(input.read_u32::<BigEndian>()?);
+
+        let mut last_lives = [0; MAX_PLAYERS];
+        for last_live in &mut last_lives {
+            *last_live = input.read_u32::<BigEndian>()?;
+        }
+
+        let mut counters = [0; 5];
+        for counter in &mut counters {
+            *counter = input.read_u32::<BigEndian>()?;
+        }
+        let [cycles, last_live_check, check_interval, live_count_since_last_check, checks_without_cycle_decrement] =
+            counters;
+
+        let mut aff_outputs: [VecDeque<AffChar>; MAX_PLAYERS] = Default::default();
+        for output in &mut aff_outputs {
+            let len = input.read_u32::<BigEndian>()?;
+            for _ in 0..len {
+                AffChar {
+                    byte: input.read_u8()?,
+                    cycle: input.read_u32::<BigEndian>()?,
+                    pid: input.read_u32::<BigEndian>()?,
+                }
+                .push_to(output);
+            }
+        }
+
+        Ok(Self {
+            rules,
+            players,
+            memory,
+            processes,
+            pid_pool,
+            last_lives,
+            cycles,
+            last_live_check,
+            check_interval,
+            live_count_since_last_check,
+            checks_without_cycle_decrement,
+            aff_outputs,
+        })
+    }
+}
+
+pub(super) fn valid_owner(owner: Owner) -> bool {
+    owner == NO_OWNER || usize::from(owner) < MAX_PLAYERS
+}
+
+fn write_string(mut out: impl Write, s: &str) -> io::Result<()> {
+    out.write_u32::<BigEndian>(s.len() as u32)?;
+    out.write_all(s.as_bytes())
+}
+
+fn read_string(mut input: impl Read) -> Result<String, SnapshotError> {
+    let len = input.read_u32::<BigEndian>()?;
+    let mut bytes = Vec::new();
+    input.take(u64::from(len)).read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(SnapshotError::IOError(io::ErrorKind::UnexpectedEof.into()));
+    }
+    String::from_utf8(bytes).map_err(|_| SnapshotError::InvalidData("invalid UTF8 string"))
+}
+
+pub(super) fn write_rules(mut out: impl Write, rules: &RuleSet) -> io::Result<()> {
+    for value in [rules.mem_size, rules.idx_mod, rules.champ_max_size] {
+        out.write_u32::<BigEndian>(value as u32)?;
+    }
+    for value in [
+        rules.check_interval,
+        rules.cycle_delta,
+        rules.nbr_live,
+        rules.max_checks,
+    ] {
+        out.write_u32::<BigEndian>(value)?;
+    }
+    out.write_u8(rules.reg_count as u8)?;
+
+    for op_type in OP_TYPES {
+        let spec = rules.ops.get(op_type);
+        out.write_u32::<BigEndian>(spec.cycles)?;
+        out.write_all(&spec.param_masks)?;
+        out.write_u8(spec.dir_size as u8)?;
+        out.write_u8(match spec.reach {
+            OffsetType::Limited => 0,
+            OffsetType::Long => 1,
+        })?;
+    }
+
+    out.write_u8(match rules.semantics {
+        Semantics::Native => 0,
+        Semantics::Compat => 1,
+    })
+}
+
+/// Reads rules written by [`write_rules`], or by its former version without
+/// the semantics if `has_semantics` is false, in which case they are native
+pub(super) fn read_rules(
+    mut input: impl Read,
+    has_semantics: bool,
+) -> Result<RuleSet, SnapshotError> {
+    let mut rules = RuleSet {
+        mem_size: input.read_u32::<BigEndian>()? as usize,
+        idx_mod: input.read_u32::<BigEndian>()? as usize,
+        champ_max_size: input.read_u32::<BigEndian>()? as usize,
+        check_interval: input.read_u32::<BigEndian>()?,
+        cycle_delta: input.read_u32::<BigEndian>()?,
+        nbr_live: input.read_u32::<BigEndian>()?,
+        max_checks: input.read_u32::<BigEndian>()?,
+        reg_count: usize::from(input.read_u8()?),
+        ..RuleSet::default()
+    };
+
+    for op_type in OP_TYPES {
+        let spec = rules.ops.get_mut(op_type);
+        spec.cycles = input.read_u32::<BigEndian>()?;
+        let mut param_masks = [0; MAX_PARAMS];
+        input.read_exact(&mut param_masks)?;
+        spec.param_masks = param_masks;
+        spec.dir_size = match input.read_u8()? {
+            2 => DirectSize::TwoBytes,
+            4 => DirectSize::FourBytes,
+            _ => return Err(SnapshotError::InvalidData("invalid direct size")),
+        };
+        spec.reach = match input.read_u8()? {
+            0 => OffsetType::Limited,
+            1 => OffsetType::Long,
+            _ => return Err(SnapshotError::InvalidData("invalid reach")),
+        };
+    }
+    if has_semantics {
+        rules.semantics = match input.read_u8()? {
+            0 => Semantics::Native,
+            1 => Semantics::Compat,
+            _ => return Err(SnapshotError::InvalidData("invalid semantics")),
+        };
+    }
+
+    rules
+        .validate()
+        .map_err(|_| SnapshotError::InvalidData("invalid rule set"))?;
+    Ok(rules)
+}
+
+pub(super) fn write_process(mut out: impl Write, process: &Process) -> io::Result<()> {
+    out.write_u32::<BigEndian>(process.pid)?;
+    out.write_u8(process.owner)?;
+    out.write_u32::<BigEndian>(process.pc.addr() as u32)?;
+    for &register in &process.registers {
+        out.write_i32::<BigEndian>(register)?;
+    }
+    out.write_u8(u8::from(process.zf))?;
+    match process.state {
+        ProcessState::Idle => out.write_u8(0)?,
+        ProcessState::Executing { op, exec_at } => {
+            out.write_u8(op as u8)?;
+            out.write_u32::<BigEndian>(exec_at)?;
+        }
+    }
+    out.write_u32::<BigEndian>(process.last_live_cycle)
+}
+
+pub(super) fn read_process(
+    mut input: impl Read,
+    rules: &RuleSet,
+) -> Result<Process, SnapshotError> {
+    use SnapshotError::InvalidData;
+
+    let pid = input.read_u32::<BigEndian>()?;
+    let owner = input.read_u8()?;
+    if usize::from(owner) >= MAX_PLAYERS {
+        return Err(InvalidData("invalid process owner"));
+    }
+    let pc = input.read_u32::<BigEndian>()? as usize;
+    if pc >= rules.mem_size {
+        return Err(InvalidData("process pc out of memory"));
+    }
+
+    let mut process = Process::new(pid, owner, pc.into());
+    for register in &mut process.registers {
+        *register = input.read_i32::<BigEndian>()?;
+    }
+    process.zf = match input.read_u8()? {
+        0 => false,
+        1 => true,
+        _ => return Err(InvalidData("invalid zero flag")),
+    };
+    process.state = match input.read_u8()? {
+        0 => ProcessState::Idle,
+        code => ProcessState::Executing {
+            op: op_from_code(code).ok_or(InvalidData("invalid op code"))?,
+            exec_at: input.read_u32::<BigEndian>()?,
+        },
+    };
+    process.last_live_cycle = input.read_u32::<BigEndian>()?;
+
+    Ok(process)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("IO error while reading snapshot: {0}")]
+    IOError(#[from] io::Error),
+    #[error("Not a VM snapshot")]
+    InvalidMagic,
+    #[error("Unsupported snapshot version: {0}")]
+    UnsupportedVersion(u16),
+    #[error("Invalid snapshot data: {0}")]
+    InvalidData(&'static str),
+    #[error("{0} unexpected bytes after the snapshot")]
+    TrailingBytes(usize),
+}
@@ -0,0 +1,387 @@
+use super::{
+    decoder::op_from_code,
+    memory::Owner,
+    observer::{VmEvent, VmObserver},
+    types::*,
+};
+use crate::spec::{
+    op_spec, ParamType, DIR_PARAM_CODE, IND_PARAM_CODE, MAX_PARAMS, REG_COUNT, REG_PARAM_CODE,
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::{
+    convert::TryFrom,
+    fmt,
+    io::{self, Read, Write},
+};
+
+const TRACE_MAGIC: [u8; 4] = *b"CWTR";
+const TRACE_VERSION: u16 = 2;
+
+/// Everything an executed instruction did to its process and the memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub cycle: u32,
+    pub pid: Pid,
+    pub owner: Owner,
+    pub pc: usize,
+    pub instr: Instruction,
+    pub register_changes: Vec<RegisterChange>,
+    /// The new value of the zero flag, if it changed
+    pub zf_change: Option<bool>,
+    pub writes: Vec<TraceWrite>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterChange {
+    /// Numbered from 1
+    pub reg: usize,
+    pub old: Register,
+    pub new: Register,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceWrite {
+    pub addr: usize,
+    pub bytes: Vec<u8>,
+}
+
+/// How a [`TraceRecorder`] writes its entries, see [`write_text`] and
+/// [`write_binary`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    Text,
+    Binary,
+}
+
+/// Builds a [`TraceEntry`] for every instruction executed by the VM it observes
+/// and writes it to `out` right away, so that nothing piles up in memory
+#[derive(Debug)]
+pub struct TraceRecorder<W: Write> {
+    out: W,
+    format: TraceFormat,
+    count: u32,
+    /// Memory writes of the instruction currently executing
+    pending_writes: Vec<TraceWrite>,
+    /// The first error met while writing, observers being unable to report it
+    error: Option<io::Error>,
+}
+
+impl<W: Write> TraceRecorder<W> {
+    /// Starts the trace, writing the header of the binary format
+    pub fn new(mut out: W, format: TraceFormat) -> io::Result<Self> {
+        if format == TraceFormat::Binary {
+            write_binary_header(&mut out)?;
+        }
+        Ok(Self {
+            out,
+            format,
+            count: 0,
+            pending_writes: Vec::new(),
+            error: None,
+        })
+    }
+
+    /// Ends the trace, writing the trailer of the binary format, and gives
+    /// back the sink
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if self.format == TraceFormat::Binary {
+            write_binary_trailer(&mut self.out, self.count)?;
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        match self.format {
+            TraceFormat::Text => writeln!(self.out, "{}", entry),
+            TraceFormat::Binary => write_binary_entry(&mut self.out, entry),
+        }
+    }
+}
+
+impl<W: Write> VmObserver for TraceRecorder<W> {
+    fn notify(&mut self, event: VmEvent) {
+        match event {
+            VmEvent::MemoryWrite {
+                addr, len, value, ..
+            } => {
+                let bytes = value.to_be_bytes();
+                self.pending_writes.push(TraceWrite {
+                    addr,
+                    bytes: bytes[bytes.len().saturating_sub(len)..].to_vec(),
+                });
+            }
+            VmEvent::InstructionExecuted {
+                cycle,
+                pid,
+                owner,
+                pc,
+                instr,
+                before,
+                after,
+            } => {
+                let (register_changes, zf_change) = match before.zip(after) {
+                    Some((before, after)) => (
+                        (0..REG_COUNT)
+                            .filter(|&idx| before.registers[idx] != after.registers[idx])
+                            .map(|idx| RegisterChange {
+                                reg: idx + 1,
+                                old: before.registers[idx],
+                                new: after.registers[idx],
+                            })
+                            .collect(),
+                        Some(after.zf).filter(|&zf| zf != before.zf),
+                    ),
+                    None => (Vec::new(), None),
+                };
+                let entry = TraceEntry {
+                    cycle,
+                    pid,
+                    owner,
+                    pc,
+                    instr,
+                    register_changes,
+                    zf_change,
+                    writes: std::mem::take(&mut self.pending_writes),
+                };
+                // The binary format stores these lengths on a byte
+                debug_assert!(
+                    entry.register_changes.len() <= REG_COUNT,
+                    "An instruction changes at most every register"
+                );
+                debug_assert!(
+                    entry.writes.len() <= 1
+                        && entry
+                            .writes
+                            .iter()
+                            .all(|write| write.bytes.len() == std::mem::size_of::<Register>()),
+                    "An instruction writes at most one register to memory"
+                );
+                if self.error.is_none() {
+                    match self.write(&entry) {
+                        Ok(()) => self.count += 1,
+                        Err(e) => self.error = Some(e),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn wants_registers(&self) -> bool {
+        true
+    }
+}
+
+/// One line per entry: `CYCLE PID OWNER PC INSTRUCTION`, followed by its
+/// effects, e.g. `9 0 0 7 st r2, 20 | mem 27 00 00 00 07`
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.cycle, self.pid, self.owner, self.pc, self.instr
+        )?;
+        for change in &self.register_changes {
+            write!(f, " | r{} {} -> {}", change.reg, change.old, change.new)?;
+        }
+        if let Some(zf) = self.zf_change {
+            write!(f, " | zf {} -> {}", u8::from(!zf), u8::from(zf))?;
+        }
+        for write in &self.writes {
+            write!(f, " | mem {}", write.addr)?;
+            for byte in &write.bytes {
+                write!(f, " {:02x}", byte)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn write_text(entries: &[TraceEntry], mut out: impl Write) -> io::Result<()> {
+    for entry in entries {
+        writeln!(out, "{}", entry)?;
+    }
+    Ok(())
+}
+
+/// Writes the entries in a compact, versioned, big endian binary format.
+/// Every entry is preceded by a `1` byte, and the trace ends with a `0` byte
+/// followed by the number of entries, so that it can be written as the
+/// entries come
+pub fn write_binary(entries: &[TraceEntry], mut out: impl Write) -> io::Result<()> {
+    write_binary_header(&mut out)?;
+    for entry in entries {
+        write_binary_entry(&mut out, entry)?;
+    }
+    write_binary_trailer(&mut out, entries.len() as u32)
+}
+
+fn write_binary_header(mut out: impl Write) -> io::Result<()> {
+    out.write_all(&TRACE_MAGIC)?;
+    out.write_u16::<BigEndian>(TRACE_VERSION)
+}
+
+fn write_binary_trailer(mut out: impl Write, count: u32) -> io::Result<()> {
+    out.write_u8(0)?;
+    out.write_u32::<BigEndian>(count)
+}
+
+fn write_binary_entry(mut out: impl Write, entry: &TraceEntry) -> io::Result<()> {
+    out.write_u8(1)?;
+    out.write_u32::<BigEndian>(entry.cycle)?;
+    out.write_u32::<BigEndian>(entry.pid)?;
+    out.write_u8(entry.owner)?;
+    out.write_u32::<BigEndian>(entry.pc as u32)?;
+
+    let instr = &entry.instr;
+    out.write_u8(instr.kind as u8)?;
+    for param in &instr.params[..op_spec(instr.kind).param_count] {
+        out.write_u8(match param.kind {
+            ParamType::Register => REG_PARAM_CODE,
+            ParamType::Direct => DIR_PARAM_CODE,
+            ParamType::Indirect => IND_PARAM_CODE,
+        })?;
+        out.write_i32::<BigEndian>(param.value)?;
+    }
+    out.write_u8(instr.byte_size as u8)?;
+
+    write_len(&mut out, entry.register_changes.len(), "register changes")?;
+    for change in &entry.register_changes {
+        out.write_u8(change.reg as u8)?;
+        out.write_i32::<BigEndian>(change.old)?;
+        out.write_i32::<BigEndian>(change.new)?;
+    }
+    out.write_u8(match entry.zf_change {
+        None => 0,
+        Some(false) => 1,
+        Some(true) => 2,
+    })?;
+
+    write_len(&mut out, entry.writes.len(), "memory writes")?;
+    for write in &entry.writes {
+        out.write_u32::<BigEndian>(write.addr as u32)?;
+        write_len(&mut out, write.bytes.len(), "written bytes")?;
+        out.write_all(&write.bytes)?;
+    }
+    Ok(())
+}
+
+/// Lengths are written on a single byte, which the entries recorded from a VM
+/// are far from needing
+fn write_len(mut out: impl Write, len: usize, what: &str) -> io::Result<()> {
+    let len = u8::try_from(len).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Too many {} for a binary trace entry: {}", what, len),
+        )
+    })?;
+    out.write_u8(len)
+}
+
+pub fn read_binary(mut input: impl Read) -> Result<Vec<TraceEntry>, TraceError> {
+    use TraceError::*;
+
+    let mut magic = [0; 4];
+    input.read_exact(&mut magic)?;
+    if magic != TRACE_MAGIC {
+        return Err(InvalidMagic);
+    }
+    let version = input.read_u16::<BigEndian>()?;
+    if version != TRACE_VERSION {
+        return Err(UnsupportedVersion(version));
+    }
+
+    let mut entries = Vec::new();
+    loop {
+        match input.read_u8()? {
+            0 => break,
+            1 => (),
+            _ => return Err(InvalidData("invalid entry marker")),
+        }
+
+        let cycle = input.read_u32::<BigEndian>()?;
+        let pid = input.read_u32::<BigEndian>()?;
+        let owner = input.read_u8()?;
+        let pc = input.read_u32::<BigEndian>()? as usize;
+
+        let kind = op_from_code(input.read_u8()?).ok_or(InvalidData("invalid op code"))?;
+        let mut params = [Param::default(); MAX_PARAMS];
+        for param in &mut params[..op_spec(kind).param_count] {
+            param.kind = match input.read_u8()? {
+                REG_PARAM_CODE => ParamType::Register,
+                DIR_PARAM_CODE => ParamType::Direct,
+                IND_PARAM_CODE => ParamType::Indirect,
+                _ => return Err(InvalidData("invalid parameter type")),
+            };
+            param.value = input.read_i32::<BigEndian>()?;
+        }
+        let byte_size = usize::from(input.read_u8()?);
+
+        let register_changes = (0..input.read_u8()?)
+            .map(|_| {
+                let reg = usize::from(input.read_u8()?);
+                if !(1..=REG_COUNT).contains(&reg) {
+                    return Err(InvalidData("invalid register"));
+                }
+                Ok(RegisterChange {
+                    reg,
+                    old: input.read_i32::<BigEndian>()?,
+                    new: input.read_i32::<BigEndian>()?,
+                })
+            })
+            .collect::<Result<_, TraceError>>()?;
+        let zf_change = match input.read_u8()? {
+            0 => None,
+            1 => Some(false),
+            2 => Some(true),
+            _ => return Err(InvalidData("invalid zero flag change")),
+        };
+
+        let writes = (0..input.read_u8()?)
+            .map(|_| {
+                let addr = input.read_u32::<BigEndian>()? as usize;
+                let mut bytes = vec![0; usize::from(input.read_u8()?)];
+                input.read_exact(&mut bytes)?;
+                Ok(TraceWrite { addr, bytes })
+            })
+            .collect::<Result<_, TraceError>>()?;
+
+        entries.push(TraceEntry {
+            cycle,
+            pid,
+            owner,
+            pc,
+            instr: Instruction {
+                kind,
+                params,
+                byte_size,
+            },
+            register_changes,
+            zf_change,
+            writes,
+        });
+    }
+
+    let count = input.read_u32::<BigEndian>()?;
+    if count as usize != entries.len() {
+        return Err(InvalidData("wrong entry count"));
+    }
+    Ok(entries)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TraceError {
+    #[error("IO error while reading trace: {0}")]
+    IOError(#[from] io::Error),
+    #[error("Not a VM trace")]
+    InvalidMagic,
+    #[error("Unsupported trace version: {0}")]
+    UnsupportedVersion(u16),
+    #[error("Invalid trace data: {0}")]
+    InvalidData(&'static str),
+}
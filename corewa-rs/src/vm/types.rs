@@ -1,7 +1,13 @@
 use crate::spec::{op_spec, OpType, ParamType, MAX_PARAMS, REG_COUNT};
-use std::fmt;
 
-#[derive(Debug)]
+pub use crate::spec::OffsetType;
+use std::{collections::VecDeque, fmt};
+
+/// Number of characters kept in the `aff` output of each player, the oldest
+/// ones being dropped past it
+pub const MAX_AFF_OUTPUT: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Player {
     pub id: PlayerId,
     pub name: String,
@@ -9,25 +15,42 @@ pub struct Player {
     pub size: usize,
 }
 
-#[derive(Debug)]
+/// A character emitted by the `aff` instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AffChar {
+    pub byte: u8,
+    pub cycle: u32,
+    pub pid: Pid,
+}
+
+impl AffChar {
+    pub fn chr(&self) -> char {
+        char::from(self.byte)
+    }
+
+    /// Appends the character to `output`, dropping the oldest one when it
+    /// already holds [`MAX_AFF_OUTPUT`] of them
+    pub(super) fn push_to(self, output: &mut VecDeque<AffChar>) {
+        if output.len() >= MAX_AFF_OUTPUT {
+            output.pop_front();
+        }
+        output.push_back(self);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Instruction {
     pub kind: OpType,
     pub params: [Param; MAX_PARAMS],
     pub byte_size: usize,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Param {
     pub kind: ParamType,
     pub value: i32,
 }
 
-#[derive(Debug)]
-pub enum OffsetType {
-    Limited,
-    Long,
-}
-
 pub type Register = i32;
 pub type Pid = u32;
 pub type Registers = [Register; REG_COUNT];
@@ -1,31 +1,52 @@
-#[derive(derive_more::From)]
-pub struct WrappingArray<T, const LEN: usize>([T; LEN]);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappingArray<T>(Box<[T]>);
 
-impl<T, const LEN: usize> WrappingArray<T, LEN> {
+impl<T> WrappingArray<T> {
     pub fn as_ptr(&self) -> *const T {
         self.0.as_ptr()
     }
 
-    pub fn inner(&self) -> &[T; LEN] {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn inner(&self) -> &[T] {
         &self.0
     }
 
-    pub fn inner_mut(&mut self) -> &mut [T; LEN] {
+    pub fn inner_mut(&mut self) -> &mut [T] {
         &mut self.0
     }
 }
 
-impl<T, const LEN: usize> std::ops::Index<usize> for WrappingArray<T, LEN> {
+impl<T: Clone> WrappingArray<T> {
+    pub fn filled(value: T, len: usize) -> Self {
+        vec![value; len].into()
+    }
+}
+
+impl<T> From<Vec<T>> for WrappingArray<T> {
+    fn from(values: Vec<T>) -> Self {
+        Self(values.into_boxed_slice())
+    }
+}
+
+impl<T> std::ops::Index<usize> for WrappingArray<T> {
     type Output = T;
 
     fn index(&self, index: usize) -> &T {
-        self.0.index(index % LEN)
+        self.0.index(index % self.0.len())
     }
 }
 
-impl<T, const LEN: usize> std::ops::IndexMut<usize> for WrappingArray<T, LEN> {
+impl<T> std::ops::IndexMut<usize> for WrappingArray<T> {
     fn index_mut(&mut self, index: usize) -> &mut T {
-        self.0.index_mut(index % LEN)
+        let len = self.0.len();
+        self.0.index_mut(index % len)
     }
 }
 
@@ -34,10 +55,10 @@ mod test {
     use super::WrappingArray;
 
     fn usize_indexing_wraps_correctly<const N: usize>() {
-        let array: WrappingArray<_, N> = {
+        let array: WrappingArray<_> = {
             let mut array = [0; N];
             (0..N).for_each(|x| array[x] = x);
-            array.into()
+            array.to_vec().into()
         };
 
         for offset in 0..256 {
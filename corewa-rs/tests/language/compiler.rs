@@ -0,0 +1,302 @@
+use super::{span, write};
+use corewa_rs::{
+    language::{
+        self,
+        compiler::{compile_champion_exhaustive, CompileError, CompileOptions},
+        WriteError,
+    },
+    rules::RuleSet,
+    spec::{OpType, HEADER_SIZE},
+};
+
+use std::io::Cursor;
+
+/// The code written for `input`, without the header
+fn compile(input: &str, rules: &RuleSet) -> Result<Vec<u8>, WriteError> {
+    write(input, rules).map(|out| out[HEADER_SIZE..].to_vec())
+}
+
+const STI_CHAMPION: &str = r#"
+.name "sti"
+.comment "sti"
+sti r1, %1, %2
+"#;
+
+#[test]
+fn default_rules() {
+    assert_eq!(
+        compile(STI_CHAMPION, &RuleSet::default()).unwrap(),
+        [0x0B, 0x68, 0x01, 0x00, 0x01, 0x00, 0x02]
+    );
+}
+
+#[test]
+fn custom_dir_size() {
+    let rules = "sti.dir_size = 4".parse().unwrap();
+    assert_eq!(
+        compile(STI_CHAMPION, &rules).unwrap(),
+        [0x0B, 0x68, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02]
+    );
+}
+
+#[test]
+fn forbidden_param_type() {
+    let rules = "sti.params = R, RDI, R".parse().unwrap();
+    assert_matches!(
+        compile(STI_CHAMPION, &rules),
+        Err(WriteError::CompileError(CompileError::ForbiddenParamType(
+            OpType::Sti,
+            3
+        )))
+    );
+}
+
+#[test]
+fn champion_max_size() {
+    let rules = "champ_max_size = 4".parse().unwrap();
+    assert_matches!(
+        compile(STI_CHAMPION, &rules),
+        Err(WriteError::CompileError(CompileError::ProgramTooLong(7, 4)))
+    );
+}
+
+fn compile_wrapping(input: &str, rules: &RuleSet) -> Vec<u8> {
+    let champion = language::read_champion(input.as_bytes()).expect("Failed to read champion");
+    let mut out = Cursor::new(Vec::new());
+    let options = CompileOptions { wrap_numbers: true };
+    compile_champion_exhaustive(&mut out, champion, rules, &options).unwrap();
+    out.into_inner()[HEADER_SIZE..].to_vec()
+}
+
+const OVERFLOWING_CHAMPION: &str = r#"
+.name "overflow"
+.comment "overflow"
+sti r1, %65536, %-1
+"#;
+
+#[test]
+fn numbers_in_range() {
+    assert_eq!(
+        compile(
+            ".name \"\"\n.comment \"\"\nld %-2147483648, r1\nld 65535, r1\n.code 255 -128",
+            &RuleSet::default()
+        )
+        .unwrap(),
+        [0x02, 0x90, 0x80, 0x00, 0x00, 0x00, 0x01, 0x02, 0xD0, 0xFF, 0xFF, 0x01, 0xFF, 0x80]
+    );
+}
+
+#[test]
+fn numbers_out_of_range() {
+    assert_matches!(
+        compile(OVERFLOWING_CHAMPION, &RuleSet::default()),
+        Err(WriteError::CompileError(CompileError::NumberOutOfRange(
+            65536, 2
+        )))
+    );
+    assert_matches!(
+        compile(
+            ".name \"\"\n.comment \"\"\nld -32769, r1",
+            &RuleSet::default()
+        ),
+        Err(WriteError::CompileError(CompileError::NumberOutOfRange(
+            -32769, 2
+        )))
+    );
+    assert_matches!(
+        compile(
+            ".name \"\"\n.comment \"\"\n.code 1 256",
+            &RuleSet::default()
+        ),
+        Err(WriteError::CompileError(CompileError::NumberOutOfRange(
+            256, 1
+        )))
+    );
+
+    let rules = "sti.dir_size = 4".parse().unwrap();
+    assert!(compile(OVERFLOWING_CHAMPION, &rules).is_ok());
+}
+
+#[test]
+fn wrapping_numbers() {
+    assert_eq!(
+        compile_wrapping(OVERFLOWING_CHAMPION, &RuleSet::default()),
+        [0x0B, 0x68, 0x01, 0x00, 0x00, 0xFF, 0xFF]
+    );
+    assert_eq!(
+        compile_wrapping(
+            ".name \"\"\n.comment \"\"\n.code 256 -129",
+            &RuleSet::default()
+        ),
+        [0x00, 0x7F]
+    );
+}
+
+#[test]
+fn label_offset_out_of_range() {
+    let rules: RuleSet = "mem_size = 65536\nchamp_max_size = 40000".parse().unwrap();
+    let padding = ".code 0 0 0 0 0 0 0 0 0 0\n".repeat(3300);
+    let source = format!(
+        ".name \"far\"\n.comment \"\"\nstart:\n{}ld :start, r1\n",
+        padding
+    );
+
+    assert_matches!(
+        compile(&source, &rules),
+        Err(WriteError::CompileError(CompileError::LabelOffsetOutOfRange(
+            label,
+            -33000,
+            2
+        ))) if label == "start"
+    );
+    assert_eq!(
+        compile_wrapping(&source, &rules)[33000..],
+        [0x02, 0xD0, 0x7F, 0x18, 0x01]
+    );
+}
+
+#[test]
+fn located_range_errors() {
+    let diagnostics = language::compile_source(
+        ".name \"\"\n.comment \"\"\nld 1, r1\n  ld 70000, r1\n.code 1000",
+        &RuleSet::default(),
+        &CompileOptions::default(),
+    )
+    .unwrap_err();
+
+    let spans: Vec<_> = diagnostics.into_iter().map(|d| d.span).collect();
+    assert_eq!(spans, [Some(span(4, 2..14)), Some(span(5, 0..10))]);
+}
+
+#[test]
+fn expressions() {
+    assert_eq!(
+        compile(
+            r#".name "expressions"
+.comment ""
+.equ SIZE, :end - :start
+.equ TWICE, SIZE * 2
+start: ld %TWICE, r2
+       st r2, -:start + 4
+end:   live %(7 / -2)
+"#,
+            &RuleSet::default()
+        )
+        .unwrap(),
+        [
+            0x02, 0x90, 0x00, 0x00, 0x00, 0x18, 0x02, 0x03, 0x70, 0x02, 0x00, 0x0B, 0x01, 0xFF,
+            0xFF, 0xFF, 0xFD
+        ]
+    );
+}
+
+fn compile_errors(input: &str) -> Vec<(Option<usize>, CompileError)> {
+    let champion = language::read_champion(input.as_bytes()).expect("Failed to read champion");
+    let mut out = Cursor::new(Vec::new());
+    compile_champion_exhaustive(
+        &mut out,
+        champion,
+        &RuleSet::default(),
+        &CompileOptions::default(),
+    )
+    .unwrap_err()
+    .into_iter()
+    .map(|located| (located.instruction, located.error))
+    .collect()
+}
+
+#[test]
+fn invalid_expressions() {
+    let errors = compile_errors(
+        r#".name "invalid"
+.comment ""
+.equ A, B + 1
+.equ B, A
+.equ C, 1
+.equ C, 2
+live %MISSING
+live %A
+live %(1 / (C - C))
+live %(4611686018427387904 * 2)
+ld (1 + 1) * 40000, r1
+ld %-:missing, r1
+"#,
+    );
+
+    assert_matches!(
+        &errors[..],
+        [
+            (Some(3), CompileError::DuplicateConstant(duplicate)),
+            (Some(4), CompileError::MissingConstant(missing)),
+            (Some(5), CompileError::CyclicConstant(cyclic)),
+            (Some(6), CompileError::DivisionByZero),
+            (Some(7), CompileError::ArithmeticOverflow),
+            (Some(8), CompileError::NumberOutOfRange(80000, 2)),
+            (Some(9), CompileError::MissingLabel(label)),
+        ] if duplicate == "C" && missing == "MISSING" && cyclic == "A" && label == "missing"
+    );
+}
+
+#[test]
+fn local_labels() {
+    assert_eq!(
+        compile(
+            r#".name "local"
+.comment ""
+first:  live %1
+.loop:  zjmp %:.loop
+second: zjmp %:.loop
+.loop:  zjmp %:.loop
+"#,
+            &RuleSet::default()
+        )
+        .unwrap(),
+        [0x01, 0x00, 0x00, 0x00, 0x01, 0x09, 0x00, 0x00, 0x09, 0x00, 0x03, 0x09, 0x00, 0x00]
+    );
+}
+
+#[test]
+fn anonymous_labels() {
+    assert_eq!(
+        compile(
+            r#".name "anonymous"
+.comment ""
+1:  zjmp %:1f
+    zjmp %:1b
+1:  zjmp %:1b
+    fork %:1b + 1
+"#,
+            &RuleSet::default()
+        )
+        .unwrap(),
+        [0x09, 0x00, 0x06, 0x09, 0xFF, 0xFD, 0x09, 0x00, 0x00, 0x0C, 0xFF, 0xFE]
+    );
+}
+
+#[test]
+fn invalid_labels() {
+    let errors = compile_errors(
+        r#".name "invalid"
+.comment ""
+.loop:
+.loop:
+a:  zjmp %:.missing
+    zjmp %:1b
+1:  zjmp %:1f
+"#,
+    );
+
+    assert_matches!(
+        &errors[..],
+        [
+            (Some(1), CompileError::DuplicateLabel(duplicate)),
+            (Some(3), CompileError::MissingLabel(missing)),
+            (Some(4), CompileError::MissingAnonymousLabel(backward)),
+            (Some(6), CompileError::MissingAnonymousLabel(forward)),
+        ] if duplicate == ".loop" && missing == "a.missing" && backward == "1b" && forward == "1f"
+    );
+    assert_eq!(
+        errors[3].1.to_string(),
+        "The reference ':1f' has no matching anonymous label after it"
+    );
+}
@@ -0,0 +1,179 @@
+use super::{compile, span};
+use corewa_rs::{
+    language::{
+        self,
+        compiler::{compile_champion_exhaustive, CompileError, CompileOptions, LocatedError},
+        diagnostic::{Diagnostic, Severity},
+    },
+    rules::RuleSet,
+};
+
+use std::io::Cursor;
+
+fn diagnostics(input: &str) -> Vec<Diagnostic> {
+    compile(input).expect_err("Compiled successfully")
+}
+
+#[test]
+fn every_parse_error() {
+    let diagnostics = diagnostics(
+        r#".name "errors"
+.comment "everywhere"
+    ldd %1, r2
+    live %1
+    st r1, r17 # comment
+    live %1, r1
+"#,
+    );
+
+    let spans: Vec<_> = diagnostics.iter().map(|d| d.span.clone()).collect();
+    assert_eq!(
+        spans,
+        [
+            Some(span(3, 4..7)),
+            Some(span(5, 11..14)),
+            Some(span(6, 11..12))
+        ],
+        "{:#?}",
+        diagnostics
+    );
+    assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+}
+
+#[test]
+fn assemble_errors() {
+    let diagnostics = diagnostics(
+        r#".name "errors"
+.name "again"
+    live %1
+"#,
+    );
+
+    assert_eq!(diagnostics.len(), 2, "{:#?}", diagnostics);
+    // The missing comment concerns the whole champion
+    assert_eq!(diagnostics[0].span, None);
+    assert_eq!(diagnostics[1].span, Some(span(2, 0..13)));
+}
+
+#[test]
+fn every_compile_error() {
+    let diagnostics = diagnostics(
+        r#".name "labels"
+.comment "missing"
+start:
+    zjmp %:nowhere
+start:  live %:start
+    fork %:elsewhere
+"#,
+    );
+
+    let located: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.span.clone(), d.severity))
+        .collect();
+    assert_eq!(
+        located,
+        [
+            (Some(span(4, 4..18)), Severity::Error),
+            (Some(span(5, 0..6)), Severity::Error),
+            (Some(span(6, 4..20)), Severity::Error),
+        ],
+        "{:#?}",
+        diagnostics
+    );
+}
+
+#[test]
+fn constant_errors_point_to_definitions() {
+    let diagnostics = diagnostics(
+        r#".name "constants"
+.comment "broken"
+.equ X, 1 / 0
+.equ Y, X + 1
+    live %Y
+    live %X
+    live %(4611686018427387904 * 2)
+"#,
+    );
+
+    let located: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.span.clone(), d.constant_definitions.clone()))
+        .collect();
+    assert_eq!(
+        located,
+        [
+            (Some(span(5, 4..11)), vec![span(3, 0..13), span(4, 0..13)]),
+            (Some(span(6, 4..11)), vec![span(3, 0..13)]),
+            (Some(span(7, 4..35)), Vec::new()),
+        ],
+        "{:#?}",
+        diagnostics
+    );
+    assert_eq!(
+        diagnostics[0].to_string().lines().collect::<Vec<_>>(),
+        [
+            "error: line 5, columns 4..11: Division by zero",
+            "  in the constant defined on line 3, columns 0..13",
+            "  in the constant defined on line 4, columns 0..13",
+        ]
+    );
+}
+
+#[test]
+fn exhaustive_compilation() {
+    let champion = language::read_champion(
+        r#".name "labels"
+.comment "missing"
+    zjmp %:first
+    ld :second, r1
+"#
+        .as_bytes(),
+    )
+    .unwrap();
+
+    let errors = compile_champion_exhaustive(
+        Cursor::new(Vec::new()),
+        champion,
+        &RuleSet::default(),
+        &CompileOptions::default(),
+    )
+    .unwrap_err();
+    assert_matches!(
+        &errors[..],
+        [
+            LocatedError {
+                instruction: Some(0),
+                error: CompileError::MissingLabel(first),
+                ..
+            },
+            LocatedError {
+                instruction: Some(1),
+                error: CompileError::MissingLabel(second),
+                ..
+            },
+        ] if first == "first" && second == "second"
+    );
+}
+
+#[test]
+fn warnings_on_success() {
+    let compilation = language::compile_source(
+        r#".name "warnings"
+.comment ""
+unused: live %1
+"#,
+        &RuleSet::default(),
+        &CompileOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        compilation.warnings,
+        [Diagnostic::warning(
+            Some(span(3, 0..7)),
+            "The label 'unused' is never used"
+        )]
+    );
+    assert_eq!(compilation.name, "warnings");
+}
@@ -0,0 +1,157 @@
+use super::write;
+use corewa_rs::{
+    language::{self, disassembler::DisassembleError},
+    rules::RuleSet,
+    spec::HEADER_SIZE,
+    vm::loader::HeaderField,
+};
+
+fn compile(input: &str, rules: &RuleSet) -> Vec<u8> {
+    write(input, rules).expect("Failed to write champion")
+}
+
+fn assert_round_trip(champion: &[u8], rules: &RuleSet) {
+    let source = language::disassemble(champion, rules).unwrap();
+    assert_eq!(compile(&source, rules), champion, "source:\n{}", source);
+}
+
+#[test]
+fn samples_round_trip() {
+    let samples: [&[u8]; 7] = [
+        include_bytes!("../vm/samples/bigzork.cor"),
+        include_bytes!("../vm/samples/justin_bee.cor"),
+        include_bytes!("../vm/samples/kappa.cor"),
+        include_bytes!("../vm/samples/mise_a_jour_windows95.cor"),
+        include_bytes!("../vm/samples/skynet.cor"),
+        include_bytes!("../vm/samples/sweepmaster.cor"),
+        include_bytes!("../vm/samples/thunder.cor"),
+    ];
+
+    for sample in &samples {
+        assert_round_trip(sample, &RuleSet::default());
+    }
+}
+
+#[test]
+fn labels_and_raw_code() {
+    let champion = compile(
+        r#"
+.name "raw"
+.comment "bytes"
+start:
+    live %-1
+    .code 0x00 0xff
+    fork %:end
+    zjmp %:start
+    zjmp %12
+end:
+"#,
+        &RuleSet::default(),
+    );
+
+    assert_eq!(
+        language::disassemble(&champion, &RuleSet::default()).unwrap(),
+        r#".name "raw"
+.comment "bytes"
+
+label_0:
+	live %-1
+	.code 0x00 0xff
+	fork %:label_16
+	zjmp %:label_0
+	zjmp %12
+label_16:
+"#
+    );
+    assert_round_trip(&champion, &RuleSet::default());
+}
+
+#[test]
+fn truncated_instruction() {
+    let champion = compile(
+        r#"
+.name "truncated"
+.comment ""
+    ld %1, r2
+    .code 0x0b 0x68 0x01
+"#,
+        &RuleSet::default(),
+    );
+
+    let source = language::disassemble(&champion, &RuleSet::default()).unwrap();
+    assert!(source.ends_with("\tld %1, r2\n\t.code 0x0b 0x68 0x01\n"));
+    assert_round_trip(&champion, &RuleSet::default());
+}
+
+#[test]
+fn custom_rules() {
+    let rules = "sti.dir_size = 4\nzjmp.dir_size = 4".parse().unwrap();
+    let champion = compile(
+        r#"
+.name "sti"
+.comment "sti"
+loop:
+    sti r1, %1, %2
+    zjmp %:loop
+"#,
+        &rules,
+    );
+
+    let source = language::disassemble(&champion, &rules).unwrap();
+    assert!(source.contains("\tsti r1, %1, %2\n\tzjmp %:label_0\n"));
+    assert_round_trip(&champion, &rules);
+}
+
+#[test]
+fn invalid_headers() {
+    let champion = compile(
+        ".name \"zork\"\n.comment \"\"\nlive %1",
+        &RuleSet::default(),
+    );
+
+    assert_matches!(
+        language::disassemble(&champion[..HEADER_SIZE - 1], &RuleSet::default()),
+        Err(DisassembleError::MissingHeader(_))
+    );
+
+    let mut bad_magic = champion.clone();
+    bad_magic[0] = 0xFF;
+    assert_matches!(
+        language::disassemble(&bad_magic, &RuleSet::default()),
+        Err(DisassembleError::InvalidMagic(_))
+    );
+
+    assert_matches!(
+        language::disassemble(&champion[..champion.len() - 1], &RuleSet::default()),
+        Err(DisassembleError::SizeMismatch {
+            declared: 5,
+            actual: 4
+        })
+    );
+
+    let mut quoted_name = champion.clone();
+    quoted_name[4] = b'"';
+    assert_matches!(
+        language::disassemble(&quoted_name, &RuleSet::default()),
+        Err(DisassembleError::UnwritableString(HeaderField::Name))
+    );
+
+    // Header written with the padded layout by another assembler
+    assert_matches!(
+        language::disassemble(
+            include_bytes!("../vm/samples/zork.cor"),
+            &RuleSet::default()
+        ),
+        Err(DisassembleError::SizeMismatch {
+            declared: 0,
+            actual: 29
+        })
+    );
+
+    let mut trailing_comment = champion.clone();
+    trailing_comment[HEADER_SIZE - 1] = b'x';
+    assert_matches!(
+        language::disassemble(&trailing_comment, &RuleSet::default()),
+        Err(DisassembleError::TrailingData(HeaderField::Comment))
+    );
+}
@@ -0,0 +1,149 @@
+use super::{compile, compile_with_files, span, span_in};
+use corewa_rs::{
+    language::{
+        self,
+        compiler::CompileOptions,
+        include::{inline_includes, IncludeErrorKind, NoFiles},
+        ReadError,
+    },
+    rules::RuleSet,
+};
+
+use std::{collections::HashMap, path::PathBuf};
+
+fn files(files: &[(&str, &str)]) -> HashMap<PathBuf, String> {
+    files
+        .iter()
+        .map(|(path, content)| (PathBuf::from(path), String::from(*content)))
+        .collect()
+}
+
+#[test]
+fn included_lines_are_inlined() {
+    let files = files(&[("lib.s", ".macro alive\n    live %1\n.endm\n")]);
+
+    let included = compile_with_files(
+        ".name \"a\"\n.comment \"b\"\n.include \"lib.s\"\n    alive\n",
+        &files,
+    );
+    let written = compile(".name \"a\"\n.comment \"b\"\n    live %1\n");
+
+    assert_eq!(included, written);
+}
+
+#[test]
+fn paths_are_relative_to_the_including_file() {
+    let files = files(&[
+        (
+            "lib/all.s",
+            ".include \"./ops/live.s\"\n.include \"../top.s\"\n",
+        ),
+        ("lib/ops/live.s", "    live %1\n"),
+        ("top.s", "    aff r1\n"),
+    ]);
+
+    let lines: Vec<_> = inline_includes(".include \"lib/all.s\"\n", None, &files)
+        .into_iter()
+        .map(|line| {
+            let line = line.expect("Failed to include");
+            (line.file.map(|file| file.to_string()), line.text)
+        })
+        .collect();
+
+    assert_eq!(
+        lines,
+        [
+            (
+                Some(String::from("lib/ops/live.s")),
+                String::from("    live %1")
+            ),
+            (Some(String::from("top.s")), String::from("    aff r1")),
+        ]
+    );
+}
+
+#[test]
+fn include_cycles() {
+    let files = files(&[
+        ("a.s", ".include \"b.s\"\n"),
+        ("b.s", "    live %1\n.include \"./a.s\"\n"),
+    ]);
+
+    let results = inline_includes(".include \"a.s\"\n", None, &files);
+
+    assert_eq!(results.len(), 2);
+    assert_matches!(
+        &results[1],
+        Err(e) if matches!(&e.kind, IncludeErrorKind::Cycle(name) if name == "a.s")
+            && e.span == span_in("b.s", 2, 0..16)
+    );
+}
+
+#[test]
+fn including_the_main_source() {
+    let files = files(&[("lib/main.s", "")]);
+
+    let results = inline_includes(".include \"main.s\"\n", Some("lib/main.s".as_ref()), &files);
+
+    assert_matches!(
+        results.as_slice(),
+        [Err(e)] if matches!(e.kind, IncludeErrorKind::Cycle(_))
+    );
+}
+
+#[test]
+fn include_errors() {
+    let diagnostics = compile_with_files(
+        ".name \"a\"\n.comment \"b\"\n.include \"missing.s\"\n.include lib.s\n.include \"lib.s\"\n",
+        &files(&[("lib.s", "    live %1\n    lfork r1\n")]),
+    )
+    .unwrap_err();
+
+    let spans: Vec<_> = diagnostics.iter().map(|d| d.span.clone()).collect();
+    assert_eq!(
+        spans,
+        [
+            Some(span(3, 0..20)),
+            Some(span(4, 9..12)),
+            Some(span_in("lib.s", 2, 10..12)),
+        ],
+        "{:#?}",
+        diagnostics
+    );
+    assert!(diagnostics[0].message.contains("missing.s"));
+}
+
+#[test]
+fn files_cannot_be_included_by_default() {
+    let diagnostics = language::compile_source(
+        ".name \"a\"\n.comment \"b\"\n.include \"lib.s\"\n",
+        &RuleSet::default(),
+        &CompileOptions::default(),
+    )
+    .unwrap_err();
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].span, Some(span(3, 0..16)));
+}
+
+#[test]
+fn read_champion_reports_the_file() {
+    let files = files(&[("lib.s", "\n    live\n")]);
+
+    let result = language::read_champion_with_includes(
+        ".name \"a\"\n.comment \"b\"\n.include \"lib.s\"\n".as_bytes(),
+        None,
+        &files,
+    );
+
+    assert_matches!(&result, Err(ReadError::ParseError(_, span)) if span.file.as_deref() == Some("lib.s") && span.line == 2);
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .starts_with("Parse error on lib.s: line 2"));
+
+    assert_matches!(
+        language::read_champion_with_includes(".include \"lib.s\"\n".as_bytes(), None, &NoFiles),
+        Err(ReadError::IncludeError(_))
+    );
+}
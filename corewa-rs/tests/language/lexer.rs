@@ -79,6 +79,41 @@ fn label_use() {
     );
 }
 
+#[test]
+fn local_labels() {
+    assert_eq!(
+        tokens_ok(".loop: zjmp %:.loop"),
+        [
+            LabelDef.at(0..6),
+            Ident.at(7..11),
+            DirectChar.at(12..13),
+            LabelUse.at(13..19)
+        ]
+    );
+    assert_eq!(tokens(":."), [Err(EmptyLabel.at(0..1))]);
+}
+
+#[test]
+fn anonymous_labels() {
+    assert_eq!(
+        tokens_ok("1: ld :1b, :12f"),
+        [
+            LabelDef.at(0..2),
+            Ident.at(3..5),
+            LabelUse.at(6..9),
+            ParamSeparator.at(9..10),
+            LabelUse.at(11..15)
+        ]
+    );
+    assert_eq!(
+        tokens("0x1:"),
+        [
+            Ok(Number { base: Hexadecimal }.at(0..3)),
+            Err(EmptyLabel.at(3..4))
+        ]
+    );
+}
+
 #[test]
 fn direct() {
     assert_eq!(
@@ -91,6 +126,42 @@ fn direct() {
     );
 }
 
+#[test]
+fn constant_directives() {
+    assert_eq!(
+        tokens_ok(".equ SIZE, 4"),
+        [
+            ConstantCmd.at(0..4),
+            Ident.at(5..9),
+            ParamSeparator.at(9..10),
+            Number { base: Decimal }.at(11..12)
+        ]
+    );
+    assert_eq!(tokens(".set A, 1")[0], Err(InvalidDirective.at(0..4)));
+}
+
+#[test]
+fn operators() {
+    assert_eq!(
+        tokens_ok("%(:end - :start)*2/-4+-x"),
+        [
+            DirectChar.at(0..1),
+            OpenParen.at(1..2),
+            LabelUse.at(2..6),
+            Minus.at(7..8),
+            LabelUse.at(9..15),
+            CloseParen.at(15..16),
+            Star.at(16..17),
+            Number { base: Decimal }.at(17..18),
+            Slash.at(18..19),
+            Number { base: Decimal }.at(19..21),
+            Plus.at(21..22),
+            Minus.at(22..23),
+            Ident.at(23..24),
+        ]
+    );
+}
+
 #[test]
 fn invalid_directive() {
     assert_eq!(tokens(".foo"), [Err(InvalidDirective.at(0..4))])
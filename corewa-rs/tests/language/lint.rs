@@ -0,0 +1,200 @@
+use corewa_rs::{
+    language::{self, compiler::CompileOptions, diagnostic::Severity, lint::lint},
+    rules::RuleSet,
+};
+
+fn lint_source(input: &str, rules: &RuleSet) -> Vec<(usize, String)> {
+    let champion = language::read_champion(input.as_bytes()).expect("Failed to read champion");
+    let options = CompileOptions { wrap_numbers: true };
+    lint(&champion, rules, &options)
+        .into_iter()
+        .map(|warning| {
+            assert_eq!(warning.severity, Severity::Warning);
+            (warning.span.expect("Missing span").line, warning.message)
+        })
+        .collect()
+}
+
+fn lint_lines(input: &str) -> Vec<usize> {
+    lint_source(input, &RuleSet::default())
+        .into_iter()
+        .map(|(line, _)| line)
+        .collect()
+}
+
+#[test]
+fn unused_labels() {
+    let warnings = lint_source(
+        r#".name "labels"
+.comment ""
+used:
+unused: ld :used, r1
+"#,
+        &RuleSet::default(),
+    );
+
+    assert_eq!(
+        warnings,
+        [(4, String::from("The label 'unused' is never used"))]
+    );
+}
+
+#[test]
+fn scoped_labels() {
+    let warnings = lint_source(
+        r#".name "labels"
+.comment ""
+main:   zjmp %:.loop
+.loop:  live %1
+other:  zjmp %:main
+.loop:  live %1
+1:      zjmp %:1b
+2:      live %1
+"#,
+        &RuleSet::default(),
+    );
+
+    assert_eq!(
+        warnings,
+        [
+            (5, String::from("The label 'other' is never used")),
+            (6, String::from("The label 'other.loop' is never used"))
+        ]
+    );
+}
+
+#[test]
+fn unused_constants() {
+    let warnings = lint_source(
+        r#".name "constants"
+.comment ""
+.equ SIZE, :end - :start
+.equ UNUSED, SIZE
+.equ USED, 2
+start: ld %USED * 2, r1
+end:
+"#,
+        &RuleSet::default(),
+    );
+
+    assert_eq!(
+        warnings,
+        [(4, String::from("The constant 'UNUSED' is never used"))]
+    );
+}
+
+#[test]
+fn unreachable_code() {
+    let lines = lint_lines(
+        r#".name "unreachable"
+.comment ""
+loop:
+    ld %0, r2
+    st r1, 10
+    zjmp %:loop
+    live %1
+    live %1
+reachable:
+    live %1
+    xor r3, r3, r4
+    zjmp %:reachable
+    .code 0 0
+    add r1, r2, r3
+"#,
+    );
+
+    assert_eq!(lines, [7, 14]);
+}
+
+#[test]
+fn conditional_jumps() {
+    let lines = lint_lines(
+        r#".name "conditional"
+.comment ""
+loop:
+    ld %1, r2
+    zjmp %:loop
+    and r2, %1, r3
+    zjmp %:loop
+    sub r1, r2, r3
+    zjmp %:loop
+    live %1
+"#,
+    );
+
+    assert!(lines.is_empty(), "{:?}", lines);
+}
+
+#[test]
+fn ldi_semantics() {
+    let source = r#".name "ldi"
+.comment ""
+loop:
+    ld %0, r2
+    ldi %1, %2, r3
+    zjmp %:loop
+    live %1
+"#;
+
+    assert_eq!(lint_lines(source), [7]);
+    assert!(lint_source(source, &RuleSet::compat()).is_empty());
+}
+
+#[test]
+fn truncated_code_bytes() {
+    let warnings = lint_source(
+        ".name \"code\"\n.comment \"\"\n.code 255 -128 256 -129",
+        &RuleSet::default(),
+    );
+
+    assert_eq!(
+        warnings,
+        [
+            (
+                3,
+                String::from("The .code value 256 does not fit on a byte and is truncated to 0")
+            ),
+            (
+                3,
+                String::from("The .code value -129 does not fit on a byte and is truncated to 127")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn overflowing_literals() {
+    let source = r#".name "overflow"
+.comment ""
+    ld %4294967295, r1
+    ld %4294967296, r1
+    ld 65535, r1
+    ld -32769, r1
+    sti r1, %65536, %-32768
+"#;
+
+    assert_eq!(lint_lines(source), [4, 6, 7]);
+
+    let rules = "sti.dir_size = 4".parse().unwrap();
+    let lines: Vec<_> = lint_source(source, &rules)
+        .into_iter()
+        .map(|(line, _)| line)
+        .collect();
+    assert_eq!(lines, [4, 6]);
+}
+
+#[test]
+fn implausible_live_ids() {
+    let lines = lint_lines(
+        r#".name "live"
+.comment ""
+id: live %1
+    live %-4
+    live %0
+    live %5
+    live %:id
+"#,
+    );
+
+    assert_eq!(lines, [5, 6]);
+}
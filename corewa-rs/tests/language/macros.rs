@@ -0,0 +1,264 @@
+use super::{compile, span};
+use corewa_rs::language::{
+    self,
+    diagnostic::Span,
+    include::SourceLine,
+    macros::{MacroErrorKind, MacroExpander},
+    ReadError,
+};
+
+fn source_lines(input: &str) -> impl Iterator<Item = SourceLine> + '_ {
+    input.lines().zip(1..).map(|(text, line)| SourceLine {
+        file: None,
+        line,
+        text: String::from(text),
+    })
+}
+
+fn expanded_texts(input: &str) -> Vec<String> {
+    let mut expander = MacroExpander::default();
+    let mut texts = Vec::new();
+
+    for line in source_lines(input) {
+        texts.extend(
+            expander
+                .expand(&line)
+                .expect("Failed to expand")
+                .into_iter()
+                .map(|line| line.text),
+        );
+    }
+    expander.finish().expect("Unclosed macro");
+
+    texts
+}
+
+#[test]
+fn parameter_substitution() {
+    assert_eq!(
+        expanded_texts(
+            r#".macro spawn reg, offset
+    sti reg, %offset, %0   # reg is not replaced in comments
+    fork %offset
+.endm
+spawn r2, :start + 4
+"#
+        ),
+        [
+            "    sti r2, %:start + 4, %0   # reg is not replaced in comments",
+            "    fork %:start + 4"
+        ]
+    );
+}
+
+#[test]
+fn same_code_as_written_by_hand() {
+    let with_macros = compile(
+        r#".name "macros"
+.comment ""
+.macro alive id
+    live %id
+.endm
+.macro spawn reg, target
+    alive 1
+    sti reg, %target, %0
+.endm
+start: spawn r1, :start
+       spawn r2, 42
+"#,
+    )
+    .unwrap();
+    let by_hand = compile(
+        r#".name "macros"
+.comment ""
+start: live %1
+    sti r1, %:start, %0
+    live %1
+    sti r2, %42, %0
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(with_macros, by_hand);
+}
+
+#[test]
+fn anonymous_labels_in_expansions() {
+    let texts = expanded_texts(
+        r#".macro wait
+1:  live %1
+    zjmp %:1b
+.endm
+1: wait
+"#,
+    );
+    assert_eq!(texts, ["1:", "1:  live %1", "    zjmp %:1b"]);
+
+    let with_macros = compile(
+        ".name \"\"\n.comment \"\"\n.macro wait\n1: live %1\nzjmp %:1b\n.endm\nwait\nwait\n",
+    );
+    let by_hand = compile(".name \"\"\n.comment \"\"\nlive %1\nzjmp %-5\nlive %1\nzjmp %-5\n");
+    assert_eq!(with_macros.unwrap(), by_hand.unwrap());
+}
+
+#[test]
+fn labels_are_local_to_expansions() {
+    let texts = expanded_texts(
+        r#".macro wait
+loop: live %1
+    zjmp %:loop
+    ld %:outside, r1
+.endm
+outside: wait
+wait
+"#,
+    );
+    assert_eq!(
+        texts,
+        [
+            "outside:",
+            "loop__1: live %1",
+            "    zjmp %:loop__1",
+            "    ld %:outside, r1",
+            "loop__2: live %1",
+            "    zjmp %:loop__2",
+            "    ld %:outside, r1",
+        ]
+    );
+
+    let source = format!(".name \"\"\n.comment \"\"\n{}", texts.join("\n"));
+    assert!(compile(&source).is_ok());
+}
+
+#[test]
+fn diagnostics_point_to_definitions_and_call_sites() {
+    let diagnostics = compile(
+        r#".name "errors"
+.comment ""
+.macro inner value
+    live %value
+    ld value, r17
+.endm
+.macro outer
+    inner 1
+.endm
+    inner %2
+    outer
+"#,
+    )
+    .unwrap_err();
+
+    let locations: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.span.clone().unwrap(), d.call_sites.clone()))
+        .collect();
+    assert_eq!(
+        locations,
+        [
+            // %%2 in the first expansion, on the parameter
+            (span(4, 10..15), vec![span(10, 4..12)]),
+            (span(5, 14..17), vec![span(10, 4..12)]),
+            (span(5, 14..17), vec![span(8, 4..11), span(11, 4..9)]),
+        ],
+        "{:#?}",
+        diagnostics
+    );
+    assert_eq!(
+        diagnostics[2].to_string().lines().collect::<Vec<_>>(),
+        [
+            "error: line 5, columns 14..17: '17' is not a valid register number. It must be between 1 and 16",
+            "  in the macro invoked on line 8, columns 4..11",
+            "  in the macro invoked on line 11, columns 4..9",
+        ]
+    );
+}
+
+#[test]
+fn compile_errors_and_warnings_have_call_sites() {
+    let diagnostics = compile(
+        r#".name "errors"
+.comment ""
+.macro jump
+unused: zjmp %:missing
+.endm
+jump
+"#,
+    )
+    .unwrap_err();
+
+    assert_eq!(diagnostics.len(), 2, "{:#?}", diagnostics);
+    for diagnostic in &diagnostics {
+        assert_eq!(diagnostic.call_sites, [span(6, 0..4)]);
+    }
+    assert_eq!(diagnostics[0].span, Some(span(4, 0..7)));
+    assert_eq!(diagnostics[1].span, Some(span(4, 8..22)));
+}
+
+fn macro_errors(input: &str) -> Vec<(MacroErrorKind, Span)> {
+    let mut expander = MacroExpander::default();
+    let mut errors = Vec::new();
+
+    for line in source_lines(input) {
+        if let Err(e) = expander.expand(&line) {
+            errors.push((e.kind, e.span));
+        }
+    }
+    if let Err(e) = expander.finish() {
+        errors.push((e.kind, e.span));
+    }
+
+    errors
+}
+
+#[test]
+fn invalid_macros() {
+    let errors = macro_errors(
+        r#".endm
+.macro ld
+.endm
+.macro twice a, a
+.endm
+.macro once a
+  .macro nested
+.endm
+.macro once
+.endm
+once
+once 1, 2
+.macro forever
+  forever
+.endm
+forever
+.macro
+.endm
+.macro unclosed
+"#,
+    );
+
+    assert_matches!(
+        &errors[..],
+        [
+            (MacroErrorKind::UnexpectedEnd, Span { line: 1, .. }),
+            (MacroErrorKind::ReservedName(reserved), Span { line: 2, .. }),
+            (MacroErrorKind::DuplicateParameter(param), Span { line: 4, .. }),
+            (MacroErrorKind::NestedDefinition, Span { line: 7, .. }),
+            (MacroErrorKind::DuplicateMacro(duplicate), Span { line: 9, .. }),
+            (MacroErrorKind::ArgumentCount { expected: 1, actual: 0, .. }, Span { line: 11, .. }),
+            (MacroErrorKind::ArgumentCount { expected: 1, actual: 2, .. }, Span { line: 12, .. }),
+            (MacroErrorKind::RecursiveMacro(recursive), Span { line: 14, .. }),
+            (MacroErrorKind::InvalidDefinition(_), Span { line: 17, .. }),
+            (MacroErrorKind::UnclosedMacro, Span { line: 19, columns, .. }),
+        ] if reserved == "ld"
+            && param == "a"
+            && duplicate == "once"
+            && recursive == "forever"
+            && *columns == (0..15)
+    );
+}
+
+#[test]
+fn read_champion_reports_macro_errors() {
+    let result = language::read_champion(".name \"\"\n.comment \"\"\n.endm\n".as_bytes());
+
+    assert_matches!(result, Err(ReadError::MacroError(e)) if e.span.line == 3);
+}
@@ -4,6 +4,70 @@ macro_rules! sample {
     };
 }
 
+use corewa_rs::{
+    language::{
+        self,
+        compiler::CompileOptions,
+        diagnostic::{Diagnostic, Span},
+        include::{FileResolver, NoFiles},
+        WriteError,
+    },
+    rules::RuleSet,
+};
+use std::{ops::Range, rc::Rc};
+
+/// Compiles `input` with the default rules, returning its code
+pub(crate) fn compile(input: &str) -> Result<Vec<u8>, Vec<Diagnostic>> {
+    compile_with_files(input, &NoFiles)
+}
+
+/// Same as [`compile`], the files included by `input` being read by `resolver`
+pub(crate) fn compile_with_files(
+    input: &str,
+    resolver: &dyn FileResolver,
+) -> Result<Vec<u8>, Vec<Diagnostic>> {
+    language::compile_with_includes(
+        input,
+        None,
+        resolver,
+        &RuleSet::default(),
+        &CompileOptions::default(),
+    )
+    .map(|compilation| compilation.byte_code)
+}
+
+/// Reads `input` then writes it as a `.cor` file, header included
+pub(crate) fn write(input: &str, rules: &RuleSet) -> Result<Vec<u8>, WriteError> {
+    let champion = language::read_champion(input.as_bytes()).expect("Failed to read champion");
+    let mut out = Vec::new();
+    language::write_champion(&mut out, champion, rules)?;
+    Ok(out)
+}
+
+/// A span of the main source
+pub(crate) fn span(line: usize, columns: Range<usize>) -> Span {
+    Span {
+        file: None,
+        line,
+        columns,
+    }
+}
+
+/// A span of an included file
+pub(crate) fn span_in(file: &str, line: usize, columns: Range<usize>) -> Span {
+    Span {
+        file: Some(Rc::from(file)),
+        ..span(line, columns)
+    }
+}
+
 mod assembler;
+mod compiler;
+mod diagnostics;
+mod disassembler;
+mod include;
 mod lexer;
+mod lint;
+mod macros;
 mod parser;
+mod round_trip;
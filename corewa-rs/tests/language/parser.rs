@@ -214,6 +214,162 @@ mod op {
     }
 }
 
+mod expression {
+    use super::*;
+    use corewa_rs::language::lexer::NumberBase::*;
+
+    fn num(n: i64) -> Box<Expr> {
+        Box::new(Expr::Number(n))
+    }
+
+    fn label(name: &str) -> Box<Expr> {
+        Box::new(Expr::Label(name.into()))
+    }
+
+    fn constant(name: &str) -> Box<Expr> {
+        Box::new(Expr::Constant(name.into()))
+    }
+
+    fn live_param(input: &str) -> Direct {
+        match parse_ok(&format!("live %{}", input)) {
+            Op(Live(dir)) => dir,
+            parsed => panic!("Unexpected parse result {:?}", parsed),
+        }
+    }
+
+    #[test]
+    fn simple_operands() {
+        assert_eq!(live_param("(42)"), Direct::Numeric(42));
+        assert_eq!(live_param("((:loop))"), Direct::Label("loop".into()));
+        assert_eq!(live_param("SIZE"), Direct::Expr(*constant("SIZE")));
+    }
+
+    #[test]
+    fn precedence() {
+        use BinaryOp::*;
+
+        assert_eq!(
+            live_param("1 + 2 * 3 - 4 / 5"),
+            Direct::Expr(Expr::Binary(
+                Sub,
+                Box::new(Expr::Binary(
+                    Add,
+                    num(1),
+                    Box::new(Expr::Binary(Mul, num(2), num(3)))
+                )),
+                Box::new(Expr::Binary(Div, num(4), num(5)))
+            ))
+        );
+        assert_eq!(
+            live_param("(1 + 2) * SIZE"),
+            Direct::Expr(Expr::Binary(
+                Mul,
+                Box::new(Expr::Binary(Add, num(1), num(2))),
+                constant("SIZE")
+            ))
+        );
+    }
+
+    #[test]
+    fn minus_signs() {
+        use BinaryOp::*;
+
+        assert_eq!(live_param("-:loop"), Direct::Expr(Expr::Neg(label("loop"))));
+        assert_eq!(
+            live_param(":end - :start + 4"),
+            Direct::Expr(Expr::Binary(
+                Add,
+                Box::new(Expr::Binary(Sub, label("end"), label("start"))),
+                num(4)
+            ))
+        );
+        assert_eq!(
+            live_param(":end -4"),
+            Direct::Expr(Expr::Binary(Add, label("end"), num(-4)))
+        );
+        assert_eq!(
+            live_param(":end -4*2"),
+            Direct::Expr(Expr::Binary(
+                Add,
+                label("end"),
+                Box::new(Expr::Binary(Mul, num(-4), num(2)))
+            ))
+        );
+        assert_eq!(
+            live_param("SIZE -2*3"),
+            Direct::Expr(Expr::Binary(
+                Add,
+                constant("SIZE"),
+                Box::new(Expr::Binary(Mul, num(-2), num(3)))
+            ))
+        );
+        assert_eq!(live_param("--1"), Direct::Expr(Expr::Neg(num(-1))));
+    }
+
+    #[test]
+    fn indirect_expressions() {
+        parse_test(
+            "st r1, :end - 2",
+            Op(St(
+                Register(1),
+                Indirect::Expr(Expr::Binary(BinaryOp::Sub, label("end"), num(2))).into(),
+            )),
+        );
+        parse_test(
+            "and OFFSET, %MASK, r2",
+            Op(And(
+                Indirect::Expr(*constant("OFFSET")).into(),
+                Direct::Expr(*constant("MASK")).into(),
+                Register(2),
+            )),
+        );
+    }
+
+    #[test]
+    fn constant_definition() {
+        parse_test(".equ SIZE, 4", Constant("SIZE".into(), Expr::Number(4)));
+        parse_test(
+            ".equ LENGTH, :end - :start # comment",
+            Constant(
+                "LENGTH".into(),
+                Expr::Binary(BinaryOp::Sub, label("end"), label("start")),
+            ),
+        );
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        let unclosed = parse_line("live %(1 + 2").unwrap_err().to_string();
+        assert!(
+            unclosed.contains("Expected 'Closing parenthesis' before the end of the line"),
+            "{}",
+            unclosed
+        );
+        parse_expect_err(".equ r4, 4", RegisterAsConstant(Ident.at(5..7)));
+        parse_expect_err(
+            "live %1 +",
+            ExpectedOneOf(vec![
+                ExpectedOneOf(vec![
+                    ExpectedButGotEof(Number { base: Decimal }),
+                    ExpectedButGotEof(Number { base: Hexadecimal }),
+                ]),
+                ExpectedButGotEof(LabelUse),
+                ExpectedButGotEof(Ident),
+                ExpectedButGotEof(OpenParen),
+                ExpectedButGotEof(Minus),
+            ]),
+        );
+        parse_expect_err(
+            ".equ 4, 4",
+            ExpectedButGot(Ident, Number { base: Decimal }.at(5..6)),
+        );
+        parse_expect_err(
+            ".equ SIZE 4",
+            ExpectedButGot(ParamSeparator, Number { base: Decimal }.at(10..11)),
+        );
+    }
+}
+
 #[test]
 fn label() {
     parse_test("loop:", Label("loop".into()))
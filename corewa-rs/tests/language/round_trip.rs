@@ -0,0 +1,177 @@
+use corewa_rs::{
+    language::{
+        self,
+        assembler::{Champion, ParsedInstruction},
+        compiler::{compile_champion_exhaustive, CompileOptions},
+        types::*,
+    },
+    rules::RuleSet,
+    spec::{DirectSize, OP_TYPES},
+};
+
+use proptest::{collection::vec, prelude::*};
+use std::io::Cursor;
+
+const LABEL_COUNT: usize = 4;
+
+/// Numbers wrap so that random literals compile, and get truncated the same
+/// way on both sides
+fn compile(champion: Champion, rules: &RuleSet) -> Vec<u8> {
+    let mut out = Cursor::new(Vec::new());
+    let options = CompileOptions { wrap_numbers: true };
+    compile_champion_exhaustive(&mut out, champion, rules, &options)
+        .expect("Failed to compile champion");
+    out.into_inner()
+}
+
+fn reassemble(compiled: &[u8], rules: &RuleSet) -> Vec<u8> {
+    let source = language::disassemble(compiled, rules).expect("Failed to disassemble");
+    let champion = language::read_champion(source.as_bytes()).expect("Failed to read source");
+    compile(champion, rules)
+}
+
+fn label() -> impl Strategy<Value = String> {
+    (0..LABEL_COUNT).prop_map(|idx| format!("l{}", idx))
+}
+
+fn register() -> impl Strategy<Value = Register> {
+    (1u8..=16).prop_map(Register)
+}
+
+fn direct() -> impl Strategy<Value = Direct> {
+    prop_oneof![
+        any::<i64>().prop_map(Direct::Numeric),
+        (-0x1_0000i64..0x1_0000).prop_map(Direct::Numeric),
+        label().prop_map(Direct::Label),
+    ]
+}
+
+fn indirect() -> impl Strategy<Value = Indirect> {
+    prop_oneof![
+        any::<i64>().prop_map(Indirect::Numeric),
+        (-0x1_0000i64..0x1_0000).prop_map(Indirect::Numeric),
+        label().prop_map(Indirect::Label),
+    ]
+}
+
+fn reg_dir() -> impl Strategy<Value = RegDir> {
+    prop_oneof![
+        register().prop_map(RegDir::Reg),
+        direct().prop_map(RegDir::Dir)
+    ]
+}
+
+fn reg_ind() -> impl Strategy<Value = RegInd> {
+    prop_oneof![
+        register().prop_map(RegInd::Reg),
+        indirect().prop_map(RegInd::Ind)
+    ]
+}
+
+fn dir_ind() -> impl Strategy<Value = DirInd> {
+    prop_oneof![
+        direct().prop_map(DirInd::Dir),
+        indirect().prop_map(DirInd::Ind)
+    ]
+}
+
+fn any_param() -> impl Strategy<Value = AnyParam> {
+    prop_oneof![
+        register().prop_map(AnyParam::Reg),
+        direct().prop_map(AnyParam::Dir),
+        indirect().prop_map(AnyParam::Ind),
+    ]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        direct().prop_map(Op::Live),
+        (dir_ind(), register()).prop_map(|(di, r)| Op::Ld(di, r)),
+        (register(), reg_ind()).prop_map(|(r, ri)| Op::St(r, ri)),
+        (register(), register(), register()).prop_map(|(r1, r2, r3)| Op::Add(r1, r2, r3)),
+        (register(), register(), register()).prop_map(|(r1, r2, r3)| Op::Sub(r1, r2, r3)),
+        (any_param(), any_param(), register()).prop_map(|(a1, a2, r)| Op::And(a1, a2, r)),
+        (any_param(), any_param(), register()).prop_map(|(a1, a2, r)| Op::Or(a1, a2, r)),
+        (any_param(), any_param(), register()).prop_map(|(a1, a2, r)| Op::Xor(a1, a2, r)),
+        direct().prop_map(Op::Zjmp),
+        (any_param(), reg_dir(), register()).prop_map(|(a, rd, r)| Op::Ldi(a, rd, r)),
+        (register(), any_param(), reg_dir()).prop_map(|(r, a, rd)| Op::Sti(r, a, rd)),
+        direct().prop_map(Op::Fork),
+        (dir_ind(), register()).prop_map(|(di, r)| Op::Lld(di, r)),
+        (any_param(), reg_dir(), register()).prop_map(|(a, rd, r)| Op::Lldi(a, rd, r)),
+        direct().prop_map(Op::Lfork),
+        register().prop_map(Op::Aff),
+    ]
+}
+
+fn instruction() -> impl Strategy<Value = ParsedInstruction> {
+    prop_oneof![
+        4 => op().prop_map(ParsedInstruction::Op),
+        1 => vec(any::<u8>().prop_map(i64::from), 1..8).prop_map(ParsedInstruction::RawCode),
+    ]
+}
+
+/// Every label is declared once, at a random position of the program
+fn champion() -> impl Strategy<Value = Champion> {
+    (
+        "[a-zA-Z0-9 _#.-]{0,32}",
+        "[a-zA-Z0-9 _#.-]{0,64}",
+        vec(instruction(), 0..40),
+        vec(any::<prop::sample::Index>(), LABEL_COUNT),
+    )
+        .prop_map(|(name, comment, mut instructions, label_positions)| {
+            for (idx, position) in label_positions.into_iter().enumerate() {
+                let at = position.index(instructions.len() + 1);
+                instructions.insert(at, ParsedInstruction::Label(format!("l{}", idx)));
+            }
+            Champion {
+                name,
+                comment,
+                instructions,
+                spans: Vec::new(),
+                call_sites: Vec::new(),
+            }
+        })
+}
+
+fn rules() -> impl Strategy<Value = RuleSet> {
+    vec(any::<bool>(), OP_TYPES.len()).prop_map(|four_bytes| {
+        let mut rules = RuleSet::default();
+        for (&op_type, four_bytes) in OP_TYPES.iter().zip(four_bytes) {
+            rules.ops.get_mut(op_type).dir_size = if four_bytes {
+                DirectSize::FourBytes
+            } else {
+                DirectSize::TwoBytes
+            };
+        }
+        rules
+    })
+}
+
+/// Biased towards op codes so that some of the bytes decode as instructions
+fn code_byte() -> impl Strategy<Value = u8> {
+    prop_oneof![any::<u8>(), 0u8..=17]
+}
+
+proptest! {
+    #[test]
+    fn programs_round_trip(champion in champion(), rules in rules()) {
+        let compiled = compile(champion, &rules);
+        prop_assert_eq!(reassemble(&compiled, &rules), compiled);
+    }
+
+    #[test]
+    fn bytes_round_trip(code in vec(code_byte(), 0..256), rules in rules()) {
+        let champion = Champion {
+            name: String::from("bytes"),
+            comment: String::new(),
+            instructions: vec![ParsedInstruction::RawCode(
+                code.into_iter().map(i64::from).collect(),
+            )],
+            spans: Vec::new(),
+            call_sites: Vec::new(),
+        };
+        let compiled = compile(champion, &rules);
+        prop_assert_eq!(reassemble(&compiled, &rules), compiled);
+    }
+}
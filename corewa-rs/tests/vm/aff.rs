@@ -0,0 +1,80 @@
+use super::compile;
+use corewa_rs::vm::{
+    replay::{ReplayReader, ReplayWriter},
+    snapshot::Snapshot,
+    types::{AffChar, MAX_AFF_OUTPUT},
+    VirtualMachine,
+};
+
+const TALKER: &str = r#"
+.name "talker"
+.comment "says hi"
+ld  %72, r2
+aff r2
+ld  %-151, r2
+aff r2
+"#;
+
+const CHATTER: &str = r#"
+.name "chatter"
+.comment "never stops talking"
+ld  %65, r2
+ld  %0, r3
+loop: aff r2
+zjmp %:loop
+"#;
+
+#[test]
+fn aff_outputs_by_player() {
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, compile(TALKER)), (2, sample!(zork).to_vec())])
+        .unwrap();
+    vm.run_to_completion(Some(100));
+
+    assert_eq!(vm.aff_text(0), "Hi");
+    assert_eq!(vm.aff_text(1), "");
+    assert_eq!(
+        vm.aff_outputs[0],
+        [
+            AffChar {
+                byte: b'H',
+                cycle: 6,
+                pid: 0
+            },
+            AffChar {
+                byte: b'i',
+                cycle: 13,
+                pid: 0
+            },
+        ]
+    );
+}
+
+#[test]
+fn aff_outputs_are_bounded() {
+    let rules = "check_interval = 100000\nzjmp.cycles = 2".parse().unwrap();
+    let players = [(1, compile(CHATTER))];
+    let mut vm = VirtualMachine::with_rules(rules);
+    vm.load_players(&players).unwrap();
+
+    // Records past the point where the output is full, the last cycle not
+    // being a keyframe
+    let mut writer = ReplayWriter::new(Vec::new(), &vm, &players).unwrap();
+    while vm.cycles < 4 * MAX_AFF_OUTPUT as u32 + 1000 {
+        writer.tick(&mut vm).unwrap();
+    }
+
+    let output = &vm.aff_outputs[0];
+    assert_eq!(output.len(), MAX_AFF_OUTPUT);
+    // One character every 4 cycles, the oldest ones being dropped
+    assert!(output.back().unwrap().cycle >= vm.cycles - 4);
+    assert_eq!(vm.aff_text(0), "A".repeat(MAX_AFF_OUTPUT));
+
+    let snapshot = vm.snapshot();
+    assert_eq!(
+        Snapshot::from_bytes(&snapshot.to_bytes()).unwrap(),
+        snapshot
+    );
+    let mut replay = ReplayReader::from_bytes(&writer.finish().unwrap()).unwrap();
+    assert_eq!(replay.seek(vm.cycles), &snapshot);
+}
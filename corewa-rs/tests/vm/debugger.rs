@@ -0,0 +1,217 @@
+use super::compile;
+use corewa_rs::{
+    spec::OpType,
+    vm::{
+        debugger::{AddressOutOfRange, Breakpoint, BreakpointHit},
+        outcome::TickStatus,
+        VirtualMachine,
+    },
+};
+
+const FORKER: &str = r#"
+.name "forker"
+.comment "forks and stores"
+live %42
+st   r1, 10
+fork %0
+"#;
+
+const LOADER: &str = r#"
+.name "loader"
+.comment "loads values"
+ld %7, r2
+ld %0, r2
+ld %7, r2
+"#;
+
+fn vm_with(champion: &str) -> VirtualMachine {
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, compile(champion))]).unwrap();
+    vm
+}
+
+fn hits(status: TickStatus) -> Vec<BreakpointHit> {
+    match status {
+        TickStatus::Break(hits) => hits,
+        other => panic!("Expected a breakpoint hit, got {:?}", other),
+    }
+}
+
+#[test]
+fn op_breakpoint() {
+    let mut vm = vm_with(FORKER);
+    let id = vm
+        .add_breakpoint(Breakpoint::Op {
+            pid: 0,
+            op: OpType::St,
+        })
+        .unwrap();
+
+    let status = vm.run_until_break(None);
+    assert_eq!(
+        hits(status),
+        [BreakpointHit {
+            id,
+            cycle: 14,
+            pid: 0
+        }]
+    );
+    assert_eq!(vm.cycles, 15);
+}
+
+#[test]
+fn memory_write_breakpoint() {
+    let mut vm = vm_with(FORKER);
+    vm.add_breakpoint(Breakpoint::MemoryWrite {
+        addr: 0,
+        len: 15,
+        owner: None,
+    })
+    .unwrap();
+    let other_owner = vm
+        .add_breakpoint(Breakpoint::MemoryWrite {
+            addr: 18,
+            len: 1,
+            owner: Some(1),
+        })
+        .unwrap();
+    let id = vm
+        .add_breakpoint(Breakpoint::MemoryWrite {
+            addr: 18,
+            len: 10,
+            owner: Some(0),
+        })
+        .unwrap();
+
+    let hits = hits(vm.run_until_break(None));
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].id, id);
+    assert_eq!(hits[0].cycle, 14);
+
+    vm.breakpoints.remove(id);
+    vm.breakpoints.remove(other_owner);
+    assert_eq!(vm.run_until_break(Some(1000)), TickStatus::Running);
+    assert_eq!(vm.cycles, 1000);
+}
+
+#[test]
+fn address_breakpoint() {
+    let mut vm = vm_with(FORKER);
+    let id = vm.add_breakpoint(Breakpoint::Address(10)).unwrap();
+
+    assert_eq!(
+        hits(vm.run_until_break(None)),
+        [BreakpointHit {
+            id,
+            cycle: 14,
+            pid: 0
+        }]
+    );
+
+    // The forked process spawns on the breakpoint
+    assert_eq!(
+        hits(vm.run_until_break(None)),
+        [BreakpointHit {
+            id,
+            cycle: 814,
+            pid: 1
+        }]
+    );
+}
+
+#[test]
+fn out_of_memory_breakpoints() {
+    let mut vm = vm_with(FORKER);
+    let last = vm.rules.mem_size - 1;
+
+    assert!(vm.add_breakpoint(Breakpoint::Address(last)).is_ok());
+    assert_matches!(
+        vm.add_breakpoint(Breakpoint::Address(last + 1)),
+        Err(AddressOutOfRange { addr, .. }) if addr == last + 1
+    );
+    assert!(vm
+        .add_breakpoint(Breakpoint::MemoryWrite {
+            addr: last,
+            len: 1,
+            owner: None,
+        })
+        .is_ok());
+    assert_matches!(
+        vm.add_breakpoint(Breakpoint::MemoryWrite {
+            addr: last,
+            len: 2,
+            owner: None,
+        }),
+        Err(AddressOutOfRange { addr, .. }) if addr == last + 1
+    );
+}
+
+#[test]
+fn register_breakpoint() {
+    let mut vm = vm_with(LOADER);
+    let id = vm
+        .add_breakpoint(Breakpoint::Register { reg: 2, value: 7 })
+        .unwrap();
+
+    let first = hits(vm.run_until_break(None));
+    let second = hits(vm.run_until_break(None));
+    assert_eq!((first[0].id, first[0].cycle), (id, 4));
+    assert_eq!((second[0].id, second[0].cycle), (id, 14));
+}
+
+#[test]
+fn run_until_break_without_breakpoints() {
+    let mut vm = vm_with(FORKER);
+    assert_eq!(vm.run_until_break(Some(100)), TickStatus::Running);
+    assert!(vm.run_until_break(None).is_finished());
+}
+
+#[test]
+fn parse_breakpoints() {
+    let specs = [
+        ("pc=12", Breakpoint::Address(12)),
+        (
+            "op=3:sti",
+            Breakpoint::Op {
+                pid: 3,
+                op: OpType::Sti,
+            },
+        ),
+        (
+            "write=100..104",
+            Breakpoint::MemoryWrite {
+                addr: 100,
+                len: 4,
+                owner: None,
+            },
+        ),
+        (
+            "write=0..1:2",
+            Breakpoint::MemoryWrite {
+                addr: 0,
+                len: 1,
+                owner: Some(2),
+            },
+        ),
+        ("reg=r16:-1", Breakpoint::Register { reg: 16, value: -1 }),
+    ];
+
+    for (spec, breakpoint) in specs.iter() {
+        assert_eq!(&spec.parse::<Breakpoint>().unwrap(), breakpoint);
+        assert_eq!(&breakpoint.to_string(), spec);
+    }
+
+    for spec in [
+        "pc",
+        "pc=x",
+        "op=1",
+        "op=1:nop",
+        "write=4..4",
+        "reg=r17:0",
+        "brk=1",
+    ]
+    .iter()
+    {
+        assert!(spec.parse::<Breakpoint>().is_err(), "{}", spec);
+    }
+}
@@ -1,14 +1,18 @@
-use corewa_rs::vm::VirtualMachine;
+use super::raw_champion;
+use corewa_rs::vm::{
+    outcome::{EndReason, MatchOutcome},
+    VirtualMachine,
+};
 
-fn fight_cycles(players: &[(i32, Vec<u8>)]) -> u32 {
+fn fight(players: &[(i32, Vec<u8>)]) -> MatchOutcome {
     let mut vm = VirtualMachine::new();
-    vm.load_players(players);
+    vm.load_players(players).unwrap();
 
-    while !vm.processes.is_empty() {
-        vm.tick();
-    }
+    vm.run_to_completion(None)
+}
 
-    vm.cycles
+fn fight_cycles(players: &[(i32, Vec<u8>)]) -> u32 {
+    fight(players).cycles
 }
 
 macro_rules! test_single {
@@ -35,3 +39,87 @@ fn four_players() {
     ];
     assert_eq!(fight_cycles(&players), 24367)
 }
+
+#[test]
+fn four_players_outcome() {
+    let players = [
+        (1, sample!(kappa).to_vec()),
+        (2, sample!(thunder).to_vec()),
+        (3, sample!(sweepmaster).to_vec()),
+        (4, sample!(skynet).to_vec()),
+    ];
+    let outcome = fight(&players);
+
+    assert_eq!(outcome.reason, EndReason::AllProcessesKilled);
+    assert_eq!(outcome.last_lives.len(), 4);
+    let best_last_live = outcome.last_lives.iter().max().copied();
+    let winner_idx = players
+        .iter()
+        .position(|(id, _)| Some(*id) == outcome.winner)
+        .expect("Winner is not a player");
+    assert_eq!(Some(outcome.last_lives[winner_idx]), best_last_live);
+}
+
+#[test]
+fn nobody_alive_goes_to_last_player() {
+    let players = [(1, raw_champion(&[0; 8])), (2, raw_champion(&[0; 8]))];
+    let outcome = fight(&players);
+
+    assert_eq!(outcome.winner, Some(2));
+    assert_eq!(outcome.last_lives.as_slice(), [0, 0]);
+    assert_eq!(outcome.cycles, 1536);
+}
+
+#[test]
+fn cycle_limit() {
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, sample!(zork).to_vec())]).unwrap();
+
+    let outcome = vm.run_to_completion(Some(1000));
+
+    assert_eq!(outcome.reason, EndReason::CycleLimitReached);
+    assert_eq!(outcome.cycles, 1000);
+    assert_eq!(outcome.winner, Some(1));
+}
+
+#[test]
+fn fast_forward_matches_ticking() {
+    let players = [
+        (1, sample!(zork).to_vec()),
+        (2, sample!(helltrain).to_vec()),
+    ];
+
+    let mut ticked = VirtualMachine::new();
+    ticked.load_players(&players).unwrap();
+    let mut fast = VirtualMachine::new();
+    fast.load_players(&players).unwrap();
+
+    for limit in (1000..=10_000).step_by(1000) {
+        while ticked.cycles < limit {
+            ticked.tick();
+        }
+        fast.run_to_completion(Some(limit));
+        assert_eq!(fast.snapshot(), ticked.snapshot(), "at cycle {}", limit);
+    }
+}
+
+#[test]
+fn fast_forward_keeps_checkpoints_with_history() {
+    let players = [
+        (1, sample!(zork).to_vec()),
+        (2, sample!(helltrain).to_vec()),
+    ];
+
+    let mut ticked = VirtualMachine::new();
+    ticked.load_players(&players).unwrap();
+    while ticked.cycles < 4000 {
+        ticked.tick();
+    }
+    let mut fast = VirtualMachine::new();
+    fast.load_players(&players).unwrap();
+    fast.enable_history(8);
+
+    fast.run_to_completion(Some(5000));
+    assert_eq!(fast.step_back(1000), 1000);
+    assert_eq!(fast.snapshot(), ticked.snapshot());
+}
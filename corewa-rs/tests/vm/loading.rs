@@ -0,0 +1,294 @@
+use super::{compile, raw_champion};
+use corewa_rs::{
+    spec::{HEADER_SIZE, PROG_NAME_LENGTH},
+    vm::{
+        builder::RandomSetup,
+        loader::{HeaderField, LoadError, PlacementMode},
+        memory::NO_OWNER,
+        VirtualMachine,
+    },
+};
+
+const NAME_OFFSET: usize = 4;
+const COMMENT_OFFSET: usize = NAME_OFFSET + PROG_NAME_LENGTH + 1 + 4;
+
+fn load(program: Vec<u8>) -> Result<(), LoadError> {
+    VirtualMachine::new().load_players(&[(1, program)])
+}
+
+#[test]
+fn valid_champions() {
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[
+        (1, sample!(kappa).to_vec()),
+        (2, compile(".name \"x\"\n.comment \"y\"\nlive %1")),
+    ])
+    .unwrap();
+
+    assert_eq!(vm.players[0].name, "Kappa");
+    assert_eq!(vm.players[1].name, "x");
+    assert_eq!(vm.players[1].comment, "y");
+    assert_eq!(vm.players[1].size, 5);
+}
+
+#[test]
+fn short_input() {
+    assert_matches!(load(vec![0; 10]), Err(LoadError::MissingHeader(1, 10)));
+    assert_matches!(load(Vec::new()), Err(LoadError::MissingHeader(1, 0)));
+}
+
+#[test]
+fn invalid_magic() {
+    let mut program = raw_champion(&[1]);
+    program[0] = 0x42;
+    assert_matches!(load(program), Err(LoadError::InvalidMagic(1, 0x42EA_83F3)));
+}
+
+#[test]
+fn missing_nul() {
+    let mut program = raw_champion(&[1]);
+    program[NAME_OFFSET..NAME_OFFSET + PROG_NAME_LENGTH + 1].fill(b'a');
+    assert_matches!(
+        load(program),
+        Err(LoadError::MissingNul(1, HeaderField::Name))
+    );
+
+    let mut program = raw_champion(&[1]);
+    program[COMMENT_OFFSET..HEADER_SIZE].fill(b'a');
+    assert_matches!(
+        load(program),
+        Err(LoadError::MissingNul(1, HeaderField::Comment))
+    );
+}
+
+#[test]
+fn invalid_utf8() {
+    let mut program = raw_champion(&[1]);
+    program[NAME_OFFSET] = 0xFF;
+    assert_matches!(
+        load(program),
+        Err(LoadError::InvalidUtf8(1, HeaderField::Name))
+    );
+
+    let mut program = raw_champion(&[1]);
+    program[COMMENT_OFFSET..COMMENT_OFFSET + 2].copy_from_slice(&[0xC3, 0x28]);
+    assert_matches!(
+        load(program),
+        Err(LoadError::InvalidUtf8(1, HeaderField::Comment))
+    );
+}
+
+#[test]
+fn size_mismatch() {
+    let mut program = raw_champion(&[1, 2, 3]);
+    program.pop();
+    assert_matches!(
+        load(program),
+        Err(LoadError::SizeMismatch {
+            player_id: 1,
+            declared: 3,
+            actual: 2
+        })
+    );
+}
+
+#[test]
+fn unknown_size() {
+    let mut program = raw_champion(&[1, 2, 3]);
+    program[COMMENT_OFFSET - 4..COMMENT_OFFSET].copy_from_slice(&[0; 4]);
+
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, program), (2, sample!(zork).to_vec())])
+        .unwrap();
+    assert_eq!(vm.players[0].size, 3);
+    assert_eq!(vm.players[1].size, 29);
+}
+
+#[test]
+fn champion_too_big() {
+    let program = raw_champion(&[0; 683]);
+    assert_matches!(load(program), Err(LoadError::ChampionTooBig(1, 683, 682)));
+}
+
+#[test]
+fn too_many_players() {
+    let players: Vec<_> = (1..=5).map(|id| (id, raw_champion(&[1]))).collect();
+    assert_matches!(
+        VirtualMachine::new().load_players(&players),
+        Err(LoadError::TooManyPlayers(5))
+    );
+}
+
+#[test]
+fn loading_twice() {
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, sample!(zork).to_vec())]).unwrap();
+
+    assert_matches!(
+        vm.load_players(&[(2, sample!(kappa).to_vec())]),
+        Err(LoadError::AlreadyLoaded)
+    );
+    assert_eq!(vm.players.len(), 1);
+    assert_eq!(vm.processes.len(), 1);
+}
+
+#[test]
+fn failed_loading_leaves_the_vm_untouched() {
+    let mut vm = VirtualMachine::new();
+    let result = vm.load_players(&[(1, sample!(kappa).to_vec()), (2, vec![0; 3])]);
+
+    assert_matches!(result, Err(LoadError::MissingHeader(2, 3)));
+    assert!(vm.players.is_empty());
+    assert!(vm.processes.is_empty());
+}
+
+fn process_addresses(vm: &VirtualMachine) -> Vec<usize> {
+    vm.processes
+        .iter()
+        .map(|process| process.pc.addr())
+        .collect()
+}
+
+fn random_placement(seed: u64, min_separation: usize) -> Result<VirtualMachine, LoadError> {
+    VirtualMachine::builder()
+        .player(1, sample!(kappa).to_vec())
+        .player(2, sample!(thunder).to_vec())
+        .player(3, sample!(zork).to_vec())
+        .placement_mode(PlacementMode::Random {
+            seed,
+            min_separation,
+        })
+        .build()
+}
+
+#[test]
+fn explicit_addresses() {
+    let vm = VirtualMachine::builder()
+        .player_at(1, sample!(zork).to_vec(), 100)
+        .player(2, sample!(zork).to_vec())
+        .player_at(3, sample!(zork).to_vec(), 4090)
+        .build()
+        .unwrap();
+
+    assert_eq!(process_addresses(&vm), [100, 1365, 4090]);
+    assert_eq!(vm.memory.owners[4090 + 28], 2);
+    assert_eq!(vm.memory.owners[100], 0);
+}
+
+#[test]
+fn invalid_addresses() {
+    let result = VirtualMachine::builder()
+        .player_at(1, sample!(zork).to_vec(), 4096)
+        .build();
+    assert_matches!(result.err(), Some(LoadError::InvalidAddress(1, 4096)));
+
+    let result = VirtualMachine::builder()
+        .player_at(1, sample!(zork).to_vec(), 4080)
+        .player_at(2, sample!(zork).to_vec(), 5)
+        .build();
+    assert_matches!(result.err(), Some(LoadError::OverlappingChampions(1, 2)));
+}
+
+#[test]
+fn seeded_random_placement() {
+    let placements: Vec<_> = (0..20)
+        .map(|seed| process_addresses(&random_placement(seed, 200).unwrap()))
+        .collect();
+
+    let sizes = [682, 634, 29];
+    for addresses in &placements {
+        for i in 0..3 {
+            for j in 0..3 {
+                if i != j {
+                    let distance = (addresses[j] + 4096 - addresses[i]) % 4096;
+                    assert!(distance >= sizes[i] + 200, "{:?}", addresses);
+                }
+            }
+        }
+    }
+
+    assert_eq!(
+        process_addresses(&random_placement(7, 200).unwrap()),
+        placements[7]
+    );
+    assert_ne!(placements[0], placements[1]);
+}
+
+#[test]
+fn no_room_for_random_placement() {
+    assert_matches!(
+        random_placement(0, 1500).err(),
+        Some(LoadError::NoRoomForPlacement(_))
+    );
+}
+
+fn randomized_duel(setup: RandomSetup) -> VirtualMachine {
+    VirtualMachine::builder()
+        .player(1, sample!(zork).to_vec())
+        .player(2, sample!(thunder).to_vec())
+        .randomized(setup)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn randomized_setup_is_reproducible() {
+    for seed in 0..10 {
+        let setup = RandomSetup::new(seed);
+        let mut vm = randomized_duel(setup);
+        let mut same_vm = randomized_duel(setup);
+        assert_eq!(vm.snapshot(), same_vm.snapshot());
+        assert_eq!(
+            vm.run_to_completion(Some(5000)),
+            same_vm.run_to_completion(Some(5000))
+        );
+    }
+
+    assert_ne!(
+        randomized_duel(RandomSetup::new(1)).snapshot(),
+        randomized_duel(RandomSetup::new(2)).snapshot()
+    );
+}
+
+#[test]
+fn randomized_setup_options() {
+    let setup = RandomSetup {
+        seed: 3,
+        shuffle_slots: false,
+        random_placement: None,
+        noise: false,
+    };
+    let plain = VirtualMachine::builder()
+        .player(1, sample!(zork).to_vec())
+        .player(2, sample!(thunder).to_vec())
+        .build()
+        .unwrap();
+    assert_eq!(randomized_duel(setup).snapshot(), plain.snapshot());
+
+    let shuffled_orders: Vec<Vec<i32>> = (0..10)
+        .map(|seed| {
+            let setup = RandomSetup {
+                shuffle_slots: true,
+                ..RandomSetup::new(seed)
+            };
+            let vm = randomized_duel(setup);
+            vm.players.iter().map(|player| player.id).collect()
+        })
+        .collect();
+    assert!(shuffled_orders.contains(&vec![1, 2]));
+    assert!(shuffled_orders.contains(&vec![2, 1]));
+
+    let noisy = randomized_duel(RandomSetup {
+        noise: true,
+        ..setup
+    });
+    let noise_cells = (0..noisy.memory.size())
+        .filter(|&idx| noisy.memory.owners[idx] == NO_OWNER && noisy.memory[idx] != 0)
+        .count();
+    assert!(noise_cells > 3000);
+    // Champions are loaded on top of the noise
+    assert_eq!(
+        &noisy.memory.values.inner()[..29],
+        &sample!(zork)[HEADER_SIZE..]
+    );
+}
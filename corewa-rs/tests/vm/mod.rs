@@ -4,4 +4,35 @@ macro_rules! sample {
     };
 }
 
+fn compile(input: &str) -> Vec<u8> {
+    use corewa_rs::{language, rules::RuleSet};
+
+    let champion = language::read_champion(input.as_bytes()).expect("Failed to read champion");
+    let mut out = Vec::new();
+    language::write_champion(&mut out, champion, &RuleSet::default())
+        .expect("Failed to write champion");
+    out
+}
+
+/// Builds a valid `.cor` file with an empty name and comment around `code`
+fn raw_champion(code: &[u8]) -> Vec<u8> {
+    use corewa_rs::spec::{COREWAR_MAGIC, HEADER_SIZE, PROG_NAME_LENGTH};
+
+    let mut champion = vec![0; HEADER_SIZE];
+    champion[..4].copy_from_slice(&COREWAR_MAGIC.to_be_bytes());
+    let size_offset = 4 + PROG_NAME_LENGTH + 1;
+    champion[size_offset..size_offset + 4].copy_from_slice(&(code.len() as u32).to_be_bytes());
+    champion.extend_from_slice(code);
+    champion
+}
+
+mod aff;
+mod debugger;
 mod fights;
+mod loading;
+mod observer;
+mod replay;
+mod rules;
+mod snapshot;
+mod tournament;
+mod trace;
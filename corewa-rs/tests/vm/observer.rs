@@ -0,0 +1,141 @@
+use super::compile;
+use corewa_rs::vm::{
+    observer::{
+        VmEvent::{self, *},
+        VmObserver,
+    },
+    VirtualMachine,
+};
+
+const FORKER: &str = r#"
+.name "forker"
+.comment "forks and stores"
+live %42
+st   r1, 10
+fork %0
+"#;
+
+fn events_until(vm: &mut VirtualMachine, cycles: u32) -> Vec<VmEvent> {
+    let mut events = Vec::new();
+    while vm.cycles < cycles {
+        vm.tick_with(&mut events);
+    }
+    events
+}
+
+#[test]
+fn instruction_events() {
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, compile(FORKER))]).unwrap();
+
+    let events = events_until(&mut vm, 825);
+    let event_kinds: Vec<_> = events
+        .iter()
+        .map(|event| match event {
+            InstructionExecuted { instr, .. } => format!("{}", instr),
+            MemoryWrite { addr, len, .. } => format!("write {} {}", addr, len),
+            ForkSpawned { child, pc, .. } => format!("fork {} {}", child, pc),
+            LiveReported { player_id, .. } => format!("live {}", player_id),
+            other => format!("{:?}", other),
+        })
+        .collect();
+
+    assert_eq!(
+        event_kinds,
+        [
+            "live 42",
+            "live %42",
+            "write 15 4",
+            "st r1, 10",
+            "fork 1 10",
+            "fork %0",
+        ]
+    );
+    assert_matches!(
+        events[4],
+        ForkSpawned {
+            cycle: 814,
+            parent: 0,
+            ..
+        }
+    );
+}
+
+#[test]
+fn registers_are_opt_in() {
+    struct Executed(Vec<VmEvent>);
+
+    impl VmObserver for Executed {
+        fn notify(&mut self, event: VmEvent) {
+            if let InstructionExecuted { .. } = event {
+                self.0.push(event)
+            }
+        }
+    }
+
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, compile(FORKER))]).unwrap();
+    let mut executed = Executed(Vec::new());
+    while vm.cycles < 30 {
+        vm.tick_with(&mut executed);
+    }
+    assert_matches!(
+        executed.0.as_slice(),
+        [
+            InstructionExecuted {
+                before: None,
+                after: None,
+                ..
+            },
+            ..
+        ]
+    );
+
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, compile(FORKER))]).unwrap();
+    let events = events_until(&mut vm, 30);
+    assert!(events.iter().any(|event| matches!(
+        event,
+        InstructionExecuted {
+            before: Some(_),
+            after: Some(_),
+            ..
+        }
+    )));
+}
+
+#[test]
+fn live_check_events() {
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, compile(FORKER))]).unwrap();
+
+    let events = events_until(&mut vm, 1536);
+    let kills: Vec<_> = events
+        .iter()
+        .filter(|event| matches!(event, ProcessKilled { .. }))
+        .collect();
+
+    // The forked process never executed a live
+    assert_eq!(
+        kills,
+        [&ProcessKilled {
+            cycle: 1536,
+            pid: 1,
+            owner: 0
+        }]
+    );
+}
+
+#[test]
+fn run_to_completion_events() {
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, compile(FORKER))]).unwrap();
+    let mut ticked_vm = VirtualMachine::new();
+    ticked_vm.load_players(&[(1, compile(FORKER))]).unwrap();
+
+    let mut events = Vec::new();
+    let outcome = vm.run_to_completion_with(None, &mut events);
+
+    assert_eq!(events, events_until(&mut ticked_vm, outcome.cycles));
+    assert_eq!(outcome, ticked_vm.outcome().unwrap());
+}
@@ -0,0 +1,140 @@
+use corewa_rs::vm::{
+    replay::{ReplayError, ReplayReader, ReplayWriter},
+    snapshot::Snapshot,
+    VirtualMachine,
+};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::io::{Read, Write};
+
+fn record(cycles: u32) -> (Vec<u8>, Vec<Snapshot>) {
+    let players = [
+        (1, sample!(zork).to_vec()),
+        (2, sample!(helltrain).to_vec()),
+    ];
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&players).unwrap();
+
+    let mut writer = ReplayWriter::new(Vec::new(), &vm, &players).unwrap();
+    let mut states = vec![vm.snapshot()];
+    while vm.cycles < cycles {
+        writer.tick(&mut vm).unwrap();
+        states.push(vm.snapshot());
+    }
+
+    (writer.finish().unwrap(), states)
+}
+
+#[test]
+fn header_round_trip() {
+    let (bytes, _) = record(10);
+    let replay = ReplayReader::from_bytes(&bytes).unwrap();
+
+    let players = &replay.header.players;
+    assert_eq!(players.len(), 2);
+    assert_eq!((players[0].id, players[0].address), (1, 0));
+    assert_eq!((players[1].id, players[1].address), (2, 2048));
+    assert_eq!(players[0].champion, sample!(zork));
+    assert_eq!(players[1].champion, sample!(helltrain));
+    assert_eq!((replay.first_cycle(), replay.last_cycle()), (0, 10));
+}
+
+#[test]
+fn playback_matches_the_match() {
+    let (bytes, states) = record(2000);
+    let mut replay = ReplayReader::from_bytes(&bytes).unwrap();
+
+    let mut cycle = 0;
+    while replay.step() {
+        cycle += 1;
+        assert_eq!(replay.state(), &states[cycle]);
+    }
+    assert_eq!(cycle, 2000);
+}
+
+#[test]
+fn seeking() {
+    let (bytes, states) = record(2000);
+    let mut replay = ReplayReader::from_bytes(&bytes).unwrap();
+
+    for &cycle in &[1500, 1510, 700, 0, 1999, 256, 255, 1000] {
+        assert_eq!(replay.seek(cycle), &states[cycle as usize]);
+    }
+    assert_eq!(replay.seek(5000), &states[2000]);
+}
+
+#[test]
+fn ticks_without_the_writer() {
+    let players = [(1, sample!(zork).to_vec())];
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&players).unwrap();
+
+    let mut writer = ReplayWriter::new(Vec::new(), &vm, &players).unwrap();
+    let mut states = vec![vm.snapshot()];
+    while vm.cycles < 600 {
+        // The memory changes of these ticks are not observed by the writer
+        if vm.cycles % 100 == 50 {
+            vm.tick();
+        }
+        writer.tick(&mut vm).unwrap();
+        states.push(vm.snapshot());
+    }
+
+    let mut replay = ReplayReader::from_bytes(&writer.finish().unwrap()).unwrap();
+    let mut played = vec![replay.state().clone()];
+    while replay.step() {
+        played.push(replay.state().clone());
+    }
+    assert_eq!(played, states);
+}
+
+#[test]
+fn invalid_replays() {
+    assert_matches!(
+        ReplayReader::from_bytes(b"CWVM"),
+        Err(ReplayError::InvalidMagic)
+    );
+    assert_matches!(
+        ReplayReader::from_bytes(b"CWRP\x00\x02"),
+        Err(ReplayError::UnsupportedVersion(2))
+    );
+
+    let (bytes, _) = record(10);
+    assert_matches!(
+        ReplayReader::from_bytes(&bytes[..bytes.len() / 2]),
+        Err(ReplayError::IOError(_))
+    );
+
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, sample!(zork).to_vec())]).unwrap();
+    assert_matches!(
+        ReplayWriter::new(Vec::new(), &vm, &[(2, sample!(zork).to_vec())]).err(),
+        Some(ReplayError::MissingChampion(1))
+    );
+}
+
+#[test]
+fn oversized_frames() {
+    let (bytes, _) = record(10);
+    let mut stream = Vec::new();
+    ZlibDecoder::new(&bytes[6..])
+        .read_to_end(&mut stream)
+        .unwrap();
+
+    // A delta announcing more memory changes than there are cells
+    stream.push(1);
+    for value in [11, 0, 1536, 0, 0, 2] {
+        stream.extend_from_slice(&u32::to_be_bytes(value));
+    }
+    stream.extend_from_slice(&[0; 4 * 4]);
+    stream.extend_from_slice(&u32::MAX.to_be_bytes());
+
+    let mut crafted = bytes[..6].to_vec();
+    let mut out = ZlibEncoder::new(&mut crafted, Compression::default());
+    out.write_all(&stream).unwrap();
+    out.finish().unwrap();
+
+    assert_matches!(
+        ReplayReader::from_bytes(&crafted),
+        Err(ReplayError::InvalidData("too many memory changes"))
+    );
+}
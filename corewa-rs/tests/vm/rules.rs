@@ -0,0 +1,251 @@
+use super::raw_champion;
+use corewa_rs::{
+    rules::{RuleSet, RuleSetError},
+    spec::*,
+    vm::{loader::LoadError, VirtualMachine},
+};
+
+#[test]
+fn empty_preset_is_default() {
+    assert_eq!("# nothing".parse::<RuleSet>().unwrap(), RuleSet::default());
+}
+
+#[test]
+fn preset_overrides() {
+    let rules: RuleSet = "
+        mem_size = 2048 # half the arena
+        idx_mod  = 256
+        nbr_live = 42
+    "
+    .parse()
+    .unwrap();
+
+    assert_eq!(
+        rules,
+        RuleSet {
+            mem_size: 2048,
+            idx_mod: 256,
+            nbr_live: 42,
+            ..RuleSet::default()
+        }
+    );
+}
+
+#[test]
+fn invalid_presets() {
+    assert_matches!(
+        "mem_size: 12".parse::<RuleSet>(),
+        Err(RuleSetError::InvalidLine(1, _))
+    );
+    assert_matches!(
+        "\nmem_sizes = 12".parse::<RuleSet>(),
+        Err(RuleSetError::InvalidLine(2, _))
+    );
+    assert_matches!(
+        "idx_mod = -1".parse::<RuleSet>(),
+        Err(RuleSetError::InvalidLine(1, _))
+    );
+    assert_matches!(
+        "mem_size = 0".parse::<RuleSet>(),
+        Err(RuleSetError::EmptyMemory)
+    );
+    assert_matches!(
+        "reg_count = 17".parse::<RuleSet>(),
+        Err(RuleSetError::InvalidRegCount(17))
+    );
+    assert_matches!(
+        "cycle_delta = 0".parse::<RuleSet>(),
+        Err(RuleSetError::NoCycleDelta)
+    );
+    assert_matches!(
+        "max_checks = 0".parse::<RuleSet>(),
+        Err(RuleSetError::NoMaxChecks)
+    );
+}
+
+#[test]
+fn default_rules_fight() {
+    let mut vm = VirtualMachine::with_rules(RuleSet::default());
+    vm.load_players(&[(1, sample!(zork).to_vec())]).unwrap();
+
+    assert_eq!(vm.run_to_completion(None).cycles, 57_955);
+}
+
+#[test]
+fn shorter_check_interval_fight() {
+    let rules = RuleSet {
+        check_interval: 512,
+        ..RuleSet::default()
+    };
+    let mut vm = VirtualMachine::with_rules(rules);
+    vm.load_players(&[(1, sample!(zork).to_vec())]).unwrap();
+
+    let outcome = vm.run_to_completion(None);
+    assert!(outcome.cycles < 57_955);
+    assert!(vm.check_interval < 512);
+}
+
+#[test]
+fn smaller_arena() {
+    let rules = RuleSet {
+        mem_size: 1024,
+        idx_mod: 128,
+        ..RuleSet::default()
+    };
+    let mut vm = VirtualMachine::with_rules(rules);
+    vm.load_players(&[(1, sample!(zork).to_vec()), (2, sample!(zork).to_vec())])
+        .unwrap();
+
+    assert_eq!(vm.memory.size(), 1024);
+    assert_eq!(vm.processes[1].pc.addr(), 512);
+    vm.run_to_completion(None);
+}
+
+#[test]
+fn op_overrides() {
+    let rules: RuleSet = "
+        fork.cycles = 400
+        sti.dir_size = 4
+        sti.reach = long
+        sti.params = R, RDI, RDI
+    "
+    .parse()
+    .unwrap();
+
+    let fork = rules.ops.get(OpType::Fork);
+    assert_eq!(fork.cycles, 400);
+
+    let sti = rules.ops.get(OpType::Sti);
+    assert_eq!(sti.dir_size, DirectSize::FourBytes);
+    assert_eq!(sti.reach, OffsetType::Long);
+    assert_eq!(
+        sti.param_masks,
+        [T_REG, T_REG | T_DIR | T_IND, T_REG | T_DIR | T_IND]
+    );
+}
+
+#[test]
+fn invalid_op_overrides() {
+    assert_matches!(
+        "jmp.cycles = 2".parse::<RuleSet>(),
+        Err(RuleSetError::InvalidLine(1, _))
+    );
+    assert_matches!(
+        "fork.dir_size = 3".parse::<RuleSet>(),
+        Err(RuleSetError::InvalidLine(1, _))
+    );
+    assert_matches!(
+        "add.params = R, R".parse::<RuleSet>(),
+        Err(RuleSetError::InvalidLine(1, _))
+    );
+    assert_matches!(
+        "live.cycles = 0".parse::<RuleSet>(),
+        Err(RuleSetError::InvalidOpSpec(OpType::Live))
+    );
+    assert_matches!(
+        "fork.params = I".parse::<RuleSet>(),
+        Err(RuleSetError::UnsupportedParamTypes(OpType::Fork))
+    );
+    assert_matches!(
+        "add.params = R, R, D".parse::<RuleSet>(),
+        Err(RuleSetError::UnsupportedParamTypes(OpType::Add))
+    );
+}
+
+#[test]
+fn cheaper_fork() {
+    let fork_champion = raw_champion(&[0x0C, 0x00, 0x00]); // fork %0
+
+    let process_count_at = |rules: RuleSet, cycles| {
+        let mut vm = VirtualMachine::with_rules(rules);
+        vm.load_players(&[(1, fork_champion.clone())]).unwrap();
+        vm.run_to_completion(Some(cycles));
+        vm.processes.len()
+    };
+
+    let cheap_fork_rules = "fork.cycles = 400".parse().unwrap();
+    assert_eq!(process_count_at(RuleSet::default(), 400), 1);
+    assert_eq!(process_count_at(cheap_fork_rules, 400), 2);
+}
+
+#[test]
+fn compat_preset() {
+    assert_eq!(
+        "semantics = compat".parse::<RuleSet>().unwrap(),
+        RuleSet::compat()
+    );
+    assert_matches!(
+        "semantics = 42".parse::<RuleSet>(),
+        Err(RuleSetError::InvalidLine(1, _))
+    );
+}
+
+fn vm_after(rules: RuleSet, code: &[u8], cycles: u32) -> VirtualMachine {
+    let mut vm = VirtualMachine::with_rules(rules);
+    vm.load_players(&[(1, raw_champion(code))]).unwrap();
+    while vm.cycles < cycles {
+        vm.tick();
+    }
+    vm
+}
+
+#[test]
+fn compat_skips_invalid_pcb_instructions() {
+    // ld with an indirect destination: 1 op byte, the pcb and 2 indirects
+    let code = [0x02, 0b11_11_00_00, 0x00, 0x01, 0x00, 0x02];
+
+    let native = vm_after(RuleSet::default(), &code, 5);
+    assert_eq!(native.processes[0].pc.addr(), 1);
+    let compat = vm_after(RuleSet::compat(), &code, 5);
+    assert_eq!(compat.processes[0].pc.addr(), 6);
+}
+
+#[test]
+fn compat_ldi_affects_zf() {
+    // ldi %100, %0, r2 reads the empty memory after the champion
+    let code = [0x0A, 0b10_10_01_00, 0x00, 0x64, 0x00, 0x00, 0x02];
+
+    let native = vm_after(RuleSet::default(), &code, 25);
+    assert!(!native.processes[0].zf);
+    let compat = vm_after(RuleSet::compat(), &code, 25);
+    assert!(compat.processes[0].zf);
+}
+
+#[test]
+fn compat_forks_inherit_last_live() {
+    // live %1, fork %100
+    let code = [0x01, 0x00, 0x00, 0x00, 0x01, 0x0C, 0x00, 0x64];
+    let check_interval = RuleSet::default().check_interval;
+
+    let native = vm_after(RuleSet::default(), &code, check_interval);
+    assert_eq!(native.processes.len(), 1);
+    let compat = vm_after(RuleSet::compat(), &code, check_interval);
+    assert_eq!(compat.processes.len(), 2);
+}
+
+#[test]
+fn builder_rejects_invalid_rules() {
+    for rules in [
+        RuleSet {
+            mem_size: 0,
+            ..RuleSet::default()
+        },
+        RuleSet {
+            idx_mod: 0,
+            ..RuleSet::default()
+        },
+        RuleSet {
+            cycle_delta: 0,
+            ..RuleSet::default()
+        },
+        RuleSet {
+            max_checks: 0,
+            ..RuleSet::default()
+        },
+    ] {
+        assert_matches!(
+            VirtualMachine::builder().rules(rules).build().err(),
+            Some(LoadError::InvalidRules(_))
+        );
+    }
+}
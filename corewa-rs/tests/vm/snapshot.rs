@@ -0,0 +1,227 @@
+use corewa_rs::{
+    rules::{RuleSet, RuleSetError, MAX_MEM_SIZE},
+    spec::{MAX_PARAMS, MAX_PLAYERS, OP_TYPES},
+    vm::{
+        loader::LoadError,
+        snapshot::{Snapshot, SnapshotError},
+        VirtualMachine,
+    },
+};
+
+fn duel() -> VirtualMachine {
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, sample!(zork).to_vec()), (2, sample!(thunder).to_vec())])
+        .unwrap();
+    vm
+}
+
+fn run_for(vm: &mut VirtualMachine, cycles: u32) {
+    for _ in 0..cycles {
+        vm.tick();
+    }
+}
+
+#[test]
+fn restore_resumes_identically() {
+    let mut vm = duel();
+    run_for(&mut vm, 3000);
+
+    let snapshot = vm.snapshot();
+    let outcome = vm.run_to_completion(None);
+
+    vm.restore(&snapshot);
+    assert_eq!(vm.snapshot(), snapshot);
+    assert_eq!(vm.run_to_completion(None), outcome);
+
+    let mut other = VirtualMachine::from_snapshot(&snapshot);
+    assert_eq!(other.run_to_completion(None), outcome);
+}
+
+#[test]
+fn snapshots_compare_states() {
+    let mut vm = duel();
+    run_for(&mut vm, 500);
+    let before = vm.snapshot();
+
+    run_for(&mut vm, 1);
+    assert_ne!(vm.snapshot(), before);
+    assert_eq!(vm.snapshot(), vm.snapshot());
+}
+
+#[test]
+fn binary_round_trip() {
+    let mut vm = duel();
+    run_for(&mut vm, 2000);
+
+    let snapshot = vm.snapshot();
+    let bytes = snapshot.to_bytes();
+    assert_eq!(&bytes[..4], b"CWVM");
+    assert_eq!(Snapshot::from_bytes(&bytes).unwrap(), snapshot);
+
+    // The encoding is deterministic
+    assert_eq!(Snapshot::from_bytes(&bytes).unwrap().to_bytes(), bytes);
+}
+
+#[test]
+fn invalid_binaries() {
+    let bytes = duel().snapshot().to_bytes();
+
+    assert_matches!(
+        Snapshot::from_bytes(b"NOPE\0\x01"),
+        Err(SnapshotError::InvalidMagic)
+    );
+
+    let mut future = bytes.clone();
+    future[5] = 3;
+    assert_matches!(
+        Snapshot::from_bytes(&future),
+        Err(SnapshotError::UnsupportedVersion(3))
+    );
+
+    assert_matches!(
+        Snapshot::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(SnapshotError::IOError(_))
+    );
+
+    let mut trailing = bytes;
+    trailing.push(0);
+    assert_matches!(
+        Snapshot::from_bytes(&trailing),
+        Err(SnapshotError::TrailingBytes(1))
+    );
+}
+
+#[test]
+fn version_1_binaries() {
+    let snapshot = duel().snapshot();
+    let bytes = snapshot.to_bytes();
+
+    // Version 1 had no semantics byte after the rules
+    let semantics_offset = 4 + 2 + 4 * 7 + 1 + OP_TYPES.len() * (4 + MAX_PARAMS + 2);
+    let mut v1 = bytes.clone();
+    v1[5] = 1;
+    assert_eq!(v1.remove(semantics_offset), 0);
+
+    assert_eq!(Snapshot::from_bytes(&v1).unwrap(), snapshot);
+    assert_eq!(Snapshot::from_bytes(&v1).unwrap().to_bytes(), bytes);
+}
+
+#[test]
+fn oversized_binaries() {
+    let bytes = VirtualMachine::new().snapshot().to_bytes();
+
+    // The memory size follows the magic number and the version
+    let mut huge_memory = bytes.clone();
+    huge_memory[6..10].copy_from_slice(&u32::MAX.to_be_bytes());
+    assert_matches!(
+        Snapshot::from_bytes(&huge_memory),
+        Err(SnapshotError::InvalidData(_))
+    );
+
+    // Without players, the process count is followed by the pid pool, the last
+    // lives, 5 counters and the empty aff outputs
+    let mut many_processes = bytes;
+    let at = many_processes.len() - 4 * (1 + 1 + MAX_PLAYERS + 5 + MAX_PLAYERS);
+    many_processes[at..at + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+    assert_matches!(
+        Snapshot::from_bytes(&many_processes),
+        Err(SnapshotError::InvalidData(_))
+    );
+}
+
+#[test]
+fn largest_memory() {
+    let with_memory = |mem_size| {
+        VirtualMachine::builder()
+            .rules(RuleSet {
+                mem_size,
+                ..RuleSet::default()
+            })
+            .player(1, sample!(zork).to_vec())
+            .build()
+    };
+
+    // Every valid VM can be read back
+    let snapshot = with_memory(MAX_MEM_SIZE).unwrap().snapshot();
+    assert_eq!(
+        Snapshot::from_bytes(&snapshot.to_bytes()).unwrap(),
+        snapshot
+    );
+
+    assert_matches!(
+        with_memory(MAX_MEM_SIZE + 1).err(),
+        Some(LoadError::InvalidRules(RuleSetError::MemoryTooBig(_)))
+    );
+}
+
+#[test]
+fn step_back_restores_prior_states() {
+    let mut vm = duel();
+    vm.enable_history(16);
+    let mut history = Vec::new();
+    for _ in 0..700 {
+        history.push(vm.snapshot());
+        vm.tick();
+    }
+
+    assert_eq!(vm.step_back(1), 1);
+    assert_eq!(vm.snapshot(), history[699]);
+
+    assert_eq!(vm.step_back(300), 300);
+    assert_eq!(vm.snapshot(), history[399]);
+
+    run_for(&mut vm, 100);
+    assert_eq!(vm.snapshot(), history[499]);
+
+    assert_eq!(vm.step_back(1000), 499);
+    assert_eq!(vm.snapshot(), history[0]);
+    assert_eq!(vm.step_back(1), 0);
+}
+
+#[test]
+fn step_back_then_resume() {
+    let mut vm = duel();
+    vm.enable_history(64);
+    let outcome = duel().run_to_completion(None);
+
+    run_for(&mut vm, 5000);
+    vm.step_back(1234);
+    assert_eq!(vm.cycles, 5000 - 1234);
+    assert_eq!(vm.run_to_completion(None), outcome);
+
+    vm.step_back(10);
+    assert_eq!(vm.run_to_completion(None), outcome);
+}
+
+#[test]
+fn history_is_opt_in() {
+    let mut vm = duel();
+    run_for(&mut vm, 1000);
+    assert!(!vm.is_history_enabled());
+    assert_eq!(vm.step_back(10), 0);
+    assert_eq!(vm.cycles, 1000);
+
+    vm.enable_history(4);
+    run_for(&mut vm, 10);
+    assert_eq!(vm.step_back(100), 10);
+    assert_eq!(vm.cycles, 1000);
+
+    vm.disable_history();
+    run_for(&mut vm, 10);
+    assert_eq!(vm.step_back(5), 0);
+}
+
+#[test]
+fn history_is_bounded() {
+    let mut vm = duel();
+    vm.enable_history(2);
+    let mut history = Vec::new();
+    for _ in 0..1000 {
+        history.push(vm.snapshot());
+        vm.tick();
+    }
+
+    // Only the checkpoints of cycles 512 and 768 are kept
+    assert_eq!(vm.step_back(1000), 488);
+    assert_eq!(vm.snapshot(), history[512]);
+}
@@ -0,0 +1,156 @@
+use super::compile;
+use corewa_rs::tournament::{
+    run_tournament, Entrant, Matchup, TournamentConfig, TournamentError, INITIAL_ELO,
+};
+
+const SURVIVOR: &str = r#"
+.name "survivor"
+.comment "lives forever"
+sti r1, %:live, %1
+live: live %0
+zjmp %:live
+"#;
+
+const IDLER: &str = r#"
+.name "idler"
+.comment "never lives"
+idle: ld %0, r2
+zjmp %:idle
+"#;
+
+fn entrant(name: &str, source: &str) -> Entrant {
+    Entrant {
+        name: name.to_owned(),
+        champion: compile(source),
+    }
+}
+
+fn entrants() -> Vec<Entrant> {
+    vec![
+        entrant("survivor", SURVIVOR),
+        entrant("idler", IDLER),
+        entrant("survivor_bis", SURVIVOR),
+    ]
+}
+
+#[test]
+fn round_robin() {
+    let results = run_tournament(&entrants(), &TournamentConfig::default()).unwrap();
+
+    // 3 pairings in both seat orders
+    assert_eq!(results.matches.len(), 6);
+    assert_eq!(results.matches[0].seats, [0, 1]);
+    assert_eq!(results.matches[1].seats, [1, 0]);
+
+    let standing = |entrant| {
+        let standing = results
+            .standings
+            .iter()
+            .find(|standing| standing.entrant == entrant)
+            .unwrap();
+        (standing.wins, standing.losses, standing.draws)
+    };
+    // Survivors tie on their last live, the one seated last wins
+    assert_eq!(results.matches[2].seats, [0, 2]);
+    assert_eq!(results.matches[2].winner, Some(2));
+    assert_eq!(results.matches[3].winner, Some(0));
+    assert_eq!(standing(0), (3, 1, 0));
+    assert_eq!(standing(1), (0, 4, 0));
+    assert_eq!(standing(2), (3, 1, 0));
+
+    assert_eq!(results.standings[2].entrant, 1);
+    assert!(results.standings[2].elo < INITIAL_ELO);
+    assert!(results.standings[0].elo > INITIAL_ELO);
+
+    assert_eq!(results.matchups.len(), 6);
+    assert_eq!(
+        results.matchups[0],
+        Matchup {
+            entrant: 0,
+            opponent: 1,
+            wins: 2,
+            losses: 0,
+            draws: 0
+        }
+    );
+}
+
+#[test]
+fn multiplayer_matches() {
+    let config = TournamentConfig {
+        max_players: 3,
+        ..TournamentConfig::default()
+    };
+    let results = run_tournament(&entrants(), &config).unwrap();
+
+    assert_eq!(results.matches.len(), 8);
+    assert_eq!(results.matches[6].seats, [0, 1, 2]);
+    assert_eq!(results.matches[7].seats, [2, 1, 0]);
+    // Only 2-player matches count in the matchups
+    assert_eq!(results.matchups.len(), 6);
+}
+
+#[test]
+fn seeded_matches() {
+    let config = TournamentConfig {
+        seeds: vec![7, 42],
+        ..TournamentConfig::default()
+    };
+    let results = run_tournament(&entrants(), &config).unwrap();
+
+    // Every seating once per seed
+    assert_eq!(results.matches.len(), 12);
+    let seatings: Vec<_> = results.matches[..4]
+        .iter()
+        .map(|result| (result.seats.clone(), result.seed))
+        .collect();
+    assert_eq!(
+        seatings,
+        [
+            (vec![0, 1], Some(7)),
+            (vec![0, 1], Some(42)),
+            (vec![1, 0], Some(7)),
+            (vec![1, 0], Some(42)),
+        ]
+    );
+    assert_eq!(results, run_tournament(&entrants(), &config).unwrap());
+
+    let unseeded = run_tournament(&entrants(), &TournamentConfig::default()).unwrap();
+    assert!(unseeded.matches.iter().all(|result| result.seed.is_none()));
+}
+
+#[test]
+fn threads_do_not_change_the_results() {
+    let results_with = |threads| {
+        let config = TournamentConfig {
+            max_players: 3,
+            threads,
+            ..TournamentConfig::default()
+        };
+        run_tournament(&entrants(), &config).unwrap()
+    };
+
+    assert_eq!(results_with(1), results_with(4));
+}
+
+#[test]
+fn invalid_tournaments() {
+    let config = TournamentConfig {
+        max_players: 5,
+        ..TournamentConfig::default()
+    };
+    assert_matches!(
+        run_tournament(&entrants(), &config).err(),
+        Some(TournamentError::InvalidPlayerCount(5))
+    );
+
+    let mut entrants = entrants();
+    entrants.push(Entrant {
+        name: String::from("broken"),
+        champion: vec![0; 4],
+    });
+    assert_matches!(
+        run_tournament(&entrants, &TournamentConfig::default()).err(),
+        Some(TournamentError::InvalidChampion(name, _)) if name == "broken"
+    );
+}
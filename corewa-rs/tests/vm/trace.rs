@@ -0,0 +1,113 @@
+use super::compile;
+use corewa_rs::vm::{
+    trace::{self, RegisterChange, TraceEntry, TraceError, TraceFormat, TraceRecorder, TraceWrite},
+    VirtualMachine,
+};
+use std::io::ErrorKind;
+
+const LOADER: &str = r#"
+.name "loader"
+.comment "loads and stores"
+ld  %7, r2
+st  r2, 20
+ld  %0, r2
+"#;
+
+fn record(cycles: u32, format: TraceFormat) -> Vec<u8> {
+    let mut vm = VirtualMachine::new();
+    vm.load_players(&[(1, compile(LOADER))]).unwrap();
+    let mut recorder = TraceRecorder::new(Vec::new(), format).unwrap();
+    while vm.cycles < cycles {
+        vm.tick_with(&mut recorder);
+    }
+    recorder.finish().unwrap()
+}
+
+fn entries(cycles: u32) -> Vec<TraceEntry> {
+    trace::read_binary(&record(cycles, TraceFormat::Binary)[..]).unwrap()
+}
+
+#[test]
+fn records_instruction_effects() {
+    let entries = entries(30);
+
+    let ld = &entries[0];
+    assert_eq!((ld.cycle, ld.pid, ld.pc), (4, 0, 0));
+    assert_eq!(
+        ld.register_changes,
+        [RegisterChange {
+            reg: 2,
+            old: 0,
+            new: 7
+        }]
+    );
+    assert_eq!(ld.zf_change, None);
+
+    let text = "4 0 0 0 ld %7, r2 | r2 0 -> 7\n\
+                9 0 0 7 st r2, 20 | mem 27 00 00 00 07\n\
+                14 0 0 12 ld %0, r2 | r2 7 -> 0 | zf 0 -> 1\n";
+    assert_eq!(
+        String::from_utf8(record(30, TraceFormat::Text)).unwrap(),
+        text
+    );
+
+    let mut written = Vec::new();
+    trace::write_text(&entries, &mut written).unwrap();
+    assert_eq!(String::from_utf8(written).unwrap(), text);
+}
+
+#[test]
+fn binary_round_trip() {
+    let bytes = record(30, TraceFormat::Binary);
+
+    let mut written = Vec::new();
+    trace::write_binary(&entries(30), &mut written).unwrap();
+    assert_eq!(written, bytes);
+}
+
+#[test]
+fn oversized_binary_entries() {
+    let mut entry = entries(30).remove(0);
+    entry.writes = vec![
+        TraceWrite {
+            addr: 0,
+            bytes: vec![0; 4],
+        };
+        256
+    ];
+
+    let error = trace::write_binary(&[entry], Vec::new()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn invalid_binary_traces() {
+    assert_matches!(
+        trace::read_binary(&b"CWVM"[..]),
+        Err(TraceError::InvalidMagic)
+    );
+    assert_matches!(
+        trace::read_binary(&b"CWTR\x00\x03"[..]),
+        Err(TraceError::UnsupportedVersion(3))
+    );
+
+    let mut bytes = record(30, TraceFormat::Binary);
+    assert_matches!(
+        trace::read_binary(&bytes[..bytes.len() - 1]),
+        Err(TraceError::IOError(_))
+    );
+
+    let mut wrong_count = bytes.clone();
+    *wrong_count.last_mut().unwrap() += 1;
+    assert_matches!(
+        trace::read_binary(&wrong_count[..]),
+        Err(TraceError::InvalidData(_))
+    );
+
+    // The op code of the first entry
+    bytes[6 + 1 + 4 + 4 + 1 + 4] = 0x42;
+    assert_matches!(
+        trace::read_binary(&bytes[..]),
+        Err(TraceError::InvalidData(_))
+    );
+}
@@ -3,7 +3,7 @@ import CodeMirror from "codemirror";
 
 import { observer } from "mobx-react-lite";
 
-import { CompileError } from "corewa-rs";
+import { CompileError, Diagnostic, Region, Severity } from "corewa-rs";
 import { CorewarPlayer } from "../state/player";
 import { champions } from "../assets/champions";
 import { autorun } from "mobx";
@@ -94,32 +94,61 @@ CodeMirror.registerHelper(
   ASM_LANGUAGE_ID,
   (code: string, { player }: { player: CorewarPlayer }) => {
     try {
-      player.compile(code);
-      return [];
+      return player.compile(code).flatMap(lintAnnotations);
     } catch (err) {
       if (!(err instanceof CompileError)) return;
 
-      const region = err.region();
-      const [from_row, from_col, to_row, to_col] = region
-        ? [
-            region.from_row - 1,
-            region.from_col,
-            region.to_row - 1,
-            region.to_col,
-          ]
-        : [0, 0, 5000, 5000];
-
-      return [
-        {
-          from: CodeMirror.Pos(from_row, from_col),
-          to: CodeMirror.Pos(to_row, from_col == to_col ? to_col + 1 : to_col),
-          message: err.reason(),
-        },
-      ];
+      return err.diagnostics().flatMap(lintAnnotations);
     }
   }
 );
 
+// Diagnostics inside macros are also shown on the invocations they come from,
+// and those coming from constants on their definitions.
+// The editor has no files to include, a diagnostic located in another file is
+// only shown over the whole editor, its regions being elsewhere
+function lintAnnotations(diagnostic: Diagnostic) {
+  const severity =
+    diagnostic.severity === Severity.Warning ? "warning" : "error";
+  const message = diagnostic.message();
+  const file = diagnostic.file();
+
+  if (file !== undefined) {
+    return [lintAnnotation(undefined, `In ${file}: ${message}`, severity)];
+  }
+
+  return [
+    lintAnnotation(diagnostic.region(), message, severity),
+    ...diagnostic
+      .call_sites()
+      .map((region) =>
+        lintAnnotation(region, `In this macro: ${message}`, severity)
+      ),
+    ...diagnostic
+      .constant_definitions()
+      .map((region) =>
+        lintAnnotation(region, `In this constant: ${message}`, severity)
+      ),
+  ];
+}
+
+function lintAnnotation(
+  region: Region | undefined,
+  message: string,
+  severity: string
+) {
+  const [from_row, from_col, to_row, to_col] = region
+    ? [region.from_row - 1, region.from_col, region.to_row - 1, region.to_col]
+    : [0, 0, 5000, 5000];
+
+  return {
+    from: CodeMirror.Pos(from_row, from_col),
+    to: CodeMirror.Pos(to_row, from_col == to_col ? to_col + 1 : to_col),
+    message,
+    severity,
+  };
+}
+
 CodeMirror.registerHelper(
   "hint",
   ASM_LANGUAGE_ID,
@@ -157,7 +186,13 @@ const COMMENT_CHAR = "#";
 CodeMirror.defineMode(ASM_LANGUAGE_ID, function (_config, _parserConfig) {
   const lineCommentStartSymbol = COMMENT_CHAR;
 
-  const directives = new Set([".name", ".comment", ".code"]);
+  const directives = new Set([
+    ".name",
+    ".comment",
+    ".code",
+    ".equ",
+    ".include",
+  ]);
 
   const KEYWORDS = new Set(ALL_KEYWORDS.map(([kw, ..._]) => kw));
 
@@ -197,11 +232,13 @@ CodeMirror.defineMode(ASM_LANGUAGE_ID, function (_config, _parserConfig) {
 
       if (ch === ".") {
         stream.eatWhile(/\w/);
+        if (stream.eat(":")) return "tag";
         const cur = stream.current().toLowerCase();
         if (directives.has(cur)) return "builtin";
       }
 
       if (ch === ":") {
+        stream.eat(".");
         stream.eatWhile(/\w/);
         return "tag";
       }
@@ -232,6 +269,7 @@ CodeMirror.defineMode(ASM_LANGUAGE_ID, function (_config, _parserConfig) {
 
       if (/\d/.test(ch)) {
         stream.eatWhile(/\d/);
+        if (stream.eat(":")) return "tag";
         return "number";
       }
 
@@ -1,6 +1,6 @@
 import { makeObservable, observable, action } from "mobx";
 
-import { compile_champion } from "corewa-rs";
+import { compile_champion, Diagnostic } from "corewa-rs";
 
 import { Game } from "./game";
 
@@ -39,7 +39,8 @@ export class CorewarPlayer {
     });
   }
 
-  compile(newCode: string) {
+  /** Returns the warnings, throws a `CompileError` if there were errors */
+  compile(newCode: string): Diagnostic[] {
     this.code = newCode;
     this.champion = undefined;
 
@@ -51,6 +52,8 @@ export class CorewarPlayer {
       byteCode: champion.byte_code(),
       codeSize: champion.code_size,
     };
+
+    return champion.warnings();
   }
 
   delete() {
@@ -140,13 +140,17 @@ export class VirtualMachine {
     this.pause();
     this.matchResult = undefined;
     this.engine.release();
-    this.engine = this.players
-      .reduce(
-        (builder, player) =>
-          builder.with_player(player.id, player.champion.byteCode),
-        new VMBuilder()
-      )
-      .finish();
+    const builder = this.players.reduce(
+      (builder, player) =>
+        builder.with_player(player.id, player.champion.byteCode, undefined),
+      new VMBuilder()
+    );
+    try {
+      this.engine = builder.finish();
+    } catch (error) {
+      console.error(error);
+      this.engine = new VMBuilder().finish();
+    }
 
     this.cycles = this.engine.cycles();
     this.coverages = this.engine.coverages();